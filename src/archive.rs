@@ -0,0 +1,75 @@
+//! Reading ROMs out of zip and gzip archives.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::emulator::MAX_ROM_SIZE;
+use crate::savestate::invalid_data;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+
+/// Read a ROM from `path`, which can be a plain ROM, a gzip-compressed ROM or a zip archive.
+///
+/// From a zip archive, this reads the entry called `entry`, or without one, the first
+/// entry with a `.gb` or `.gbc` extension. Archives are recognized by their contents,
+/// not their file extension.
+pub fn read_rom(path: &Path, entry: Option<&str>) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut magic = Vec::new();
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    if magic.starts_with(ZIP_MAGIC) {
+        read_zip_entry(file, entry)
+    } else if entry.is_some() {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "not a zip archive, but an entry was given"))
+    } else if magic.starts_with(GZIP_MAGIC) {
+        read_limited(GzDecoder::new(file))
+    } else {
+        read_limited(file)
+    }
+}
+
+fn read_zip_entry(file: File, entry: Option<&str>) -> io::Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| invalid_data(&e.to_string()))?;
+    let name = match entry {
+        Some(name) => name.to_string(),
+        None => first_rom_name(&mut archive)?
+            .ok_or_else(|| invalid_data("no .gb or .gbc file in the zip archive"))?,
+    };
+    let file = archive.by_name(&name)
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, format!("'{}' in zip archive: {}", name, e)))?;
+    read_limited(file)
+}
+
+/// The name of the first entry in the archive with a ROM's extension.
+fn first_rom_name(archive: &mut ZipArchive<File>) -> io::Result<Option<String>> {
+    for i in 0..archive.len() {
+        let name = archive.by_index(i)
+            .map_err(|e| invalid_data(&e.to_string()))?
+            .name()
+            .to_string();
+        let lowercase = name.to_ascii_lowercase();
+        if lowercase.ends_with(".gb") || lowercase.ends_with(".gbc") {
+            return Ok(Some(name));
+        }
+    }
+    Ok(None)
+}
+
+/// Read everything from `r`, failing if it's larger than any ROM
+/// rather than filling memory with a bogus or malicious archive.
+fn read_limited(r: impl Read) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    r.take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut data)?;
+    if data.len() > MAX_ROM_SIZE {
+        return Err(invalid_data(&format!("ROM is larger than any cartridge ({} bytes)", MAX_ROM_SIZE)));
+    }
+    Ok(data)
+}
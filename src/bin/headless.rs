@@ -0,0 +1,168 @@
+//! Runs a ROM without a window, for CI and tooling.
+
+use feox::archive;
+use feox::cheats::Cheats;
+use feox::gameboy::GameBoy;
+use feox::model::Model;
+use feox::movie::Movie;
+use feox::patch;
+use feox::symbols::{self, Symbols};
+use feox::trace::{self, Trace};
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const USAGE: &str = "usage: feox-headless [options] [bootrom] [rom]
+
+options:
+    --model dmg0|dmg|mgb|sgb|cgb|agb  hardware model to emulate (default: dmg)
+    --frames N                        number of frames to run (default: 600, or the length of the movie)
+    --play FILE                       replay the joypad input of a movie file
+    --dump FILE                       write the final screen to FILE as a PPM image, or PNG
+                                      if FILE ends in .png
+    --trace FILE                      log the CPU state before every instruction, in Gameboy Doctor format
+    --trace-pc START-END              only trace instructions in this hexadecimal address range
+    --trace-after N                   only trace once N machine cycles have run
+    --trace-symbols                   append the label of PC to trace lines
+    --sym FILE                        load labels from an RGBDS or no$gmb symbol file
+                                      (default: the ROM's path with a .sym extension, if it exists)
+    --entry NAME                      file to load from a zip archive (default: the first .gb or .gbc file)
+    --patch FILE                      apply an IPS, UPS or BPS patch to the ROM (default: the ROM's
+                                      path with a .ips, .ups or .bps extension, if one exists)
+    --cheats FILE                     apply the enabled cheat codes in FILE
+
+The CRC32 of the final screen is printed on exit.";
+
+fn main() -> Result<(), String> {
+    let mut model = Model::default();
+    let mut frames = None;
+    let mut play_path: Option<String> = None;
+    let mut dump_path: Option<String> = None;
+    let mut trace_path: Option<String> = None;
+    let mut trace_pc = None;
+    let mut trace_after = 0;
+    let mut trace_symbols = false;
+    let mut sym_path: Option<PathBuf> = None;
+    let mut cheats_path: Option<String> = None;
+    let mut patch_path: Option<PathBuf> = None;
+    let mut entry: Option<String> = None;
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--model" => model = parse_option(&mut args, &arg)?,
+            "--frames" => frames = Some(parse_option(&mut args, &arg)?),
+            "--play" => play_path = Some(parse_option(&mut args, &arg)?),
+            "--dump" => dump_path = Some(parse_option(&mut args, &arg)?),
+            "--trace" => trace_path = Some(parse_option(&mut args, &arg)?),
+            "--trace-pc" => trace_pc = Some(trace::parse_pc_range(&parse_option::<String>(&mut args, &arg)?)?),
+            "--trace-after" => trace_after = parse_option(&mut args, &arg)?,
+            "--trace-symbols" => trace_symbols = true,
+            "--sym" => sym_path = Some(parse_option::<String>(&mut args, &arg)?.into()),
+            "--entry" => entry = Some(parse_option(&mut args, &arg)?),
+            "--patch" => patch_path = Some(parse_option::<String>(&mut args, &arg)?.into()),
+            "--cheats" => cheats_path = Some(parse_option(&mut args, &arg)?),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() || paths.len() > 2 {
+        eprintln!("{}", USAGE);
+        std::process::exit(-1);
+    };
+
+    let mut gb = GameBoy::new(model);
+    let rom_path = paths.last().unwrap();
+    let mut rom = archive::read_rom(Path::new(rom_path), entry.as_deref())
+        .map_err(|e| format!("failed to read rom '{}': {}", rom_path, e))?;
+    if let Some(path) = patch_path.or_else(|| patch::path_for_rom(Path::new(rom_path))) {
+        rom = fs::read(&path).and_then(|data| patch::apply(&rom, &data))
+            .map_err(|e| format!("failed to apply patch '{}': {}", path.display(), e))?;
+        eprintln!("Applied patch '{}'", path.display());
+    }
+    gb.load_rom(&rom)
+        .map_err(|e| format!("failed to load rom '{}': {}", rom_path, e))?;
+    if paths.len() == 2 {
+        File::open(&paths[0]).and_then(|mut f| gb.load_bootrom(&mut f))
+            .map_err(|e| format!("failed to read bootrom '{}': {}", paths[0], e))?;
+    } else {
+        gb.skip_boot();
+    }
+
+    if let Some(path) = sym_path.or_else(|| symbols::path_for_rom(Path::new(rom_path))) {
+        gb.symbols = Symbols::load_file(&path)
+            .map_err(|e| format!("failed to load symbols '{}': {}", path.display(), e))?;
+    }
+
+    if let Some(path) = cheats_path {
+        gb.cheats = Cheats::load_file(Path::new(&path))
+            .map_err(|e| format!("failed to load cheats '{}': {}", path, e))?;
+    }
+
+    if let Some(path) = trace_path {
+        let file = File::create(&path)
+            .map_err(|e| format!("failed to create trace '{}': {}", path, e))?;
+        let mut trace = Trace::new(Box::new(BufWriter::new(file)));
+        trace.pc_range = trace_pc;
+        trace.start_cycle = trace_after;
+        trace.show_symbols = trace_symbols;
+        gb.trace = Some(trace);
+    }
+
+    let movie = match play_path {
+        Some(path) => {
+            let movie = File::open(&path).and_then(|mut f| Movie::load(&mut f))
+                .map_err(|e| format!("failed to load movie '{}': {}", path, e))?;
+            movie.rewind_to_start(&mut gb.cpu, &mut gb.emulator)
+                .map_err(|e| format!("failed to start movie '{}': {}", path, e))?;
+            Some(movie)
+        }
+        None => None,
+    };
+    let frames = frames.unwrap_or_else(|| movie.as_ref().map_or(600, |m| m.len()));
+    // Movies don't store cheats, so they would only make the replay desync
+    if movie.is_some() && !gb.cheats.is_empty() {
+        gb.cheats.set_all_enabled(false);
+        eprintln!("Cheats are disabled while playing a movie");
+    }
+
+    let mut desynced = false;
+    for frame in 0..frames {
+        if let Some(movie) = &movie {
+            gb.set_buttons(movie.buttons(frame).unwrap_or(0));
+        }
+        gb.run_frame();
+        if let Some(movie) = &movie {
+            if let Err(desync) = movie.verify_frame(frame, gb.shades()) {
+                eprintln!("{}", desync);
+                desynced = true;
+            }
+        }
+    }
+
+    if let Some(path) = dump_path {
+        if path.to_ascii_lowercase().ends_with(".png") {
+            gb.save_screenshot(Path::new(&path))
+        } else {
+            File::create(&path).and_then(|mut f| {
+                write!(f, "P6\n160 144\n255\n")?;
+                f.write_all(gb.framebuffer())
+            })
+        }.map_err(|e| format!("failed to write '{}': {}", path, e))?;
+    }
+    println!("{:08X}", crc32fast::hash(gb.framebuffer()));
+
+    if desynced {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn parse_option<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<T, String>
+    where T::Err: std::fmt::Display
+{
+    let value = args.next().ok_or(format!("{} expects an argument", flag))?;
+    value.parse().map_err(|e| format!("invalid value '{}' for {}: {}", value, flag, e))
+}
@@ -0,0 +1,291 @@
+use sdl2::controller;
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::config::Config;
+use crate::joypad::Button;
+
+/// The bindings used when the config file doesn't change them, in the config file's format.
+///
+/// Keys are named the way SDL names them, optionally with `Shift+`, `Ctrl+` or `Alt+` in front,
+/// and controller buttons use SDL's GameController names. Several keys or buttons can be bound
+/// to the same thing by separating them with commas, and an empty value unbinds it.
+pub const DEFAULT_BINDINGS: &str = "\
+[keyboard]
+up = Up
+down = Down
+left = Left
+right = Right
+a = A
+b = B
+select = Space
+start = Return
+
+# Game Boy A and B are on the right and bottom face buttons, like on the Game Boy.
+# The left stick also works as a D-pad once pushed further than the deadzone (0-32767).
+[controller]
+deadzone = 8000
+up = dpup
+down = dpdown
+left = dpleft
+right = dpright
+a = b
+b = a
+select = back
+start = start
+
+[hotkeys]
+quit = Escape
+pause = F12
+debug_output = Q
+rewind = Backspace
+fast_forward = Tab
+fast_forward_toggle = Ctrl+Tab
+slow_motion = Shift+Tab
+pause_game = P
+frame_advance = N
+show_fps = Ctrl+F
+show_input = Ctrl+I
+fullscreen = Alt+Return
+scaling = Alt+S
+filter = Alt+F
+ghosting = Alt+G
+palette = Alt+P
+screenshot = PrintScreen
+screenshot_scaled = Shift+PrintScreen
+record_video = Alt+R
+cheats = F10
+vram_viewer = F11
+load_state_1 = F1
+load_state_2 = F2
+load_state_3 = F3
+load_state_4 = F4
+load_state_5 = F5
+load_state_6 = F6
+load_state_7 = F7
+load_state_8 = F8
+load_state_9 = F9
+save_state_1 = Shift+F1
+save_state_2 = Shift+F2
+save_state_3 = Shift+F3
+save_state_4 = Shift+F4
+save_state_5 = Shift+F5
+save_state_6 = Shift+F6
+save_state_7 = Shift+F7
+save_state_8 = Shift+F8
+save_state_9 = Shift+F9
+";
+
+/// Frontend actions that can be bound to keys.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Hotkey {
+    Quit,
+    /// Pause in the debugger console
+    Pause,
+    /// Toggle printing the CPU state before every instruction
+    DebugOutput,
+    /// Rewind while held
+    Rewind,
+    /// Run at the fast-forward speed while held
+    FastForward,
+    FastForwardToggle,
+    /// Turn slow motion on or off
+    SlowMotion,
+    /// Stop or resume running frames, without entering the debugger
+    PauseGame,
+    /// Run a single frame while the game is paused
+    FrameAdvance,
+    /// Show or hide the FPS counter
+    ShowFps,
+    /// Show or hide the held joypad buttons
+    ShowInput,
+    Fullscreen,
+    /// Switch to the next scaling mode
+    Scaling,
+    /// Switch to the next filter
+    Filter,
+    /// Turn LCD ghosting on or off
+    Ghosting,
+    /// Switch to the next palette
+    Palette,
+    /// Save the screen as a 160x144 PNG
+    Screenshot,
+    /// Save the screen as shown, filtered and scaled
+    ScreenshotScaled,
+    /// Start or stop recording video
+    RecordVideo,
+    /// Turn all cheats on or off
+    Cheats,
+    VramViewer,
+    LoadState(u8),
+    SaveState(u8),
+}
+
+impl Hotkey {
+    fn from_name(name: &str) -> Option<Self> {
+        let slot = |prefix: &str| name.strip_prefix(prefix)
+            .and_then(|slot| slot.parse::<u8>().ok())
+            .filter(|slot| (1..=9).contains(slot));
+        match name {
+            "quit" => Some(Hotkey::Quit),
+            "pause" => Some(Hotkey::Pause),
+            "debug_output" => Some(Hotkey::DebugOutput),
+            "rewind" => Some(Hotkey::Rewind),
+            "fast_forward" => Some(Hotkey::FastForward),
+            "fast_forward_toggle" => Some(Hotkey::FastForwardToggle),
+            "slow_motion" => Some(Hotkey::SlowMotion),
+            "pause_game" => Some(Hotkey::PauseGame),
+            "frame_advance" => Some(Hotkey::FrameAdvance),
+            "show_fps" => Some(Hotkey::ShowFps),
+            "show_input" => Some(Hotkey::ShowInput),
+            "fullscreen" => Some(Hotkey::Fullscreen),
+            "scaling" => Some(Hotkey::Scaling),
+            "filter" => Some(Hotkey::Filter),
+            "ghosting" => Some(Hotkey::Ghosting),
+            "palette" => Some(Hotkey::Palette),
+            "screenshot" => Some(Hotkey::Screenshot),
+            "screenshot_scaled" => Some(Hotkey::ScreenshotScaled),
+            "record_video" => Some(Hotkey::RecordVideo),
+            "cheats" => Some(Hotkey::Cheats),
+            "vram_viewer" => Some(Hotkey::VramViewer),
+            _ => slot("load_state_").map(Hotkey::LoadState)
+                .or_else(|| slot("save_state_").map(Hotkey::SaveState)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Joypad(Button),
+    Hotkey(Hotkey),
+}
+
+/// A key, along with the modifiers that must be held for it to count.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Key {
+    keycode: Keycode,
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+}
+
+impl Key {
+    fn parse(s: &str) -> Result<Self, String> {
+        let (mut shift, mut ctrl, mut alt) = (false, false, false);
+        let mut name = s;
+        // A trailing + is part of the key's name, as in "Keypad +"
+        while let Some(plus) = name.find('+').filter(|&i| i + 1 < name.len()) {
+            match name[..plus].to_ascii_lowercase().as_str() {
+                "shift" => shift = true,
+                "ctrl" => ctrl = true,
+                "alt" => alt = true,
+                _ => break,
+            }
+            name = &name[plus + 1..];
+        }
+        let keycode = Keycode::from_name(name).ok_or_else(|| format!("unknown key '{}'", s))?;
+        Ok(Key { keycode, shift, ctrl, alt })
+    }
+
+    /// Whether the modifiers this key needs are held; other modifiers don't matter.
+    fn modifiers_held(&self, keymod: Mod) -> bool {
+        (!self.shift || keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD))
+            && (!self.ctrl || keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD))
+            && (!self.alt || keymod.intersects(Mod::LALTMOD | Mod::RALTMOD))
+    }
+
+    fn modifier_count(&self) -> usize {
+        self.shift as usize + self.ctrl as usize + self.alt as usize
+    }
+}
+
+/// What keys and controller buttons do, from the `keyboard`, `controller` and `hotkeys`
+/// sections of the config file on top of `DEFAULT_BINDINGS`.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    keys: Vec<(Key, Action)>,
+    buttons: Vec<(controller::Button, Button)>,
+    deadzone: i16,
+}
+
+impl Bindings {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let mut merged = Config::load(&mut DEFAULT_BINDINGS.as_bytes(), None)
+            .expect("default bindings are valid");
+        merged.merge(config.clone());
+
+        let mut bindings = Self { keys: Vec::new(), buttons: Vec::new(), deadzone: 0 };
+        for (name, value) in merged.section("keyboard") {
+            let button = joypad_button(name).ok_or_else(|| format!("unknown button '{}' in [keyboard]", name))?;
+            for key in split_list(value) {
+                bindings.keys.push((Key::parse(key)?, Action::Joypad(button)));
+            }
+        }
+        for (name, value) in merged.section("hotkeys") {
+            let hotkey = Hotkey::from_name(name).ok_or_else(|| format!("unknown hotkey '{}' in [hotkeys]", name))?;
+            for key in split_list(value) {
+                bindings.keys.push((Key::parse(key)?, Action::Hotkey(hotkey)));
+            }
+        }
+        for (name, value) in merged.section("controller") {
+            if name == "deadzone" {
+                bindings.deadzone = value.parse().ok().filter(|&deadzone: &i16| deadzone >= 0)
+                    .ok_or_else(|| format!("invalid deadzone '{}' in [controller]", value))?;
+                continue;
+            }
+            let button = joypad_button(name).ok_or_else(|| format!("unknown button '{}' in [controller]", name))?;
+            for pad_button in split_list(value) {
+                let pad_button = controller::Button::from_string(pad_button)
+                    .ok_or_else(|| format!("unknown controller button '{}'", pad_button))?;
+                bindings.buttons.push((pad_button, button));
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// The action for a key press. When several bindings use the key, the one
+    /// needing the most of the held modifiers wins, so Shift+F1 beats F1.
+    pub fn key_down(&self, keycode: Keycode, keymod: Mod) -> Option<Action> {
+        self.keys.iter()
+            .filter(|(key, _)| key.keycode == keycode && key.modifiers_held(keymod))
+            .max_by_key(|(key, _)| key.modifier_count())
+            .map(|&(_, action)| action)
+    }
+
+    /// Every action bound to a key, whatever the modifiers, so releasing a key
+    /// ends what pressing it started even if a modifier changed in between.
+    pub fn key_up(&self, keycode: Keycode) -> impl Iterator<Item = Action> + '_ {
+        self.keys.iter()
+            .filter(move |(key, _)| key.keycode == keycode)
+            .map(|&(_, action)| action)
+    }
+
+    /// The joypad buttons bound to a controller button.
+    pub fn controller_button(&self, pad_button: controller::Button) -> impl Iterator<Item = Button> + '_ {
+        self.buttons.iter()
+            .filter(move |(bound, _)| *bound == pad_button)
+            .map(|&(_, button)| button)
+    }
+
+    /// How far an analog stick must be pushed before it counts as a D-pad direction.
+    pub fn deadzone(&self) -> i16 {
+        self.deadzone
+    }
+}
+
+fn joypad_button(name: &str) -> Option<Button> {
+    match name {
+        "up" => Some(Button::Up),
+        "down" => Some(Button::Down),
+        "left" => Some(Button::Left),
+        "right" => Some(Button::Right),
+        "a" => Some(Button::A),
+        "b" => Some(Button::B),
+        "select" => Some(Button::Select),
+        "start" => Some(Button::Start),
+        _ => None,
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
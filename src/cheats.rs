@@ -0,0 +1,347 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::emulator::Emulator;
+use crate::savestate::invalid_data;
+
+/// A Game Genie code's effect: reads of `address` return `value` instead of what the ROM holds,
+/// but only if the ROM holds `compare` there, so that codes only affect the intended bank.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RomPatch {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl RomPatch {
+    /// The value read from `address` with this patch applied, given the ROM's `original` value.
+    pub fn apply(&self, address: u16, original: u8) -> u8 {
+        if address == self.address && self.compare.iter().all(|&compare| compare == original) {
+            self.value
+        } else {
+            original
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Code {
+    /// `ABC-DEF` or `ABC-DEF-GHI`, patching ROM reads
+    GameGenie(RomPatch),
+    /// `BBVVLLHH`, writing `value` to `address` every frame.
+    ///
+    /// For cartridge RAM the code only applies while `bank` is mapped, except for
+    /// bank 01, which most codes use and which stands for whichever bank is mapped.
+    GameShark { bank: u8, value: u8, address: u16 },
+}
+
+impl FromStr for Code {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid cheat code '{}'", s);
+        let digits = s.chars()
+            .filter(|&c| c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u16))
+            .collect::<Option<Vec<u16>>>()
+            .ok_or_else(invalid)?;
+
+        match digits[..] {
+            // Game Genie: AB is the new value, FCDE the address XORed with F000,
+            // and GI the compared value XORed with BA and rotated left by 2
+            [a, b, c, d, e, f] | [a, b, c, d, e, f, _, _, _] if s.contains('-') => {
+                let address = ((f ^ 0xF) << 12) | c << 8 | d << 4 | e;
+                if address > 0x7FFF {
+                    return Err(format!("Game Genie code '{}' doesn't patch ROM", s));
+                }
+                let compare = match digits[..] {
+                    [_, _, _, _, _, _, g, _, i] => Some(((g << 4 | i) as u8).rotate_right(2) ^ 0xBA),
+                    _ => None,
+                };
+                Ok(Code::GameGenie(RomPatch { address, value: (a << 4 | b) as u8, compare }))
+            }
+            [b1, b2, v1, v2, l1, l2, h1, h2] if !s.contains('-') => {
+                let address = h1 << 12 | h2 << 8 | l1 << 4 | l2;
+                if address < 0x8000 {
+                    return Err(format!("GameShark code '{}' writes to ROM", s));
+                }
+                Ok(Code::GameShark { bank: (b1 << 4 | b2) as u8, value: (v1 << 4 | v2) as u8, address })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cheat {
+    pub code: Code,
+    /// The code as the user wrote it
+    pub text: String,
+    pub name: String,
+    pub enabled: bool,
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "{} {}", if self.enabled { "on" } else { "off" }, self.text)?;
+        if !self.name.is_empty() {
+            write!(w, " {}", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// The cheats for a ROM, kept in a text file next to it with one cheat per line:
+///
+/// `on 01FF34C1 Infinite lives`
+///
+/// Cheats have no effect while disabled, individually or all at once, so that
+/// save states and movies stay the same as without them.
+#[derive(Debug, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+    enabled: bool,
+    modified: bool,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn load(r: &mut dyn BufRead) -> io::Result<Self> {
+        let mut cheats = Self::new();
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let enabled = match parts.next() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => return Err(invalid_data(&format!("expected 'on' or 'off' on line {}: '{}'", number + 1, line))),
+            };
+            let text = parts.next().unwrap_or("");
+            let name = parts.next().unwrap_or("").trim();
+            let index = cheats.add(text, name)
+                .map_err(|e| invalid_data(&format!("{} on line {}", e, number + 1)))?;
+            cheats.cheats[index].enabled = enabled;
+        }
+        cheats.modified = false;
+        Ok(cheats)
+    }
+
+    pub fn load_file(path: &Path) -> io::Result<Self> {
+        Self::load(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, w: &mut dyn Write) -> io::Result<()> {
+        for cheat in &self.cheats {
+            writeln!(w, "{}", cheat)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_file(&mut self, path: &Path) -> io::Result<()> {
+        self.save(&mut File::create(path)?)?;
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Whether cheats were added, removed or toggled since they were loaded or saved.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Add an enabled cheat, returning its index.
+    pub fn add(&mut self, text: &str, name: &str) -> Result<usize, String> {
+        let code = text.parse()?;
+        self.cheats.push(Cheat { code, text: text.to_uppercase(), name: name.to_string(), enabled: true });
+        self.modified = true;
+        Ok(self.cheats.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        if index >= self.cheats.len() {
+            return None;
+        }
+        self.modified = true;
+        Some(self.cheats.remove(index))
+    }
+
+    /// Enable or disable one cheat, returning false if there's no cheat at `index`.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                self.modified = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    /// Whether cheats are on at all, regardless of which ones are enabled.
+    pub fn all_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_all_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Install the enabled Game Genie codes and perform the writes of the enabled GameShark codes.
+    ///
+    /// Called once per frame; with nothing enabled, this only removes previously installed patches.
+    pub fn apply(&self, emulator: &mut Emulator) {
+        let active = self.cheats.iter().filter(|cheat| self.enabled && cheat.enabled);
+        let mut patches = Vec::new();
+        for cheat in active {
+            match cheat.code {
+                Code::GameGenie(patch) => patches.push(patch),
+                Code::GameShark { bank, value, address } => {
+                    let other_bank = (0xA000..=0xBFFF).contains(&address) && bank != 0x01
+                        && bank as u16 != emulator.mapped_bank(address);
+                    if !other_bank {
+                        emulator.write_mapped(address, value);
+                    }
+                }
+            }
+        }
+        emulator.set_rom_patches(patches);
+    }
+}
+
+/// The cheat file for a ROM: `game.cht` next to `game.gb`.
+pub fn path_for_rom(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("cht")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cheats(text: &str) -> Cheats {
+        Cheats::load(&mut text.as_bytes()).unwrap()
+    }
+
+    fn emulator_with_rom(rom: &[u8]) -> Emulator {
+        let mut emulator = Emulator::default();
+        emulator.load_rom(rom).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn decodes_codes() {
+        assert_eq!("00A-17B".parse(), Ok(Code::GameGenie(RomPatch { address: 0x4A17, value: 0x00, compare: None })));
+        assert_eq!("3ea-02f-c49".parse(),
+                   Ok(Code::GameGenie(RomPatch { address: 0x0A02, value: 0x3E, compare: Some(0xC8) })));
+        assert_eq!("01FF34C1".parse(), Ok(Code::GameShark { bank: 0x01, value: 0xFF, address: 0xC134 }));
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        for code in &["", "00A-17", "00A-17B-C4", "0GA-17B", "01FF3", "01FF34C1-"] {
+            assert!(code.parse::<Code>().is_err(), "{}", code);
+        }
+        // A Game Genie address past the ROM, and a GameShark write to ROM
+        assert!("00A-177".parse::<Code>().is_err());
+        assert!("01FF3412".parse::<Code>().is_err());
+    }
+
+    #[test]
+    fn game_genie_patches_rom_reads() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0A02] = 0xC8;
+        rom[0x4A17] = 0x12;
+        let mut emulator = emulator_with_rom(&rom);
+        let mut cheats = cheats("on 3EA-02F-C49 Compared\non 00A-17B\n");
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.read_mapped(0x0A02), 0x3E);
+        assert_eq!(emulator.read_mapped(0x4A17), 0x00);
+        assert_eq!(emulator.read_mapped(0x4A18), 0x00);
+
+        // The compared value doesn't match, so the code doesn't apply
+        let mut emulator = emulator_with_rom(&vec![0; 0x8000]);
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.read_mapped(0x0A02), 0x00);
+
+        cheats.set_all_enabled(false);
+        let mut emulator = emulator_with_rom(&rom);
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.read_mapped(0x4A17), 0x12);
+    }
+
+    #[test]
+    fn gameshark_writes_every_frame() {
+        // MBC1 with 32 KiB of RAM, enabled
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x03;
+        rom[0x149] = 0x03;
+        let mut emulator = emulator_with_rom(&rom);
+        emulator.write_mapped(0x0000, 0x0A);
+
+        let mut cheats = cheats("on 01FF34C1\noff 0177A0C0\non 0255A1A0\n");
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.read_mapped(0xC134), 0xFF);
+        // Disabled, and for a cartridge RAM bank that isn't mapped
+        assert_eq!(emulator.read_mapped(0xC0A0), 0x00);
+        assert_eq!(emulator.read_mapped(0xA0A1), 0x00);
+
+        // Once that bank is mapped, it applies
+        emulator.write_mapped(0x6000, 0x01);
+        emulator.write_mapped(0x4000, 0x02);
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.read_mapped(0xA0A1), 0x55);
+        assert_eq!(emulator.read_banked(0, 0xA0A1), Some(0x00));
+
+        emulator.write_mapped(0xC134, 0x03);
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.read_mapped(0xC134), 0xFF);
+
+        cheats.set_enabled(0, false);
+        emulator.write_mapped(0xC134, 0x03);
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.read_mapped(0xC134), 0x03);
+    }
+
+    #[test]
+    fn file_round_trips() {
+        let text = "on 01FF34C1 Infinite lives\n# comment\n\noff 00A-17B-C49\n";
+        let mut cheats = cheats(text);
+        assert!(!cheats.is_modified());
+        assert_eq!(cheats.cheats().len(), 2);
+        assert_eq!(cheats.cheats()[0].name, "Infinite lives");
+
+        let index = cheats.add("3ea-02f", "").unwrap();
+        assert!(cheats.is_modified());
+        assert_eq!(cheats.cheats()[index].text, "3EA-02F");
+        let mut saved = Vec::new();
+        cheats.save(&mut saved).unwrap();
+        assert_eq!(String::from_utf8(saved).unwrap(),
+                   "on 01FF34C1 Infinite lives\noff 00A-17B-C49\non 3EA-02F\n");
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for text in &["maybe 01FF34C1", "on", "on 01FF34C", "off XYZ-123"] {
+            assert!(Cheats::load(&mut text.as_bytes()).is_err(), "{}", text);
+        }
+    }
+}
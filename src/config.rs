@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::savestate::invalid_data;
+
+/// Settings from an INI-style config file:
+///
+/// ```ini
+/// # comment
+/// [keyboard]
+/// a = X
+///
+/// [keyboard:tetris]
+/// a = Z
+/// ```
+///
+/// A section named `section:rom` overrides `section` while the ROM whose file name
+/// (without extension) is `rom` is loaded, so bindings can be changed for a single game.
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    /// Keyed by section, then key, in the order they were read
+    values: BTreeMap<String, Vec<(String, String)>>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a config file, keeping the overrides for `rom` and dropping those for other ROMs.
+    pub fn load(r: &mut dyn BufRead, rom: Option<&str>) -> io::Result<Self> {
+        enum Section {
+            Start,
+            Base(String),
+            Override(String),
+            OtherRom,
+        }
+
+        let rom = rom.map(str::to_ascii_lowercase);
+        let mut config = Self::new();
+        let mut overrides = Self::new();
+        let mut section = Section::Start;
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line[1..line.len() - 1].to_ascii_lowercase();
+                let mut parts = name.splitn(2, ':');
+                let name = parts.next().unwrap_or("").trim().to_string();
+                section = match parts.next().map(str::trim) {
+                    None => Section::Base(name),
+                    Some(other) if rom.as_deref() == Some(other) => Section::Override(name),
+                    Some(_) => Section::OtherRom,
+                };
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+                _ => return Err(invalid_data(&format!("expected 'key = value' on line {}: '{}'", number + 1, line))),
+            };
+            match &section {
+                Section::Start => return Err(invalid_data(&format!("setting outside of a section on line {}", number + 1))),
+                Section::Base(name) => config.set(name, &key, value),
+                Section::Override(name) => overrides.set(name, &key, value),
+                Section::OtherRom => (),
+            }
+        }
+        config.merge(overrides);
+        Ok(config)
+    }
+
+    pub fn load_file(path: &Path, rom: Option<&str>) -> io::Result<Self> {
+        Self::load(&mut BufReader::new(File::open(path)?), rom)
+    }
+
+    /// Set `key` in `section`, replacing any previous value.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let entries = self.values.entry(section.to_string()).or_default();
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => entries.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// Take every setting in `other`, replacing the ones already set.
+    pub fn merge(&mut self, other: Config) {
+        for (section, entries) in other.values {
+            for (key, value) in entries {
+                self.set(&section, &key, &value);
+            }
+        }
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section).find(|(k, _)| *k == key).map(|(_, value)| value)
+    }
+
+    /// All settings in a section, as `(key, value)`.
+    pub fn section<'a>(&'a self, section: &str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.values.get(section).into_iter()
+            .flat_map(|entries| entries.iter().map(|(key, value)| (key.as_str(), value.as_str())))
+    }
+}
+
+/// The config file used without `--config`: `$XDG_CONFIG_HOME/feox/config.ini`,
+/// or `~/.config/feox/config.ini` if `XDG_CONFIG_HOME` isn't set.
+pub fn default_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("feox").join("config.ini"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "# Bindings\n\
+                        [keyboard]\n\
+                        a = X\n\
+                        b = Z\n\
+                        \n\
+                        [Keyboard:Tetris]\n\
+                        ; Rotate with the arrow keys\n\
+                        a = Up\n\
+                        start =\n\
+                        \n\
+                        [keyboard:zelda]\n\
+                        a = Space\n\
+                        [speed:tetris]\n\
+                        slow_motion = 0.25\n";
+
+    fn load(rom: Option<&str>) -> Config {
+        Config::load(&mut TEXT.as_bytes(), rom).unwrap()
+    }
+
+    #[test]
+    fn reads_sections() {
+        let config = load(None);
+        assert_eq!(config.get("keyboard", "a"), Some("X"));
+        assert_eq!(config.get("keyboard", "b"), Some("Z"));
+        assert_eq!(config.get("keyboard", "start"), None);
+        assert_eq!(config.get("speed", "slow_motion"), None);
+        assert_eq!(config.section("keyboard").collect::<Vec<_>>(), [("a", "X"), ("b", "Z")]);
+    }
+
+    #[test]
+    fn rom_sections_override() {
+        let config = load(Some("TETRIS"));
+        assert_eq!(config.get("keyboard", "a"), Some("Up"));
+        assert_eq!(config.get("keyboard", "b"), Some("Z"));
+        // An empty value unbinds rather than falling back
+        assert_eq!(config.get("keyboard", "start"), Some(""));
+        assert_eq!(config.get("speed", "slow_motion"), Some("0.25"));
+
+        let config = load(Some("zelda"));
+        assert_eq!(config.get("keyboard", "a"), Some("Space"));
+        assert_eq!(config.get("keyboard", "start"), None);
+    }
+
+    #[test]
+    fn overrides_apply_wherever_they_appear() {
+        let text = "[keyboard:tetris]\na = Up\n[keyboard]\na = X\n";
+        let config = Config::load(&mut text.as_bytes(), Some("tetris")).unwrap();
+        assert_eq!(config.get("keyboard", "a"), Some("Up"));
+    }
+
+    #[test]
+    fn merge_replaces_values() {
+        let mut config = load(None);
+        let mut other = Config::new();
+        other.set("keyboard", "a", "Q");
+        other.set("video", "scale", "3");
+        config.merge(other);
+        assert_eq!(config.get("keyboard", "a"), Some("Q"));
+        assert_eq!(config.get("keyboard", "b"), Some("Z"));
+        assert_eq!(config.get("video", "scale"), Some("3"));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for text in &["a = X\n", "[keyboard]\njust a key\n"] {
+            let error = Config::load(&mut text.as_bytes(), None).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", text);
+        }
+    }
+}
@@ -89,607 +89,633 @@ pub enum JumpCond {
     NotCarry
 }
 
-impl Cpu {
-    pub fn decode(&mut self, emulator: &mut Emulator) {
-        // println!("{:#04X}", opcode);
-        match self.opcode {
-            // nop
-            0x00 => (),
-
-            // 8-bit loads
-            0x02 => self.load(emulator, Addr::BC, Reg8::A),
-            0x0A => self.load(emulator, Reg8::A, Addr::BC),
-            0x12 => self.load(emulator, Addr::DE, Reg8::A),
-            0x1A => self.load(emulator, Reg8::A, Addr::DE),
-            0x22 => {
-                self.load(emulator, Addr::HL, Reg8::A);
-                let hl = self.regs.read_pair(Reg16::HL);
-                self.regs.write_pair(Reg16::HL, hl.wrapping_add(1));
-            }
-            0x2A => {
-                self.load(emulator, Reg8::A, Addr::HL);
-                let hl = self.regs.read_pair(Reg16::HL);
-                self.regs.write_pair(Reg16::HL, hl.wrapping_add(1));
-            }
-            0x32 => {
-                self.load(emulator, Addr::HL, Reg8::A);
-                let hl = self.regs.read_pair(Reg16::HL);
-                self.regs.write_pair(Reg16::HL, hl.wrapping_sub(1));
-            }
-            0x3A => {
-                self.load(emulator, Reg8::A, Addr::HL);
-                let hl = self.regs.read_pair(Reg16::HL);
-                self.regs.write_pair(Reg16::HL, hl.wrapping_sub(1));
-            }
-            0x06 => self.load(emulator, Reg8::B, ImmediateByte),
-            0x0E => self.load(emulator, Reg8::C, ImmediateByte),
-            0x16 => self.load(emulator, Reg8::D, ImmediateByte),
-            0x1E => self.load(emulator, Reg8::E, ImmediateByte),
-            0x26 => self.load(emulator, Reg8::H, ImmediateByte),
-            0x2E => self.load(emulator, Reg8::L, ImmediateByte),
-            0x36 => self.load(emulator, Addr::HL, ImmediateByte),
-            0x3E => self.load(emulator, Reg8::A, ImmediateByte),
-
-            0x40 => self.load(emulator, Reg8::B, Reg8::B),
-            0x41 => self.load(emulator, Reg8::B, Reg8::C),
-            0x42 => self.load(emulator, Reg8::B, Reg8::D),
-            0x43 => self.load(emulator, Reg8::B, Reg8::E),
-            0x44 => self.load(emulator, Reg8::B, Reg8::H),
-            0x45 => self.load(emulator, Reg8::B, Reg8::L),
-            0x46 => self.load(emulator, Reg8::B, Addr::HL),
-            0x47 => self.load(emulator, Reg8::B, Reg8::A),
-
-            0x48 => self.load(emulator, Reg8::C, Reg8::B),
-            0x49 => self.load(emulator, Reg8::C, Reg8::C),
-            0x4A => self.load(emulator, Reg8::C, Reg8::D),
-            0x4B => self.load(emulator, Reg8::C, Reg8::E),
-            0x4C => self.load(emulator, Reg8::C, Reg8::H),
-            0x4D => self.load(emulator, Reg8::C, Reg8::L),
-            0x4E => self.load(emulator, Reg8::C, Addr::HL),
-            0x4F => self.load(emulator, Reg8::C, Reg8::A),
-
-            0x50 => self.load(emulator, Reg8::D, Reg8::B),
-            0x51 => self.load(emulator, Reg8::D, Reg8::C),
-            0x52 => self.load(emulator, Reg8::D, Reg8::D),
-            0x53 => self.load(emulator, Reg8::D, Reg8::E),
-            0x54 => self.load(emulator, Reg8::D, Reg8::H),
-            0x55 => self.load(emulator, Reg8::D, Reg8::L),
-            0x56 => self.load(emulator, Reg8::D, Addr::HL),
-            0x57 => self.load(emulator, Reg8::D, Reg8::A),
-
-            0x58 => self.load(emulator, Reg8::E, Reg8::B),
-            0x59 => self.load(emulator, Reg8::E, Reg8::C),
-            0x5A => self.load(emulator, Reg8::E, Reg8::D),
-            0x5B => self.load(emulator, Reg8::E, Reg8::E),
-            0x5C => self.load(emulator, Reg8::E, Reg8::H),
-            0x5D => self.load(emulator, Reg8::E, Reg8::L),
-            0x5E => self.load(emulator, Reg8::E, Addr::HL),
-            0x5F => self.load(emulator, Reg8::E, Reg8::A),
-
-            0x60 => self.load(emulator, Reg8::H, Reg8::B),
-            0x61 => self.load(emulator, Reg8::H, Reg8::C),
-            0x62 => self.load(emulator, Reg8::H, Reg8::D),
-            0x63 => self.load(emulator, Reg8::H, Reg8::E),
-            0x64 => self.load(emulator, Reg8::H, Reg8::H),
-            0x65 => self.load(emulator, Reg8::H, Reg8::L),
-            0x66 => self.load(emulator, Reg8::H, Addr::HL),
-            0x67 => self.load(emulator, Reg8::H, Reg8::A),
-
-            0x68 => self.load(emulator, Reg8::L, Reg8::B),
-            0x69 => self.load(emulator, Reg8::L, Reg8::C),
-            0x6A => self.load(emulator, Reg8::L, Reg8::D),
-            0x6B => self.load(emulator, Reg8::L, Reg8::E),
-            0x6C => self.load(emulator, Reg8::L, Reg8::H),
-            0x6D => self.load(emulator, Reg8::L, Reg8::L),
-            0x6E => self.load(emulator, Reg8::L, Addr::HL),
-            0x6F => self.load(emulator, Reg8::L, Reg8::A),
-
-            0x70 => self.load(emulator, Addr::HL, Reg8::B),
-            0x71 => self.load(emulator, Addr::HL, Reg8::C),
-            0x72 => self.load(emulator, Addr::HL, Reg8::D),
-            0x73 => self.load(emulator, Addr::HL, Reg8::E),
-            0x74 => self.load(emulator, Addr::HL, Reg8::H),
-            0x75 => self.load(emulator, Addr::HL, Reg8::L),
-            0x77 => self.load(emulator, Addr::HL, Reg8::A),
-
-            0x78 => self.load(emulator, Reg8::A, Reg8::B),
-            0x79 => self.load(emulator, Reg8::A, Reg8::C),
-            0x7A => self.load(emulator, Reg8::A, Reg8::D),
-            0x7B => self.load(emulator, Reg8::A, Reg8::E),
-            0x7C => self.load(emulator, Reg8::A, Reg8::H),
-            0x7D => self.load(emulator, Reg8::A, Reg8::L),
-            0x7E => self.load(emulator, Reg8::A, Addr::HL),
-            0x7F => self.load(emulator, Reg8::A, Reg8::A),
-
-            0xE0 => self.load(emulator, Addr::LDH, Reg8::A),
-            0xE2 => self.load(emulator, Addr::LDHC, Reg8::A),
-            0xF0 => self.load(emulator, Reg8::A, Addr::LDH),
-            0xF2 => self.load(emulator, Reg8::A, Addr::LDHC),
-
-            0xEA => self.load(emulator, Addr::Immediate, Reg8::A),
-            0xFA => self.load(emulator, Reg8::A, Addr::Immediate),
-
-            // 8-bit adds
-            0x80 => self.add(emulator, Reg8::B),
-            0x81 => self.add(emulator, Reg8::C),
-            0x82 => self.add(emulator, Reg8::D),
-            0x83 => self.add(emulator, Reg8::E),
-            0x84 => self.add(emulator, Reg8::H),
-            0x85 => self.add(emulator, Reg8::L),
-            0x86 => self.add(emulator, Addr::HL),
-            0x87 => self.add(emulator, Reg8::A),
-            0xC6 => self.add(emulator, ImmediateByte),
-
-            // 8-bit adds + carry bit
-            0x88 => self.adc(emulator, Reg8::B),
-            0x89 => self.adc(emulator, Reg8::C),
-            0x8A => self.adc(emulator, Reg8::D),
-            0x8B => self.adc(emulator, Reg8::E),
-            0x8C => self.adc(emulator, Reg8::H),
-            0x8D => self.adc(emulator, Reg8::L),
-            0x8E => self.adc(emulator, Addr::HL),
-            0x8F => self.adc(emulator, Reg8::A),
-            0xCE => self.adc(emulator, ImmediateByte),
-
-            // 8-bit subs
-            0x90 => self.sub(emulator, Reg8::B),
-            0x91 => self.sub(emulator, Reg8::C),
-            0x92 => self.sub(emulator, Reg8::D),
-            0x93 => self.sub(emulator, Reg8::E),
-            0x94 => self.sub(emulator, Reg8::H),
-            0x95 => self.sub(emulator, Reg8::L),
-            0x96 => self.sub(emulator, Addr::HL),
-            0x97 => self.sub(emulator, Reg8::A),
-            0xD6 => self.sub(emulator, ImmediateByte),
-
-            // 8-bit subs + carry bit
-            0x98 => self.sbc(emulator, Reg8::B),
-            0x99 => self.sbc(emulator, Reg8::C),
-            0x9A => self.sbc(emulator, Reg8::D),
-            0x9B => self.sbc(emulator, Reg8::E),
-            0x9C => self.sbc(emulator, Reg8::H),
-            0x9D => self.sbc(emulator, Reg8::L),
-            0x9E => self.sbc(emulator, Addr::HL),
-            0x9F => self.sbc(emulator, Reg8::A),
-            0xDE => self.sbc(emulator, ImmediateByte),
-
-            // 8-bit increment
-            0x04 => self.inc(emulator, Reg8::B),
-            0x0C => self.inc(emulator, Reg8::C),
-            0x14 => self.inc(emulator, Reg8::D),
-            0x1C => self.inc(emulator, Reg8::E),
-            0x24 => self.inc(emulator, Reg8::H),
-            0x2C => self.inc(emulator, Reg8::L),
-            0x34 => self.inc(emulator, Addr::HL),
-            0x3C => self.inc(emulator, Reg8::A),
-
-            // 8-bit decrement
-            0x05 => self.dec(emulator, Reg8::B),
-            0x0D => self.dec(emulator, Reg8::C),
-            0x15 => self.dec(emulator, Reg8::D),
-            0x1D => self.dec(emulator, Reg8::E),
-            0x25 => self.dec(emulator, Reg8::H),
-            0x2D => self.dec(emulator, Reg8::L),
-            0x35 => self.dec(emulator, Addr::HL),
-            0x3D => self.dec(emulator, Reg8::A),
-
-            // 16-bit loads
-            0x01 => self.load16(emulator, Reg16::BC, ImmediateWord),
-            0x11 => self.load16(emulator, Reg16::DE, ImmediateWord),
-            0x21 => self.load16(emulator, Reg16::HL, ImmediateWord),
-            0x31 => self.load16(emulator, Reg16::SP, ImmediateWord),
-            0x08 => self.load16(emulator, Addr::Immediate, Reg16::SP),
-            0xF9 => self.load16_sphl(emulator),
-
-            // 16-bit add to hl
-            0x09 => self.add16hl(emulator, Reg16::BC),
-            0x19 => self.add16hl(emulator, Reg16::DE),
-            0x29 => self.add16hl(emulator, Reg16::HL),
-            0x39 => self.add16hl(emulator, Reg16::SP),
-
-            // 16-bit stack pointer instructions
-            0xE8 => self.add16_sp_n(emulator),
-            0xF8 => self.load16_hlsp_n(emulator),
-
-            // 16-bit increments
-            0x03 => self.inc16(emulator, Reg16::BC),
-            0x13 => self.inc16(emulator, Reg16::DE),
-            0x23 => self.inc16(emulator, Reg16::HL),
-            0x33 => self.inc16(emulator, Reg16::SP),
-
-            // 16-bit decrements
-            0x0B => self.dec16(emulator, Reg16::BC),
-            0x1B => self.dec16(emulator, Reg16::DE),
-            0x2B => self.dec16(emulator, Reg16::HL),
-            0x3B => self.dec16(emulator, Reg16::SP),
-
-            // relative jumps
-            0x18 => self.jr(emulator, JumpCond::Always),
-            0x20 => self.jr(emulator, JumpCond::NotZero),
-            0x28 => self.jr(emulator, JumpCond::Zero),
-            0x30 => self.jr(emulator, JumpCond::NotCarry),
-            0x38 => self.jr(emulator, JumpCond::Carry),
-
-            // absolute jumps
-            0xC2 => self.jp(emulator, JumpCond::NotZero, ImmediateWord),
-            0xC3 => self.jp(emulator, JumpCond::Always, ImmediateWord),
-            0xCA => self.jp(emulator, JumpCond::Zero, ImmediateWord),
-            0xD2 => self.jp(emulator, JumpCond::NotCarry, ImmediateWord),
-            0xDA => self.jp(emulator, JumpCond::Carry, ImmediateWord),
-            0xE9 => self.jp_hl(emulator),
-
-            // pop from stack
-            0xC1 => self.pop(emulator, Reg16::BC),
-            0xD1 => self.pop(emulator, Reg16::DE),
-            0xE1 => self.pop(emulator, Reg16::HL),
-            0xF1 => self.pop(emulator, Reg16::AF),
-
-            // push to stack
-            0xC5 => self.push(emulator, Reg16::BC),
-            0xD5 => self.push(emulator, Reg16::DE),
-            0xE5 => self.push(emulator, Reg16::HL),
-            0xF5 => self.push(emulator, Reg16::AF),
-
-            // call
-            0xC4 => self.call(emulator, JumpCond::NotZero),
-            0xCC => self.call(emulator, JumpCond::Zero),
-            0xCD => self.call(emulator, JumpCond::Always),
-            0xD4 => self.call(emulator, JumpCond::NotCarry),
-            0xDC => self.call(emulator, JumpCond::Carry),
-
-            // return
-            0xC0 => self.ret(emulator, JumpCond::NotZero),
-            0xC8 => self.ret(emulator, JumpCond::Zero),
-            0xC9 => self.ret(emulator, JumpCond::Always),
-            0xD0 => self.ret(emulator, JumpCond::NotCarry),
-            0xD8 => self.ret(emulator, JumpCond::Carry),
-            0xD9 => self.reti(emulator, JumpCond::Always),
-
-            // call reset vetor
-            0xC7 => self.rst(emulator, 0x00),
-            0xCF => self.rst(emulator, 0x08),
-            0xD7 => self.rst(emulator, 0x10),
-            0xDF => self.rst(emulator, 0x18),
-            0xE7 => self.rst(emulator, 0x20),
-            0xEF => self.rst(emulator, 0x28),
-            0xF7 => self.rst(emulator, 0x30),
-            0xFF => self.rst(emulator, 0x38),
-
-            // bitwise and
-            0xA0 => self.and(emulator, Reg8::B),
-            0xA1 => self.and(emulator, Reg8::C),
-            0xA2 => self.and(emulator, Reg8::D),
-            0xA3 => self.and(emulator, Reg8::E),
-            0xA4 => self.and(emulator, Reg8::H),
-            0xA5 => self.and(emulator, Reg8::L),
-            0xA6 => self.and(emulator, Addr::HL),
-            0xA7 => self.and(emulator, Reg8::A),
-            0xE6 => self.and(emulator, ImmediateByte),
-
-            // bitwise xor
-            0xA8 => self.xor(emulator, Reg8::B),
-            0xA9 => self.xor(emulator, Reg8::C),
-            0xAA => self.xor(emulator, Reg8::D),
-            0xAB => self.xor(emulator, Reg8::E),
-            0xAC => self.xor(emulator, Reg8::H),
-            0xAD => self.xor(emulator, Reg8::L),
-            0xAE => self.xor(emulator, Addr::HL),
-            0xAF => self.xor(emulator, Reg8::A),
-            0xEE => self.xor(emulator, ImmediateByte),
-
-            // bitwise or
-            0xB0 => self.or(emulator, Reg8::B),
-            0xB1 => self.or(emulator, Reg8::C),
-            0xB2 => self.or(emulator, Reg8::D),
-            0xB3 => self.or(emulator, Reg8::E),
-            0xB4 => self.or(emulator, Reg8::H),
-            0xB5 => self.or(emulator, Reg8::L),
-            0xB6 => self.or(emulator, Addr::HL),
-            0xB7 => self.or(emulator, Reg8::A),
-            0xF6 => self.or(emulator, ImmediateByte),
-
-            // rotations
-            0x07 => self.rlca(emulator, ),
-            0x17 => self.rla(emulator, ),
-            0x0F => self.rrca(emulator, ),
-            0x1F => self.rra(emulator, ),
-
-            // compare
-            0xB8 => self.cp(emulator, Reg8::B),
-            0xB9 => self.cp(emulator, Reg8::C),
-            0xBA => self.cp(emulator, Reg8::D),
-            0xBB => self.cp(emulator, Reg8::E),
-            0xBC => self.cp(emulator, Reg8::H),
-            0xBD => self.cp(emulator, Reg8::L),
-            0xBE => self.cp(emulator, Addr::HL),
-            0xBF => self.cp(emulator, Reg8::A),
-            0xFE => self.cp(emulator, ImmediateByte),
-
-            // complement A register
-            0x2F => self.cpl(emulator),
-
-            // set carry flag
-            0x37 => self.scf(emulator),
-
-            // complement carry flag
-            0x3F => self.ccf(emulator),
-
-            // misc
-            0x10 => self.stop(emulator),
-            0x27 => self.daa(emulator),
-            0x76 => self.halt(emulator),
-            0xF3 => self.di(emulator),
-            0xFB => self.ei(emulator),
-
-            // prefixed instructions
-            0xCB => {
-                self.opcode = self.fetch(emulator);
-                self.prefixed_decode(emulator);
+/// Defines an opcode dispatch method along with a function returning the mnemonic
+/// of each opcode, from a single table of `opcode, "mnemonic" => action` arms.
+/// Deriving both from the same table keeps the disassembler in sync with the CPU.
+///
+/// Operands in mnemonics are written as `d8`/`d16` for immediate data, `a8`/`a16`
+/// for immediate addresses, `r8` for relative jump offsets and `s8` for signed
+/// offsets. Invalid opcodes have an empty mnemonic.
+macro_rules! opcode_table {
+    (fn $decode:ident(&mut $cpu:ident, $emulator:ident);
+     fn $mnemonic:ident;
+     $($($opcode:pat)|+, $text:literal => $action:expr,)*) => {
+        impl Cpu {
+            pub fn $decode(&mut $cpu, $emulator: &mut Emulator) {
+                match $cpu.opcode {
+                    $($($opcode)|+ => $action,)*
+                }
             }
 
-            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD =>
-                panic!("Invalid opcode {:#X}", self.opcode),
+            pub(crate) fn $mnemonic(opcode: u8) -> &'static str {
+                match opcode {
+                    $($($opcode)|+ => $text,)*
+                }
+            }
         }
     }
+}
 
-    pub fn prefixed_decode(&mut self, emulator: &mut Emulator) {
-        match self.opcode {
-            0x00 => self.rlc(emulator, Reg8::B),
-            0x01 => self.rlc(emulator, Reg8::C),
-            0x02 => self.rlc(emulator, Reg8::D),
-            0x03 => self.rlc(emulator, Reg8::E),
-            0x04 => self.rlc(emulator, Reg8::H),
-            0x05 => self.rlc(emulator, Reg8::L),
-            0x06 => self.rlc(emulator, Addr::HL),
-            0x07 => self.rlc(emulator, Reg8::A),
-            0x08 => self.rrc(emulator, Reg8::B),
-            0x09 => self.rrc(emulator, Reg8::C),
-            0x0A => self.rrc(emulator, Reg8::D),
-            0x0B => self.rrc(emulator, Reg8::E),
-            0x0C => self.rrc(emulator, Reg8::H),
-            0x0D => self.rrc(emulator, Reg8::L),
-            0x0E => self.rrc(emulator, Addr::HL),
-            0x0F => self.rrc(emulator, Reg8::A),
-            0x10 => self.rl(emulator, Reg8::B),
-            0x11 => self.rl(emulator, Reg8::C),
-            0x12 => self.rl(emulator, Reg8::D),
-            0x13 => self.rl(emulator, Reg8::E),
-            0x14 => self.rl(emulator, Reg8::H),
-            0x15 => self.rl(emulator, Reg8::L),
-            0x16 => self.rl(emulator, Addr::HL),
-            0x17 => self.rl(emulator, Reg8::A),
-            0x18 => self.rr(emulator, Reg8::B),
-            0x19 => self.rr(emulator, Reg8::C),
-            0x1A => self.rr(emulator, Reg8::D),
-            0x1B => self.rr(emulator, Reg8::E),
-            0x1C => self.rr(emulator, Reg8::H),
-            0x1D => self.rr(emulator, Reg8::L),
-            0x1E => self.rr(emulator, Addr::HL),
-            0x1F => self.rr(emulator, Reg8::A),
-            0x20 => self.sla(emulator, Reg8::B),
-            0x21 => self.sla(emulator, Reg8::C),
-            0x22 => self.sla(emulator, Reg8::D),
-            0x23 => self.sla(emulator, Reg8::E),
-            0x24 => self.sla(emulator, Reg8::H),
-            0x25 => self.sla(emulator, Reg8::L),
-            0x26 => self.sla(emulator, Addr::HL),
-            0x27 => self.sla(emulator, Reg8::A),
-            0x28 => self.sra(emulator, Reg8::B),
-            0x29 => self.sra(emulator, Reg8::C),
-            0x2A => self.sra(emulator, Reg8::D),
-            0x2B => self.sra(emulator, Reg8::E),
-            0x2C => self.sra(emulator, Reg8::H),
-            0x2D => self.sra(emulator, Reg8::L),
-            0x2E => self.sra(emulator, Addr::HL),
-            0x2F => self.sra(emulator, Reg8::A),
-            0x30 => self.swap(emulator, Reg8::B),
-            0x31 => self.swap(emulator, Reg8::C),
-            0x32 => self.swap(emulator, Reg8::D),
-            0x33 => self.swap(emulator, Reg8::E),
-            0x34 => self.swap(emulator, Reg8::H),
-            0x35 => self.swap(emulator, Reg8::L),
-            0x36 => self.swap(emulator, Addr::HL),
-            0x37 => self.swap(emulator, Reg8::A),
-            0x38 => self.srl(emulator, Reg8::B),
-            0x39 => self.srl(emulator, Reg8::C),
-            0x3A => self.srl(emulator, Reg8::D),
-            0x3B => self.srl(emulator, Reg8::E),
-            0x3C => self.srl(emulator, Reg8::H),
-            0x3D => self.srl(emulator, Reg8::L),
-            0x3E => self.srl(emulator, Addr::HL),
-            0x3F => self.srl(emulator, Reg8::A),
-            0x40 => self.bit(emulator, 0, Reg8::B),
-            0x41 => self.bit(emulator, 0, Reg8::C),
-            0x42 => self.bit(emulator, 0, Reg8::D),
-            0x43 => self.bit(emulator, 0, Reg8::E),
-            0x44 => self.bit(emulator, 0, Reg8::H),
-            0x45 => self.bit(emulator, 0, Reg8::L),
-            0x46 => self.bit(emulator, 0, Addr::HL),
-            0x47 => self.bit(emulator, 0, Reg8::A),
-            0x48 => self.bit(emulator, 1, Reg8::B),
-            0x49 => self.bit(emulator, 1, Reg8::C),
-            0x4A => self.bit(emulator, 1, Reg8::D),
-            0x4B => self.bit(emulator, 1, Reg8::E),
-            0x4C => self.bit(emulator, 1, Reg8::H),
-            0x4D => self.bit(emulator, 1, Reg8::L),
-            0x4E => self.bit(emulator, 1, Addr::HL),
-            0x4F => self.bit(emulator, 1, Reg8::A),
-            0x50 => self.bit(emulator, 2, Reg8::B),
-            0x51 => self.bit(emulator, 2, Reg8::C),
-            0x52 => self.bit(emulator, 2, Reg8::D),
-            0x53 => self.bit(emulator, 2, Reg8::E),
-            0x54 => self.bit(emulator, 2, Reg8::H),
-            0x55 => self.bit(emulator, 2, Reg8::L),
-            0x56 => self.bit(emulator, 2, Addr::HL),
-            0x57 => self.bit(emulator, 2, Reg8::A),
-            0x58 => self.bit(emulator, 3, Reg8::B),
-            0x59 => self.bit(emulator, 3, Reg8::C),
-            0x5A => self.bit(emulator, 3, Reg8::D),
-            0x5B => self.bit(emulator, 3, Reg8::E),
-            0x5C => self.bit(emulator, 3, Reg8::H),
-            0x5D => self.bit(emulator, 3, Reg8::L),
-            0x5E => self.bit(emulator, 3, Addr::HL),
-            0x5F => self.bit(emulator, 3, Reg8::A),
-            0x60 => self.bit(emulator, 4, Reg8::B),
-            0x61 => self.bit(emulator, 4, Reg8::C),
-            0x62 => self.bit(emulator, 4, Reg8::D),
-            0x63 => self.bit(emulator, 4, Reg8::E),
-            0x64 => self.bit(emulator, 4, Reg8::H),
-            0x65 => self.bit(emulator, 4, Reg8::L),
-            0x66 => self.bit(emulator, 4, Addr::HL),
-            0x67 => self.bit(emulator, 4, Reg8::A),
-            0x68 => self.bit(emulator, 5, Reg8::B),
-            0x69 => self.bit(emulator, 5, Reg8::C),
-            0x6A => self.bit(emulator, 5, Reg8::D),
-            0x6B => self.bit(emulator, 5, Reg8::E),
-            0x6C => self.bit(emulator, 5, Reg8::H),
-            0x6D => self.bit(emulator, 5, Reg8::L),
-            0x6E => self.bit(emulator, 5, Addr::HL),
-            0x6F => self.bit(emulator, 5, Reg8::A),
-            0x70 => self.bit(emulator, 6, Reg8::B),
-            0x71 => self.bit(emulator, 6, Reg8::C),
-            0x72 => self.bit(emulator, 6, Reg8::D),
-            0x73 => self.bit(emulator, 6, Reg8::E),
-            0x74 => self.bit(emulator, 6, Reg8::H),
-            0x75 => self.bit(emulator, 6, Reg8::L),
-            0x76 => self.bit(emulator, 6, Addr::HL),
-            0x77 => self.bit(emulator, 6, Reg8::A),
-            0x78 => self.bit(emulator, 7, Reg8::B),
-            0x79 => self.bit(emulator, 7, Reg8::C),
-            0x7A => self.bit(emulator, 7, Reg8::D),
-            0x7B => self.bit(emulator, 7, Reg8::E),
-            0x7C => self.bit(emulator, 7, Reg8::H),
-            0x7D => self.bit(emulator, 7, Reg8::L),
-            0x7E => self.bit(emulator, 7, Addr::HL),
-            0x7F => self.bit(emulator, 7, Reg8::A),
-            0x80 => self.res(emulator, 0, Reg8::B),
-            0x81 => self.res(emulator, 0, Reg8::C),
-            0x82 => self.res(emulator, 0, Reg8::D),
-            0x83 => self.res(emulator, 0, Reg8::E),
-            0x84 => self.res(emulator, 0, Reg8::H),
-            0x85 => self.res(emulator, 0, Reg8::L),
-            0x86 => self.res(emulator, 0, Addr::HL),
-            0x87 => self.res(emulator, 0, Reg8::A),
-            0x88 => self.res(emulator, 1, Reg8::B),
-            0x89 => self.res(emulator, 1, Reg8::C),
-            0x8A => self.res(emulator, 1, Reg8::D),
-            0x8B => self.res(emulator, 1, Reg8::E),
-            0x8C => self.res(emulator, 1, Reg8::H),
-            0x8D => self.res(emulator, 1, Reg8::L),
-            0x8E => self.res(emulator, 1, Addr::HL),
-            0x8F => self.res(emulator, 1, Reg8::A),
-            0x90 => self.res(emulator, 2, Reg8::B),
-            0x91 => self.res(emulator, 2, Reg8::C),
-            0x92 => self.res(emulator, 2, Reg8::D),
-            0x93 => self.res(emulator, 2, Reg8::E),
-            0x94 => self.res(emulator, 2, Reg8::H),
-            0x95 => self.res(emulator, 2, Reg8::L),
-            0x96 => self.res(emulator, 2, Addr::HL),
-            0x97 => self.res(emulator, 2, Reg8::A),
-            0x98 => self.res(emulator, 3, Reg8::B),
-            0x99 => self.res(emulator, 3, Reg8::C),
-            0x9A => self.res(emulator, 3, Reg8::D),
-            0x9B => self.res(emulator, 3, Reg8::E),
-            0x9C => self.res(emulator, 3, Reg8::H),
-            0x9D => self.res(emulator, 3, Reg8::L),
-            0x9E => self.res(emulator, 3, Addr::HL),
-            0x9F => self.res(emulator, 3, Reg8::A),
-            0xA0 => self.res(emulator, 4, Reg8::B),
-            0xA1 => self.res(emulator, 4, Reg8::C),
-            0xA2 => self.res(emulator, 4, Reg8::D),
-            0xA3 => self.res(emulator, 4, Reg8::E),
-            0xA4 => self.res(emulator, 4, Reg8::H),
-            0xA5 => self.res(emulator, 4, Reg8::L),
-            0xA6 => self.res(emulator, 4, Addr::HL),
-            0xA7 => self.res(emulator, 4, Reg8::A),
-            0xA8 => self.res(emulator, 5, Reg8::B),
-            0xA9 => self.res(emulator, 5, Reg8::C),
-            0xAA => self.res(emulator, 5, Reg8::D),
-            0xAB => self.res(emulator, 5, Reg8::E),
-            0xAC => self.res(emulator, 5, Reg8::H),
-            0xAD => self.res(emulator, 5, Reg8::L),
-            0xAE => self.res(emulator, 5, Addr::HL),
-            0xAF => self.res(emulator, 5, Reg8::A),
-            0xB0 => self.res(emulator, 6, Reg8::B),
-            0xB1 => self.res(emulator, 6, Reg8::C),
-            0xB2 => self.res(emulator, 6, Reg8::D),
-            0xB3 => self.res(emulator, 6, Reg8::E),
-            0xB4 => self.res(emulator, 6, Reg8::H),
-            0xB5 => self.res(emulator, 6, Reg8::L),
-            0xB6 => self.res(emulator, 6, Addr::HL),
-            0xB7 => self.res(emulator, 6, Reg8::A),
-            0xB8 => self.res(emulator, 7, Reg8::B),
-            0xB9 => self.res(emulator, 7, Reg8::C),
-            0xBA => self.res(emulator, 7, Reg8::D),
-            0xBB => self.res(emulator, 7, Reg8::E),
-            0xBC => self.res(emulator, 7, Reg8::H),
-            0xBD => self.res(emulator, 7, Reg8::L),
-            0xBE => self.res(emulator, 7, Addr::HL),
-            0xBF => self.res(emulator, 7, Reg8::A),
-            0xC0 => self.set(emulator, 0, Reg8::B),
-            0xC1 => self.set(emulator, 0, Reg8::C),
-            0xC2 => self.set(emulator, 0, Reg8::D),
-            0xC3 => self.set(emulator, 0, Reg8::E),
-            0xC4 => self.set(emulator, 0, Reg8::H),
-            0xC5 => self.set(emulator, 0, Reg8::L),
-            0xC6 => self.set(emulator, 0, Addr::HL),
-            0xC7 => self.set(emulator, 0, Reg8::A),
-            0xC8 => self.set(emulator, 1, Reg8::B),
-            0xC9 => self.set(emulator, 1, Reg8::C),
-            0xCA => self.set(emulator, 1, Reg8::D),
-            0xCB => self.set(emulator, 1, Reg8::E),
-            0xCC => self.set(emulator, 1, Reg8::H),
-            0xCD => self.set(emulator, 1, Reg8::L),
-            0xCE => self.set(emulator, 1, Addr::HL),
-            0xCF => self.set(emulator, 1, Reg8::A),
-            0xD0 => self.set(emulator, 2, Reg8::B),
-            0xD1 => self.set(emulator, 2, Reg8::C),
-            0xD2 => self.set(emulator, 2, Reg8::D),
-            0xD3 => self.set(emulator, 2, Reg8::E),
-            0xD4 => self.set(emulator, 2, Reg8::H),
-            0xD5 => self.set(emulator, 2, Reg8::L),
-            0xD6 => self.set(emulator, 2, Addr::HL),
-            0xD7 => self.set(emulator, 2, Reg8::A),
-            0xD8 => self.set(emulator, 3, Reg8::B),
-            0xD9 => self.set(emulator, 3, Reg8::C),
-            0xDA => self.set(emulator, 3, Reg8::D),
-            0xDB => self.set(emulator, 3, Reg8::E),
-            0xDC => self.set(emulator, 3, Reg8::H),
-            0xDD => self.set(emulator, 3, Reg8::L),
-            0xDE => self.set(emulator, 3, Addr::HL),
-            0xDF => self.set(emulator, 3, Reg8::A),
-            0xE0 => self.set(emulator, 4, Reg8::B),
-            0xE1 => self.set(emulator, 4, Reg8::C),
-            0xE2 => self.set(emulator, 4, Reg8::D),
-            0xE3 => self.set(emulator, 4, Reg8::E),
-            0xE4 => self.set(emulator, 4, Reg8::H),
-            0xE5 => self.set(emulator, 4, Reg8::L),
-            0xE6 => self.set(emulator, 4, Addr::HL),
-            0xE7 => self.set(emulator, 4, Reg8::A),
-            0xE8 => self.set(emulator, 5, Reg8::B),
-            0xE9 => self.set(emulator, 5, Reg8::C),
-            0xEA => self.set(emulator, 5, Reg8::D),
-            0xEB => self.set(emulator, 5, Reg8::E),
-            0xEC => self.set(emulator, 5, Reg8::H),
-            0xED => self.set(emulator, 5, Reg8::L),
-            0xEE => self.set(emulator, 5, Addr::HL),
-            0xEF => self.set(emulator, 5, Reg8::A),
-            0xF0 => self.set(emulator, 6, Reg8::B),
-            0xF1 => self.set(emulator, 6, Reg8::C),
-            0xF2 => self.set(emulator, 6, Reg8::D),
-            0xF3 => self.set(emulator, 6, Reg8::E),
-            0xF4 => self.set(emulator, 6, Reg8::H),
-            0xF5 => self.set(emulator, 6, Reg8::L),
-            0xF6 => self.set(emulator, 6, Addr::HL),
-            0xF7 => self.set(emulator, 6, Reg8::A),
-            0xF8 => self.set(emulator, 7, Reg8::B),
-            0xF9 => self.set(emulator, 7, Reg8::C),
-            0xFA => self.set(emulator, 7, Reg8::D),
-            0xFB => self.set(emulator, 7, Reg8::E),
-            0xFC => self.set(emulator, 7, Reg8::H),
-            0xFD => self.set(emulator, 7, Reg8::L),
-            0xFE => self.set(emulator, 7, Addr::HL),
-            0xFF => self.set(emulator, 7, Reg8::A),
-        }
-    }
+opcode_table! {
+    fn decode(&mut self, emulator);
+    fn mnemonic;
+
+    // nop
+    0x00, "NOP" => (),
+
+    // 8-bit loads
+    0x02, "LD (BC),A" => self.load(emulator, Addr::BC, Reg8::A),
+    0x0A, "LD A,(BC)" => self.load(emulator, Reg8::A, Addr::BC),
+    0x12, "LD (DE),A" => self.load(emulator, Addr::DE, Reg8::A),
+    0x1A, "LD A,(DE)" => self.load(emulator, Reg8::A, Addr::DE),
+    0x22, "LD (HL+),A" => {
+        self.load(emulator, Addr::HL, Reg8::A);
+        let hl = self.regs.read_pair(Reg16::HL);
+        self.regs.write_pair(Reg16::HL, hl.wrapping_add(1));
+    },
+    0x2A, "LD A,(HL+)" => {
+        self.load(emulator, Reg8::A, Addr::HL);
+        let hl = self.regs.read_pair(Reg16::HL);
+        self.regs.write_pair(Reg16::HL, hl.wrapping_add(1));
+    },
+    0x32, "LD (HL-),A" => {
+        self.load(emulator, Addr::HL, Reg8::A);
+        let hl = self.regs.read_pair(Reg16::HL);
+        self.regs.write_pair(Reg16::HL, hl.wrapping_sub(1));
+    },
+    0x3A, "LD A,(HL-)" => {
+        self.load(emulator, Reg8::A, Addr::HL);
+        let hl = self.regs.read_pair(Reg16::HL);
+        self.regs.write_pair(Reg16::HL, hl.wrapping_sub(1));
+    },
+    0x06, "LD B,d8" => self.load(emulator, Reg8::B, ImmediateByte),
+    0x0E, "LD C,d8" => self.load(emulator, Reg8::C, ImmediateByte),
+    0x16, "LD D,d8" => self.load(emulator, Reg8::D, ImmediateByte),
+    0x1E, "LD E,d8" => self.load(emulator, Reg8::E, ImmediateByte),
+    0x26, "LD H,d8" => self.load(emulator, Reg8::H, ImmediateByte),
+    0x2E, "LD L,d8" => self.load(emulator, Reg8::L, ImmediateByte),
+    0x36, "LD (HL),d8" => self.load(emulator, Addr::HL, ImmediateByte),
+    0x3E, "LD A,d8" => self.load(emulator, Reg8::A, ImmediateByte),
+
+    0x40, "LD B,B" => self.load(emulator, Reg8::B, Reg8::B),
+    0x41, "LD B,C" => self.load(emulator, Reg8::B, Reg8::C),
+    0x42, "LD B,D" => self.load(emulator, Reg8::B, Reg8::D),
+    0x43, "LD B,E" => self.load(emulator, Reg8::B, Reg8::E),
+    0x44, "LD B,H" => self.load(emulator, Reg8::B, Reg8::H),
+    0x45, "LD B,L" => self.load(emulator, Reg8::B, Reg8::L),
+    0x46, "LD B,(HL)" => self.load(emulator, Reg8::B, Addr::HL),
+    0x47, "LD B,A" => self.load(emulator, Reg8::B, Reg8::A),
+
+    0x48, "LD C,B" => self.load(emulator, Reg8::C, Reg8::B),
+    0x49, "LD C,C" => self.load(emulator, Reg8::C, Reg8::C),
+    0x4A, "LD C,D" => self.load(emulator, Reg8::C, Reg8::D),
+    0x4B, "LD C,E" => self.load(emulator, Reg8::C, Reg8::E),
+    0x4C, "LD C,H" => self.load(emulator, Reg8::C, Reg8::H),
+    0x4D, "LD C,L" => self.load(emulator, Reg8::C, Reg8::L),
+    0x4E, "LD C,(HL)" => self.load(emulator, Reg8::C, Addr::HL),
+    0x4F, "LD C,A" => self.load(emulator, Reg8::C, Reg8::A),
+
+    0x50, "LD D,B" => self.load(emulator, Reg8::D, Reg8::B),
+    0x51, "LD D,C" => self.load(emulator, Reg8::D, Reg8::C),
+    0x52, "LD D,D" => self.load(emulator, Reg8::D, Reg8::D),
+    0x53, "LD D,E" => self.load(emulator, Reg8::D, Reg8::E),
+    0x54, "LD D,H" => self.load(emulator, Reg8::D, Reg8::H),
+    0x55, "LD D,L" => self.load(emulator, Reg8::D, Reg8::L),
+    0x56, "LD D,(HL)" => self.load(emulator, Reg8::D, Addr::HL),
+    0x57, "LD D,A" => self.load(emulator, Reg8::D, Reg8::A),
+
+    0x58, "LD E,B" => self.load(emulator, Reg8::E, Reg8::B),
+    0x59, "LD E,C" => self.load(emulator, Reg8::E, Reg8::C),
+    0x5A, "LD E,D" => self.load(emulator, Reg8::E, Reg8::D),
+    0x5B, "LD E,E" => self.load(emulator, Reg8::E, Reg8::E),
+    0x5C, "LD E,H" => self.load(emulator, Reg8::E, Reg8::H),
+    0x5D, "LD E,L" => self.load(emulator, Reg8::E, Reg8::L),
+    0x5E, "LD E,(HL)" => self.load(emulator, Reg8::E, Addr::HL),
+    0x5F, "LD E,A" => self.load(emulator, Reg8::E, Reg8::A),
+
+    0x60, "LD H,B" => self.load(emulator, Reg8::H, Reg8::B),
+    0x61, "LD H,C" => self.load(emulator, Reg8::H, Reg8::C),
+    0x62, "LD H,D" => self.load(emulator, Reg8::H, Reg8::D),
+    0x63, "LD H,E" => self.load(emulator, Reg8::H, Reg8::E),
+    0x64, "LD H,H" => self.load(emulator, Reg8::H, Reg8::H),
+    0x65, "LD H,L" => self.load(emulator, Reg8::H, Reg8::L),
+    0x66, "LD H,(HL)" => self.load(emulator, Reg8::H, Addr::HL),
+    0x67, "LD H,A" => self.load(emulator, Reg8::H, Reg8::A),
+
+    0x68, "LD L,B" => self.load(emulator, Reg8::L, Reg8::B),
+    0x69, "LD L,C" => self.load(emulator, Reg8::L, Reg8::C),
+    0x6A, "LD L,D" => self.load(emulator, Reg8::L, Reg8::D),
+    0x6B, "LD L,E" => self.load(emulator, Reg8::L, Reg8::E),
+    0x6C, "LD L,H" => self.load(emulator, Reg8::L, Reg8::H),
+    0x6D, "LD L,L" => self.load(emulator, Reg8::L, Reg8::L),
+    0x6E, "LD L,(HL)" => self.load(emulator, Reg8::L, Addr::HL),
+    0x6F, "LD L,A" => self.load(emulator, Reg8::L, Reg8::A),
+
+    0x70, "LD (HL),B" => self.load(emulator, Addr::HL, Reg8::B),
+    0x71, "LD (HL),C" => self.load(emulator, Addr::HL, Reg8::C),
+    0x72, "LD (HL),D" => self.load(emulator, Addr::HL, Reg8::D),
+    0x73, "LD (HL),E" => self.load(emulator, Addr::HL, Reg8::E),
+    0x74, "LD (HL),H" => self.load(emulator, Addr::HL, Reg8::H),
+    0x75, "LD (HL),L" => self.load(emulator, Addr::HL, Reg8::L),
+    0x77, "LD (HL),A" => self.load(emulator, Addr::HL, Reg8::A),
+
+    0x78, "LD A,B" => self.load(emulator, Reg8::A, Reg8::B),
+    0x79, "LD A,C" => self.load(emulator, Reg8::A, Reg8::C),
+    0x7A, "LD A,D" => self.load(emulator, Reg8::A, Reg8::D),
+    0x7B, "LD A,E" => self.load(emulator, Reg8::A, Reg8::E),
+    0x7C, "LD A,H" => self.load(emulator, Reg8::A, Reg8::H),
+    0x7D, "LD A,L" => self.load(emulator, Reg8::A, Reg8::L),
+    0x7E, "LD A,(HL)" => self.load(emulator, Reg8::A, Addr::HL),
+    0x7F, "LD A,A" => self.load(emulator, Reg8::A, Reg8::A),
+
+    0xE0, "LDH (a8),A" => self.load(emulator, Addr::LDH, Reg8::A),
+    0xE2, "LD ($FF00+C),A" => self.load(emulator, Addr::LDHC, Reg8::A),
+    0xF0, "LDH A,(a8)" => self.load(emulator, Reg8::A, Addr::LDH),
+    0xF2, "LD A,($FF00+C)" => self.load(emulator, Reg8::A, Addr::LDHC),
+
+    0xEA, "LD (a16),A" => self.load(emulator, Addr::Immediate, Reg8::A),
+    0xFA, "LD A,(a16)" => self.load(emulator, Reg8::A, Addr::Immediate),
+
+    // 8-bit adds
+    0x80, "ADD A,B" => self.add(emulator, Reg8::B),
+    0x81, "ADD A,C" => self.add(emulator, Reg8::C),
+    0x82, "ADD A,D" => self.add(emulator, Reg8::D),
+    0x83, "ADD A,E" => self.add(emulator, Reg8::E),
+    0x84, "ADD A,H" => self.add(emulator, Reg8::H),
+    0x85, "ADD A,L" => self.add(emulator, Reg8::L),
+    0x86, "ADD A,(HL)" => self.add(emulator, Addr::HL),
+    0x87, "ADD A,A" => self.add(emulator, Reg8::A),
+    0xC6, "ADD A,d8" => self.add(emulator, ImmediateByte),
+
+    // 8-bit adds + carry bit
+    0x88, "ADC A,B" => self.adc(emulator, Reg8::B),
+    0x89, "ADC A,C" => self.adc(emulator, Reg8::C),
+    0x8A, "ADC A,D" => self.adc(emulator, Reg8::D),
+    0x8B, "ADC A,E" => self.adc(emulator, Reg8::E),
+    0x8C, "ADC A,H" => self.adc(emulator, Reg8::H),
+    0x8D, "ADC A,L" => self.adc(emulator, Reg8::L),
+    0x8E, "ADC A,(HL)" => self.adc(emulator, Addr::HL),
+    0x8F, "ADC A,A" => self.adc(emulator, Reg8::A),
+    0xCE, "ADC A,d8" => self.adc(emulator, ImmediateByte),
+
+    // 8-bit subs
+    0x90, "SUB B" => self.sub(emulator, Reg8::B),
+    0x91, "SUB C" => self.sub(emulator, Reg8::C),
+    0x92, "SUB D" => self.sub(emulator, Reg8::D),
+    0x93, "SUB E" => self.sub(emulator, Reg8::E),
+    0x94, "SUB H" => self.sub(emulator, Reg8::H),
+    0x95, "SUB L" => self.sub(emulator, Reg8::L),
+    0x96, "SUB (HL)" => self.sub(emulator, Addr::HL),
+    0x97, "SUB A" => self.sub(emulator, Reg8::A),
+    0xD6, "SUB d8" => self.sub(emulator, ImmediateByte),
+
+    // 8-bit subs + carry bit
+    0x98, "SBC A,B" => self.sbc(emulator, Reg8::B),
+    0x99, "SBC A,C" => self.sbc(emulator, Reg8::C),
+    0x9A, "SBC A,D" => self.sbc(emulator, Reg8::D),
+    0x9B, "SBC A,E" => self.sbc(emulator, Reg8::E),
+    0x9C, "SBC A,H" => self.sbc(emulator, Reg8::H),
+    0x9D, "SBC A,L" => self.sbc(emulator, Reg8::L),
+    0x9E, "SBC A,(HL)" => self.sbc(emulator, Addr::HL),
+    0x9F, "SBC A,A" => self.sbc(emulator, Reg8::A),
+    0xDE, "SBC A,d8" => self.sbc(emulator, ImmediateByte),
+
+    // 8-bit increment
+    0x04, "INC B" => self.inc(emulator, Reg8::B),
+    0x0C, "INC C" => self.inc(emulator, Reg8::C),
+    0x14, "INC D" => self.inc(emulator, Reg8::D),
+    0x1C, "INC E" => self.inc(emulator, Reg8::E),
+    0x24, "INC H" => self.inc(emulator, Reg8::H),
+    0x2C, "INC L" => self.inc(emulator, Reg8::L),
+    0x34, "INC (HL)" => self.inc(emulator, Addr::HL),
+    0x3C, "INC A" => self.inc(emulator, Reg8::A),
+
+    // 8-bit decrement
+    0x05, "DEC B" => self.dec(emulator, Reg8::B),
+    0x0D, "DEC C" => self.dec(emulator, Reg8::C),
+    0x15, "DEC D" => self.dec(emulator, Reg8::D),
+    0x1D, "DEC E" => self.dec(emulator, Reg8::E),
+    0x25, "DEC H" => self.dec(emulator, Reg8::H),
+    0x2D, "DEC L" => self.dec(emulator, Reg8::L),
+    0x35, "DEC (HL)" => self.dec(emulator, Addr::HL),
+    0x3D, "DEC A" => self.dec(emulator, Reg8::A),
+
+    // 16-bit loads
+    0x01, "LD BC,d16" => self.load16(emulator, Reg16::BC, ImmediateWord),
+    0x11, "LD DE,d16" => self.load16(emulator, Reg16::DE, ImmediateWord),
+    0x21, "LD HL,d16" => self.load16(emulator, Reg16::HL, ImmediateWord),
+    0x31, "LD SP,d16" => self.load16(emulator, Reg16::SP, ImmediateWord),
+    0x08, "LD (a16),SP" => self.load16(emulator, Addr::Immediate, Reg16::SP),
+    0xF9, "LD SP,HL" => self.load16_sphl(emulator),
+
+    // 16-bit add to hl
+    0x09, "ADD HL,BC" => self.add16hl(emulator, Reg16::BC),
+    0x19, "ADD HL,DE" => self.add16hl(emulator, Reg16::DE),
+    0x29, "ADD HL,HL" => self.add16hl(emulator, Reg16::HL),
+    0x39, "ADD HL,SP" => self.add16hl(emulator, Reg16::SP),
+
+    // 16-bit stack pointer instructions
+    0xE8, "ADD SP,s8" => self.add16_sp_n(emulator),
+    0xF8, "LD HL,SP+s8" => self.load16_hlsp_n(emulator),
+
+    // 16-bit increments
+    0x03, "INC BC" => self.inc16(emulator, Reg16::BC),
+    0x13, "INC DE" => self.inc16(emulator, Reg16::DE),
+    0x23, "INC HL" => self.inc16(emulator, Reg16::HL),
+    0x33, "INC SP" => self.inc16(emulator, Reg16::SP),
+
+    // 16-bit decrements
+    0x0B, "DEC BC" => self.dec16(emulator, Reg16::BC),
+    0x1B, "DEC DE" => self.dec16(emulator, Reg16::DE),
+    0x2B, "DEC HL" => self.dec16(emulator, Reg16::HL),
+    0x3B, "DEC SP" => self.dec16(emulator, Reg16::SP),
+
+    // relative jumps
+    0x18, "JR r8" => self.jr(emulator, JumpCond::Always),
+    0x20, "JR NZ,r8" => self.jr(emulator, JumpCond::NotZero),
+    0x28, "JR Z,r8" => self.jr(emulator, JumpCond::Zero),
+    0x30, "JR NC,r8" => self.jr(emulator, JumpCond::NotCarry),
+    0x38, "JR C,r8" => self.jr(emulator, JumpCond::Carry),
+
+    // absolute jumps
+    0xC2, "JP NZ,a16" => self.jp(emulator, JumpCond::NotZero, ImmediateWord),
+    0xC3, "JP a16" => self.jp(emulator, JumpCond::Always, ImmediateWord),
+    0xCA, "JP Z,a16" => self.jp(emulator, JumpCond::Zero, ImmediateWord),
+    0xD2, "JP NC,a16" => self.jp(emulator, JumpCond::NotCarry, ImmediateWord),
+    0xDA, "JP C,a16" => self.jp(emulator, JumpCond::Carry, ImmediateWord),
+    0xE9, "JP HL" => self.jp_hl(emulator),
+
+    // pop from stack
+    0xC1, "POP BC" => self.pop(emulator, Reg16::BC),
+    0xD1, "POP DE" => self.pop(emulator, Reg16::DE),
+    0xE1, "POP HL" => self.pop(emulator, Reg16::HL),
+    0xF1, "POP AF" => self.pop(emulator, Reg16::AF),
+
+    // push to stack
+    0xC5, "PUSH BC" => self.push(emulator, Reg16::BC),
+    0xD5, "PUSH DE" => self.push(emulator, Reg16::DE),
+    0xE5, "PUSH HL" => self.push(emulator, Reg16::HL),
+    0xF5, "PUSH AF" => self.push(emulator, Reg16::AF),
+
+    // call
+    0xC4, "CALL NZ,a16" => self.call(emulator, JumpCond::NotZero),
+    0xCC, "CALL Z,a16" => self.call(emulator, JumpCond::Zero),
+    0xCD, "CALL a16" => self.call(emulator, JumpCond::Always),
+    0xD4, "CALL NC,a16" => self.call(emulator, JumpCond::NotCarry),
+    0xDC, "CALL C,a16" => self.call(emulator, JumpCond::Carry),
+
+    // return
+    0xC0, "RET NZ" => self.ret(emulator, JumpCond::NotZero),
+    0xC8, "RET Z" => self.ret(emulator, JumpCond::Zero),
+    0xC9, "RET" => self.ret(emulator, JumpCond::Always),
+    0xD0, "RET NC" => self.ret(emulator, JumpCond::NotCarry),
+    0xD8, "RET C" => self.ret(emulator, JumpCond::Carry),
+    0xD9, "RETI" => self.reti(emulator, JumpCond::Always),
+
+    // call reset vetor
+    0xC7, "RST $00" => self.rst(emulator, 0x00),
+    0xCF, "RST $08" => self.rst(emulator, 0x08),
+    0xD7, "RST $10" => self.rst(emulator, 0x10),
+    0xDF, "RST $18" => self.rst(emulator, 0x18),
+    0xE7, "RST $20" => self.rst(emulator, 0x20),
+    0xEF, "RST $28" => self.rst(emulator, 0x28),
+    0xF7, "RST $30" => self.rst(emulator, 0x30),
+    0xFF, "RST $38" => self.rst(emulator, 0x38),
+
+    // bitwise and
+    0xA0, "AND B" => self.and(emulator, Reg8::B),
+    0xA1, "AND C" => self.and(emulator, Reg8::C),
+    0xA2, "AND D" => self.and(emulator, Reg8::D),
+    0xA3, "AND E" => self.and(emulator, Reg8::E),
+    0xA4, "AND H" => self.and(emulator, Reg8::H),
+    0xA5, "AND L" => self.and(emulator, Reg8::L),
+    0xA6, "AND (HL)" => self.and(emulator, Addr::HL),
+    0xA7, "AND A" => self.and(emulator, Reg8::A),
+    0xE6, "AND d8" => self.and(emulator, ImmediateByte),
+
+    // bitwise xor
+    0xA8, "XOR B" => self.xor(emulator, Reg8::B),
+    0xA9, "XOR C" => self.xor(emulator, Reg8::C),
+    0xAA, "XOR D" => self.xor(emulator, Reg8::D),
+    0xAB, "XOR E" => self.xor(emulator, Reg8::E),
+    0xAC, "XOR H" => self.xor(emulator, Reg8::H),
+    0xAD, "XOR L" => self.xor(emulator, Reg8::L),
+    0xAE, "XOR (HL)" => self.xor(emulator, Addr::HL),
+    0xAF, "XOR A" => self.xor(emulator, Reg8::A),
+    0xEE, "XOR d8" => self.xor(emulator, ImmediateByte),
+
+    // bitwise or
+    0xB0, "OR B" => self.or(emulator, Reg8::B),
+    0xB1, "OR C" => self.or(emulator, Reg8::C),
+    0xB2, "OR D" => self.or(emulator, Reg8::D),
+    0xB3, "OR E" => self.or(emulator, Reg8::E),
+    0xB4, "OR H" => self.or(emulator, Reg8::H),
+    0xB5, "OR L" => self.or(emulator, Reg8::L),
+    0xB6, "OR (HL)" => self.or(emulator, Addr::HL),
+    0xB7, "OR A" => self.or(emulator, Reg8::A),
+    0xF6, "OR d8" => self.or(emulator, ImmediateByte),
+
+    // rotations
+    0x07, "RLCA" => self.rlca(emulator, ),
+    0x17, "RLA" => self.rla(emulator, ),
+    0x0F, "RRCA" => self.rrca(emulator, ),
+    0x1F, "RRA" => self.rra(emulator, ),
+
+    // compare
+    0xB8, "CP B" => self.cp(emulator, Reg8::B),
+    0xB9, "CP C" => self.cp(emulator, Reg8::C),
+    0xBA, "CP D" => self.cp(emulator, Reg8::D),
+    0xBB, "CP E" => self.cp(emulator, Reg8::E),
+    0xBC, "CP H" => self.cp(emulator, Reg8::H),
+    0xBD, "CP L" => self.cp(emulator, Reg8::L),
+    0xBE, "CP (HL)" => self.cp(emulator, Addr::HL),
+    0xBF, "CP A" => self.cp(emulator, Reg8::A),
+    0xFE, "CP d8" => self.cp(emulator, ImmediateByte),
+
+    // complement A register
+    0x2F, "CPL" => self.cpl(emulator),
+
+    // set carry flag
+    0x37, "SCF" => self.scf(emulator),
+
+    // complement carry flag
+    0x3F, "CCF" => self.ccf(emulator),
+
+    // misc
+    0x10, "STOP d8" => self.stop(emulator),
+    0x27, "DAA" => self.daa(emulator),
+    0x76, "HALT" => self.halt(emulator),
+    0xF3, "DI" => self.di(emulator),
+    0xFB, "EI" => self.ei(emulator),
+
+    // prefixed instructions
+    0xCB, "PREFIX CB" => {
+        self.opcode = self.fetch(emulator);
+        self.prefixed_decode(emulator);
+    },
+
+    0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD, "" =>
+        panic!("Invalid opcode {:#X}", self.opcode),
+}
+
+opcode_table! {
+    fn prefixed_decode(&mut self, emulator);
+    fn prefixed_mnemonic;
+
+    0x00, "RLC B" => self.rlc(emulator, Reg8::B),
+    0x01, "RLC C" => self.rlc(emulator, Reg8::C),
+    0x02, "RLC D" => self.rlc(emulator, Reg8::D),
+    0x03, "RLC E" => self.rlc(emulator, Reg8::E),
+    0x04, "RLC H" => self.rlc(emulator, Reg8::H),
+    0x05, "RLC L" => self.rlc(emulator, Reg8::L),
+    0x06, "RLC (HL)" => self.rlc(emulator, Addr::HL),
+    0x07, "RLC A" => self.rlc(emulator, Reg8::A),
+    0x08, "RRC B" => self.rrc(emulator, Reg8::B),
+    0x09, "RRC C" => self.rrc(emulator, Reg8::C),
+    0x0A, "RRC D" => self.rrc(emulator, Reg8::D),
+    0x0B, "RRC E" => self.rrc(emulator, Reg8::E),
+    0x0C, "RRC H" => self.rrc(emulator, Reg8::H),
+    0x0D, "RRC L" => self.rrc(emulator, Reg8::L),
+    0x0E, "RRC (HL)" => self.rrc(emulator, Addr::HL),
+    0x0F, "RRC A" => self.rrc(emulator, Reg8::A),
+    0x10, "RL B" => self.rl(emulator, Reg8::B),
+    0x11, "RL C" => self.rl(emulator, Reg8::C),
+    0x12, "RL D" => self.rl(emulator, Reg8::D),
+    0x13, "RL E" => self.rl(emulator, Reg8::E),
+    0x14, "RL H" => self.rl(emulator, Reg8::H),
+    0x15, "RL L" => self.rl(emulator, Reg8::L),
+    0x16, "RL (HL)" => self.rl(emulator, Addr::HL),
+    0x17, "RL A" => self.rl(emulator, Reg8::A),
+    0x18, "RR B" => self.rr(emulator, Reg8::B),
+    0x19, "RR C" => self.rr(emulator, Reg8::C),
+    0x1A, "RR D" => self.rr(emulator, Reg8::D),
+    0x1B, "RR E" => self.rr(emulator, Reg8::E),
+    0x1C, "RR H" => self.rr(emulator, Reg8::H),
+    0x1D, "RR L" => self.rr(emulator, Reg8::L),
+    0x1E, "RR (HL)" => self.rr(emulator, Addr::HL),
+    0x1F, "RR A" => self.rr(emulator, Reg8::A),
+    0x20, "SLA B" => self.sla(emulator, Reg8::B),
+    0x21, "SLA C" => self.sla(emulator, Reg8::C),
+    0x22, "SLA D" => self.sla(emulator, Reg8::D),
+    0x23, "SLA E" => self.sla(emulator, Reg8::E),
+    0x24, "SLA H" => self.sla(emulator, Reg8::H),
+    0x25, "SLA L" => self.sla(emulator, Reg8::L),
+    0x26, "SLA (HL)" => self.sla(emulator, Addr::HL),
+    0x27, "SLA A" => self.sla(emulator, Reg8::A),
+    0x28, "SRA B" => self.sra(emulator, Reg8::B),
+    0x29, "SRA C" => self.sra(emulator, Reg8::C),
+    0x2A, "SRA D" => self.sra(emulator, Reg8::D),
+    0x2B, "SRA E" => self.sra(emulator, Reg8::E),
+    0x2C, "SRA H" => self.sra(emulator, Reg8::H),
+    0x2D, "SRA L" => self.sra(emulator, Reg8::L),
+    0x2E, "SRA (HL)" => self.sra(emulator, Addr::HL),
+    0x2F, "SRA A" => self.sra(emulator, Reg8::A),
+    0x30, "SWAP B" => self.swap(emulator, Reg8::B),
+    0x31, "SWAP C" => self.swap(emulator, Reg8::C),
+    0x32, "SWAP D" => self.swap(emulator, Reg8::D),
+    0x33, "SWAP E" => self.swap(emulator, Reg8::E),
+    0x34, "SWAP H" => self.swap(emulator, Reg8::H),
+    0x35, "SWAP L" => self.swap(emulator, Reg8::L),
+    0x36, "SWAP (HL)" => self.swap(emulator, Addr::HL),
+    0x37, "SWAP A" => self.swap(emulator, Reg8::A),
+    0x38, "SRL B" => self.srl(emulator, Reg8::B),
+    0x39, "SRL C" => self.srl(emulator, Reg8::C),
+    0x3A, "SRL D" => self.srl(emulator, Reg8::D),
+    0x3B, "SRL E" => self.srl(emulator, Reg8::E),
+    0x3C, "SRL H" => self.srl(emulator, Reg8::H),
+    0x3D, "SRL L" => self.srl(emulator, Reg8::L),
+    0x3E, "SRL (HL)" => self.srl(emulator, Addr::HL),
+    0x3F, "SRL A" => self.srl(emulator, Reg8::A),
+    0x40, "BIT 0,B" => self.bit(emulator, 0, Reg8::B),
+    0x41, "BIT 0,C" => self.bit(emulator, 0, Reg8::C),
+    0x42, "BIT 0,D" => self.bit(emulator, 0, Reg8::D),
+    0x43, "BIT 0,E" => self.bit(emulator, 0, Reg8::E),
+    0x44, "BIT 0,H" => self.bit(emulator, 0, Reg8::H),
+    0x45, "BIT 0,L" => self.bit(emulator, 0, Reg8::L),
+    0x46, "BIT 0,(HL)" => self.bit(emulator, 0, Addr::HL),
+    0x47, "BIT 0,A" => self.bit(emulator, 0, Reg8::A),
+    0x48, "BIT 1,B" => self.bit(emulator, 1, Reg8::B),
+    0x49, "BIT 1,C" => self.bit(emulator, 1, Reg8::C),
+    0x4A, "BIT 1,D" => self.bit(emulator, 1, Reg8::D),
+    0x4B, "BIT 1,E" => self.bit(emulator, 1, Reg8::E),
+    0x4C, "BIT 1,H" => self.bit(emulator, 1, Reg8::H),
+    0x4D, "BIT 1,L" => self.bit(emulator, 1, Reg8::L),
+    0x4E, "BIT 1,(HL)" => self.bit(emulator, 1, Addr::HL),
+    0x4F, "BIT 1,A" => self.bit(emulator, 1, Reg8::A),
+    0x50, "BIT 2,B" => self.bit(emulator, 2, Reg8::B),
+    0x51, "BIT 2,C" => self.bit(emulator, 2, Reg8::C),
+    0x52, "BIT 2,D" => self.bit(emulator, 2, Reg8::D),
+    0x53, "BIT 2,E" => self.bit(emulator, 2, Reg8::E),
+    0x54, "BIT 2,H" => self.bit(emulator, 2, Reg8::H),
+    0x55, "BIT 2,L" => self.bit(emulator, 2, Reg8::L),
+    0x56, "BIT 2,(HL)" => self.bit(emulator, 2, Addr::HL),
+    0x57, "BIT 2,A" => self.bit(emulator, 2, Reg8::A),
+    0x58, "BIT 3,B" => self.bit(emulator, 3, Reg8::B),
+    0x59, "BIT 3,C" => self.bit(emulator, 3, Reg8::C),
+    0x5A, "BIT 3,D" => self.bit(emulator, 3, Reg8::D),
+    0x5B, "BIT 3,E" => self.bit(emulator, 3, Reg8::E),
+    0x5C, "BIT 3,H" => self.bit(emulator, 3, Reg8::H),
+    0x5D, "BIT 3,L" => self.bit(emulator, 3, Reg8::L),
+    0x5E, "BIT 3,(HL)" => self.bit(emulator, 3, Addr::HL),
+    0x5F, "BIT 3,A" => self.bit(emulator, 3, Reg8::A),
+    0x60, "BIT 4,B" => self.bit(emulator, 4, Reg8::B),
+    0x61, "BIT 4,C" => self.bit(emulator, 4, Reg8::C),
+    0x62, "BIT 4,D" => self.bit(emulator, 4, Reg8::D),
+    0x63, "BIT 4,E" => self.bit(emulator, 4, Reg8::E),
+    0x64, "BIT 4,H" => self.bit(emulator, 4, Reg8::H),
+    0x65, "BIT 4,L" => self.bit(emulator, 4, Reg8::L),
+    0x66, "BIT 4,(HL)" => self.bit(emulator, 4, Addr::HL),
+    0x67, "BIT 4,A" => self.bit(emulator, 4, Reg8::A),
+    0x68, "BIT 5,B" => self.bit(emulator, 5, Reg8::B),
+    0x69, "BIT 5,C" => self.bit(emulator, 5, Reg8::C),
+    0x6A, "BIT 5,D" => self.bit(emulator, 5, Reg8::D),
+    0x6B, "BIT 5,E" => self.bit(emulator, 5, Reg8::E),
+    0x6C, "BIT 5,H" => self.bit(emulator, 5, Reg8::H),
+    0x6D, "BIT 5,L" => self.bit(emulator, 5, Reg8::L),
+    0x6E, "BIT 5,(HL)" => self.bit(emulator, 5, Addr::HL),
+    0x6F, "BIT 5,A" => self.bit(emulator, 5, Reg8::A),
+    0x70, "BIT 6,B" => self.bit(emulator, 6, Reg8::B),
+    0x71, "BIT 6,C" => self.bit(emulator, 6, Reg8::C),
+    0x72, "BIT 6,D" => self.bit(emulator, 6, Reg8::D),
+    0x73, "BIT 6,E" => self.bit(emulator, 6, Reg8::E),
+    0x74, "BIT 6,H" => self.bit(emulator, 6, Reg8::H),
+    0x75, "BIT 6,L" => self.bit(emulator, 6, Reg8::L),
+    0x76, "BIT 6,(HL)" => self.bit(emulator, 6, Addr::HL),
+    0x77, "BIT 6,A" => self.bit(emulator, 6, Reg8::A),
+    0x78, "BIT 7,B" => self.bit(emulator, 7, Reg8::B),
+    0x79, "BIT 7,C" => self.bit(emulator, 7, Reg8::C),
+    0x7A, "BIT 7,D" => self.bit(emulator, 7, Reg8::D),
+    0x7B, "BIT 7,E" => self.bit(emulator, 7, Reg8::E),
+    0x7C, "BIT 7,H" => self.bit(emulator, 7, Reg8::H),
+    0x7D, "BIT 7,L" => self.bit(emulator, 7, Reg8::L),
+    0x7E, "BIT 7,(HL)" => self.bit(emulator, 7, Addr::HL),
+    0x7F, "BIT 7,A" => self.bit(emulator, 7, Reg8::A),
+    0x80, "RES 0,B" => self.res(emulator, 0, Reg8::B),
+    0x81, "RES 0,C" => self.res(emulator, 0, Reg8::C),
+    0x82, "RES 0,D" => self.res(emulator, 0, Reg8::D),
+    0x83, "RES 0,E" => self.res(emulator, 0, Reg8::E),
+    0x84, "RES 0,H" => self.res(emulator, 0, Reg8::H),
+    0x85, "RES 0,L" => self.res(emulator, 0, Reg8::L),
+    0x86, "RES 0,(HL)" => self.res(emulator, 0, Addr::HL),
+    0x87, "RES 0,A" => self.res(emulator, 0, Reg8::A),
+    0x88, "RES 1,B" => self.res(emulator, 1, Reg8::B),
+    0x89, "RES 1,C" => self.res(emulator, 1, Reg8::C),
+    0x8A, "RES 1,D" => self.res(emulator, 1, Reg8::D),
+    0x8B, "RES 1,E" => self.res(emulator, 1, Reg8::E),
+    0x8C, "RES 1,H" => self.res(emulator, 1, Reg8::H),
+    0x8D, "RES 1,L" => self.res(emulator, 1, Reg8::L),
+    0x8E, "RES 1,(HL)" => self.res(emulator, 1, Addr::HL),
+    0x8F, "RES 1,A" => self.res(emulator, 1, Reg8::A),
+    0x90, "RES 2,B" => self.res(emulator, 2, Reg8::B),
+    0x91, "RES 2,C" => self.res(emulator, 2, Reg8::C),
+    0x92, "RES 2,D" => self.res(emulator, 2, Reg8::D),
+    0x93, "RES 2,E" => self.res(emulator, 2, Reg8::E),
+    0x94, "RES 2,H" => self.res(emulator, 2, Reg8::H),
+    0x95, "RES 2,L" => self.res(emulator, 2, Reg8::L),
+    0x96, "RES 2,(HL)" => self.res(emulator, 2, Addr::HL),
+    0x97, "RES 2,A" => self.res(emulator, 2, Reg8::A),
+    0x98, "RES 3,B" => self.res(emulator, 3, Reg8::B),
+    0x99, "RES 3,C" => self.res(emulator, 3, Reg8::C),
+    0x9A, "RES 3,D" => self.res(emulator, 3, Reg8::D),
+    0x9B, "RES 3,E" => self.res(emulator, 3, Reg8::E),
+    0x9C, "RES 3,H" => self.res(emulator, 3, Reg8::H),
+    0x9D, "RES 3,L" => self.res(emulator, 3, Reg8::L),
+    0x9E, "RES 3,(HL)" => self.res(emulator, 3, Addr::HL),
+    0x9F, "RES 3,A" => self.res(emulator, 3, Reg8::A),
+    0xA0, "RES 4,B" => self.res(emulator, 4, Reg8::B),
+    0xA1, "RES 4,C" => self.res(emulator, 4, Reg8::C),
+    0xA2, "RES 4,D" => self.res(emulator, 4, Reg8::D),
+    0xA3, "RES 4,E" => self.res(emulator, 4, Reg8::E),
+    0xA4, "RES 4,H" => self.res(emulator, 4, Reg8::H),
+    0xA5, "RES 4,L" => self.res(emulator, 4, Reg8::L),
+    0xA6, "RES 4,(HL)" => self.res(emulator, 4, Addr::HL),
+    0xA7, "RES 4,A" => self.res(emulator, 4, Reg8::A),
+    0xA8, "RES 5,B" => self.res(emulator, 5, Reg8::B),
+    0xA9, "RES 5,C" => self.res(emulator, 5, Reg8::C),
+    0xAA, "RES 5,D" => self.res(emulator, 5, Reg8::D),
+    0xAB, "RES 5,E" => self.res(emulator, 5, Reg8::E),
+    0xAC, "RES 5,H" => self.res(emulator, 5, Reg8::H),
+    0xAD, "RES 5,L" => self.res(emulator, 5, Reg8::L),
+    0xAE, "RES 5,(HL)" => self.res(emulator, 5, Addr::HL),
+    0xAF, "RES 5,A" => self.res(emulator, 5, Reg8::A),
+    0xB0, "RES 6,B" => self.res(emulator, 6, Reg8::B),
+    0xB1, "RES 6,C" => self.res(emulator, 6, Reg8::C),
+    0xB2, "RES 6,D" => self.res(emulator, 6, Reg8::D),
+    0xB3, "RES 6,E" => self.res(emulator, 6, Reg8::E),
+    0xB4, "RES 6,H" => self.res(emulator, 6, Reg8::H),
+    0xB5, "RES 6,L" => self.res(emulator, 6, Reg8::L),
+    0xB6, "RES 6,(HL)" => self.res(emulator, 6, Addr::HL),
+    0xB7, "RES 6,A" => self.res(emulator, 6, Reg8::A),
+    0xB8, "RES 7,B" => self.res(emulator, 7, Reg8::B),
+    0xB9, "RES 7,C" => self.res(emulator, 7, Reg8::C),
+    0xBA, "RES 7,D" => self.res(emulator, 7, Reg8::D),
+    0xBB, "RES 7,E" => self.res(emulator, 7, Reg8::E),
+    0xBC, "RES 7,H" => self.res(emulator, 7, Reg8::H),
+    0xBD, "RES 7,L" => self.res(emulator, 7, Reg8::L),
+    0xBE, "RES 7,(HL)" => self.res(emulator, 7, Addr::HL),
+    0xBF, "RES 7,A" => self.res(emulator, 7, Reg8::A),
+    0xC0, "SET 0,B" => self.set(emulator, 0, Reg8::B),
+    0xC1, "SET 0,C" => self.set(emulator, 0, Reg8::C),
+    0xC2, "SET 0,D" => self.set(emulator, 0, Reg8::D),
+    0xC3, "SET 0,E" => self.set(emulator, 0, Reg8::E),
+    0xC4, "SET 0,H" => self.set(emulator, 0, Reg8::H),
+    0xC5, "SET 0,L" => self.set(emulator, 0, Reg8::L),
+    0xC6, "SET 0,(HL)" => self.set(emulator, 0, Addr::HL),
+    0xC7, "SET 0,A" => self.set(emulator, 0, Reg8::A),
+    0xC8, "SET 1,B" => self.set(emulator, 1, Reg8::B),
+    0xC9, "SET 1,C" => self.set(emulator, 1, Reg8::C),
+    0xCA, "SET 1,D" => self.set(emulator, 1, Reg8::D),
+    0xCB, "SET 1,E" => self.set(emulator, 1, Reg8::E),
+    0xCC, "SET 1,H" => self.set(emulator, 1, Reg8::H),
+    0xCD, "SET 1,L" => self.set(emulator, 1, Reg8::L),
+    0xCE, "SET 1,(HL)" => self.set(emulator, 1, Addr::HL),
+    0xCF, "SET 1,A" => self.set(emulator, 1, Reg8::A),
+    0xD0, "SET 2,B" => self.set(emulator, 2, Reg8::B),
+    0xD1, "SET 2,C" => self.set(emulator, 2, Reg8::C),
+    0xD2, "SET 2,D" => self.set(emulator, 2, Reg8::D),
+    0xD3, "SET 2,E" => self.set(emulator, 2, Reg8::E),
+    0xD4, "SET 2,H" => self.set(emulator, 2, Reg8::H),
+    0xD5, "SET 2,L" => self.set(emulator, 2, Reg8::L),
+    0xD6, "SET 2,(HL)" => self.set(emulator, 2, Addr::HL),
+    0xD7, "SET 2,A" => self.set(emulator, 2, Reg8::A),
+    0xD8, "SET 3,B" => self.set(emulator, 3, Reg8::B),
+    0xD9, "SET 3,C" => self.set(emulator, 3, Reg8::C),
+    0xDA, "SET 3,D" => self.set(emulator, 3, Reg8::D),
+    0xDB, "SET 3,E" => self.set(emulator, 3, Reg8::E),
+    0xDC, "SET 3,H" => self.set(emulator, 3, Reg8::H),
+    0xDD, "SET 3,L" => self.set(emulator, 3, Reg8::L),
+    0xDE, "SET 3,(HL)" => self.set(emulator, 3, Addr::HL),
+    0xDF, "SET 3,A" => self.set(emulator, 3, Reg8::A),
+    0xE0, "SET 4,B" => self.set(emulator, 4, Reg8::B),
+    0xE1, "SET 4,C" => self.set(emulator, 4, Reg8::C),
+    0xE2, "SET 4,D" => self.set(emulator, 4, Reg8::D),
+    0xE3, "SET 4,E" => self.set(emulator, 4, Reg8::E),
+    0xE4, "SET 4,H" => self.set(emulator, 4, Reg8::H),
+    0xE5, "SET 4,L" => self.set(emulator, 4, Reg8::L),
+    0xE6, "SET 4,(HL)" => self.set(emulator, 4, Addr::HL),
+    0xE7, "SET 4,A" => self.set(emulator, 4, Reg8::A),
+    0xE8, "SET 5,B" => self.set(emulator, 5, Reg8::B),
+    0xE9, "SET 5,C" => self.set(emulator, 5, Reg8::C),
+    0xEA, "SET 5,D" => self.set(emulator, 5, Reg8::D),
+    0xEB, "SET 5,E" => self.set(emulator, 5, Reg8::E),
+    0xEC, "SET 5,H" => self.set(emulator, 5, Reg8::H),
+    0xED, "SET 5,L" => self.set(emulator, 5, Reg8::L),
+    0xEE, "SET 5,(HL)" => self.set(emulator, 5, Addr::HL),
+    0xEF, "SET 5,A" => self.set(emulator, 5, Reg8::A),
+    0xF0, "SET 6,B" => self.set(emulator, 6, Reg8::B),
+    0xF1, "SET 6,C" => self.set(emulator, 6, Reg8::C),
+    0xF2, "SET 6,D" => self.set(emulator, 6, Reg8::D),
+    0xF3, "SET 6,E" => self.set(emulator, 6, Reg8::E),
+    0xF4, "SET 6,H" => self.set(emulator, 6, Reg8::H),
+    0xF5, "SET 6,L" => self.set(emulator, 6, Reg8::L),
+    0xF6, "SET 6,(HL)" => self.set(emulator, 6, Addr::HL),
+    0xF7, "SET 6,A" => self.set(emulator, 6, Reg8::A),
+    0xF8, "SET 7,B" => self.set(emulator, 7, Reg8::B),
+    0xF9, "SET 7,C" => self.set(emulator, 7, Reg8::C),
+    0xFA, "SET 7,D" => self.set(emulator, 7, Reg8::D),
+    0xFB, "SET 7,E" => self.set(emulator, 7, Reg8::E),
+    0xFC, "SET 7,H" => self.set(emulator, 7, Reg8::H),
+    0xFD, "SET 7,L" => self.set(emulator, 7, Reg8::L),
+    0xFE, "SET 7,(HL)" => self.set(emulator, 7, Addr::HL),
+    0xFF, "SET 7,A" => self.set(emulator, 7, Reg8::A),
 }
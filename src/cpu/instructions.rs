@@ -14,6 +14,14 @@ impl Cpu {
         }
     }
 
+    /// On DMG-family models, a 16-bit increment or decrement of a register
+    /// pointing into OAM corrupts it if the PPU is currently scanning OAM.
+    fn oam_bug(&self, emulator: &mut Emulator, address: u16) {
+        if self.model.has_oam_corruption() && (0xFE00..=0xFEFF).contains(&address) {
+            emulator.corrupt_oam();
+        }
+    }
+
     pub fn halt(&mut self, _: &mut Emulator) {
         self.halted = true;
     }
@@ -250,6 +258,7 @@ impl Cpu {
     pub fn inc16<T: WordSrc + WordDest>(&mut self, emulator: &mut Emulator, target: T) {
         // 2 cycles to increment a register pair
         let old_value = target.read(self, emulator);
+        self.oam_bug(emulator, old_value);
         let new_value = old_value.wrapping_add(1);
         target.write(self, emulator, new_value);
         emulator.step();
@@ -258,6 +267,7 @@ impl Cpu {
     pub fn dec16<T: WordSrc + WordDest>(&mut self, emulator: &mut Emulator, target: T) {
         // 2 cycles to decrement a register pair
         let old_value = target.read(self, emulator);
+        self.oam_bug(emulator, old_value);
         let new_value = old_value.wrapping_sub(1);
         target.write(self, emulator, new_value);
         emulator.step();
@@ -2,11 +2,14 @@ mod registers;
 mod decode;
 mod instructions;
 
-use registers::Registers;
+pub use registers::{Registers, Reg16};
 use crate::emulator::Emulator;
 use crate::interrupts::Interrupt;
+use crate::model::Model;
+use crate::savestate::{self, SaveState};
 
 pub struct Cpu {
+    model: Model,
     regs: Registers,
     halted: bool,
     interrupt_enabled: bool,
@@ -30,8 +33,9 @@ pub trait WordDest {
 }
 
 impl Cpu {
-    pub fn new() -> Self {
+    pub fn new(model: Model) -> Self {
         Self {
+            model,
             regs: Registers::default(),
             interrupt_enabled: false,
             halted: false,
@@ -39,12 +43,47 @@ impl Cpu {
         }
     }
 
+    /// Put the registers in the state the boot ROM of this model leaves them in.
+    pub fn skip_boot(&mut self) {
+        let (af, bc, de, hl) = match self.model {
+            Model::Dmg0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+            Model::Dmg => (0x01B0, 0x0013, 0x00D8, 0x014D),
+            Model::Mgb => (0xFFB0, 0x0013, 0x00D8, 0x014D),
+            Model::Sgb => (0x0100, 0x0014, 0x0000, 0xC060),
+            Model::Cgb => (0x1180, 0x0000, 0xFF56, 0x000D),
+            Model::Agb => (0x1100, 0x0100, 0xFF56, 0x000D),
+        };
+        self.regs.write_pair(Reg16::AF, af);
+        self.regs.write_pair(Reg16::BC, bc);
+        self.regs.write_pair(Reg16::DE, de);
+        self.regs.write_pair(Reg16::HL, hl);
+        self.regs.sp = 0xFFFE;
+        self.regs.pc = 0x0100;
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.regs
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.regs
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Whether the interrupt master enable flag (IME) is set.
+    pub fn interrupts_enabled(&self) -> bool {
+        self.interrupt_enabled
+    }
+
     fn handle_interrupt(&mut self, emulator: &mut Emulator) {
         emulator.step();
         emulator.step();
         let interrupt = emulator.interrupts.pop()
             .expect("Could not retrieve interrupt from queue");
-        self.push(emulator, registers::Reg16::PC);
+        self.push(emulator, Reg16::PC);
         match interrupt {
             Interrupt::VBlank => self.regs.pc = 0x40,
             Interrupt::Lcd => self.regs.pc = 0x48,
@@ -107,6 +146,23 @@ impl Cpu {
     }
 }
 
+impl SaveState for Cpu {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.regs.save_state(w)?;
+        savestate::write_bool(w, self.halted)?;
+        savestate::write_bool(w, self.interrupt_enabled)?;
+        savestate::write_u8(w, self.opcode)
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        self.regs.load_state(r)?;
+        self.halted = savestate::read_bool(r)?;
+        self.interrupt_enabled = savestate::read_bool(r)?;
+        self.opcode = savestate::read_u8(r)?;
+        Ok(())
+    }
+}
+
 impl std::fmt::Debug for Cpu {
     fn fmt(&self, w: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(w, "CPU \n\
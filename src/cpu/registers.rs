@@ -1,5 +1,6 @@
 use crate::cpu::{Cpu, ByteSrc, ByteDest, WordSrc, WordDest};
 use crate::emulator::Emulator;
+use crate::savestate::{self, SaveState};
 
 #[derive(Debug, Copy, Clone)]
 pub enum Reg8 {
@@ -145,6 +146,31 @@ impl Registers {
     }
 }
 
+impl SaveState for Registers {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        w.write_all(&[self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l])?;
+        savestate::write_u16(w, self.pc)?;
+        savestate::write_u16(w, self.sp)
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        let mut bytes = [0; 8];
+        r.read_exact(&mut bytes)?;
+        let [a, f, b, c, d, e, h, l] = bytes;
+        self.a = a;
+        self.f = f & 0b11110000;
+        self.b = b;
+        self.c = c;
+        self.d = d;
+        self.e = e;
+        self.h = h;
+        self.l = l;
+        self.pc = savestate::read_u16(r)?;
+        self.sp = savestate::read_u16(r)?;
+        Ok(())
+    }
+}
+
 impl std::fmt::Debug for Registers {
     fn fmt(&self, w: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(w, "Registers {{\
@@ -0,0 +1,477 @@
+use std::io::{self, BufRead, Write};
+
+use crate::debugger::{Condition, Register, StopReason};
+use crate::disasm::{disassemble, Instruction};
+use crate::gameboy::GameBoy;
+use crate::memsearch::{Filter, MemorySearch, ValueSize};
+
+const HELP: &str = "\
+Numbers are hexadecimal (optionally prefixed with $ or 0x), except counts and scanlines.
+Addresses can also be labels from a symbol file.
+  c, continue                   resume execution
+  s, step [N]                   execute N instructions (default 1)
+  n, next                       step over CALL and RST
+  finish                        run until the current subroutine returns
+  scanline LY                   run until LY reaches the given line
+  vblank                        run until the next VBlank
+  b, break ADDR [if REG OP VAL] break at ADDR, optionally only when a register
+                                comparison (==, !=, <, <=, >, >=) holds
+  w, watch ADDR [r|w|x|rw|rwx]  break when ADDR is read, written or executed (default w)
+  d, delete ID                  remove a breakpoint or watchpoint
+  i, info                       list breakpoints and watchpoints
+  bt, backtrace                 show calls and interrupts that haven't returned
+  r, regs                       show CPU registers
+  l, list [ADDR] [N]            disassemble N instructions (default 10) from ADDR or PC
+  x [BANK:]ADDR [LEN]           dump memory (default 64 bytes), from the mapped bank or BANK
+  io                            show IO registers
+  set REG VAL                   change a register
+  poke ADDR VAL...              write bytes to memory, starting at ADDR
+  search new [8|16]             start searching RAM for an 8-bit (default) or 16-bit value
+  search == VAL | != VAL        keep addresses holding (or not holding) VAL
+  search changed | unchanged    keep addresses whose value changed (or not) since the last search
+  search inc | dec              keep addresses whose value increased (or decreased)
+  search [list]                 show the addresses left
+  cheat [list]                  list cheats
+  cheat add CODE [NAME]         add a Game Genie (ABC-DEF[-GHI]) or GameShark (BBVVLLHH) code
+  cheat on|off ID               enable or disable a cheat
+  cheat del ID                  remove a cheat
+  q, quit                       exit the emulator
+An empty line repeats the previous command.";
+
+/// Named IO registers shown by the `io` command.
+const IO_REGISTERS: [(&str, u16); 11] = [
+    ("P1", 0xFF00), ("DIV", 0xFF04), ("TIMA", 0xFF05), ("TMA", 0xFF06),
+    ("IF", 0xFF0F), ("LCDC", 0xFF40), ("STAT", 0xFF41), ("SCY", 0xFF42),
+    ("SCX", 0xFF43), ("LY", 0xFF44), ("IE", 0xFFFF),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsoleResult {
+    /// The debugger was told to resume; keep running the machine
+    Resume,
+    Quit,
+}
+
+/// Interactive command prompt for a paused debugger.
+///
+/// Reads commands from `input` until one of them resumes execution.
+pub fn console(gb: &mut GameBoy, input: &mut dyn BufRead) -> ConsoleResult {
+    if let Some(reason) = gb.debugger.stop_reason() {
+        print_stop_reason(reason);
+    }
+    print_location(gb);
+
+    let mut last_command = String::new();
+    loop {
+        print!("(feox) ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return ConsoleResult::Quit,
+            Ok(_) => (),
+        }
+        let line = if line.trim().is_empty() { last_command.clone() } else { line.trim().to_string() };
+        last_command = line.clone();
+
+        match run_command(gb, &line) {
+            Ok(Some(result)) => return result,
+            Ok(None) => (),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
+
+fn run_command(gb: &mut GameBoy, line: &str) -> Result<Option<ConsoleResult>, String> {
+    let args: Vec<&str> = line.split_whitespace().collect();
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (*command, args),
+        None => return Ok(None),
+    };
+
+    match command {
+        "h" | "help" => println!("{}", HELP),
+        "c" | "continue" => {
+            gb.debugger.resume_continue(&gb.cpu);
+            return Ok(Some(ConsoleResult::Resume));
+        }
+        "s" | "step" => {
+            let count = match args.first() {
+                Some(n) => n.parse().map_err(|_| format!("invalid count '{}'", n))?,
+                None => 1,
+            };
+            gb.debugger.resume_step(&gb.cpu, count);
+            return Ok(Some(ConsoleResult::Resume));
+        }
+        "n" | "next" => {
+            gb.debugger.resume_step_over(&gb.cpu, &gb.emulator);
+            return Ok(Some(ConsoleResult::Resume));
+        }
+        "finish" => {
+            gb.debugger.resume_step_out(&gb.cpu);
+            return Ok(Some(ConsoleResult::Resume));
+        }
+        "scanline" => {
+            let line = args.first().ok_or("expected a scanline")?;
+            let line = line.parse().map_err(|_| format!("invalid scanline '{}'", line))?;
+            gb.debugger.resume_to_scanline(&gb.cpu, line);
+            return Ok(Some(ConsoleResult::Resume));
+        }
+        "vblank" => {
+            gb.debugger.resume_to_vblank(&gb.cpu);
+            return Ok(Some(ConsoleResult::Resume));
+        }
+        "b" | "break" => {
+            let (address, bank) = parse_location(gb, args.first().ok_or("expected an address")?)?;
+            let condition = match args.get(1) {
+                Some(&"if") => Some(parse_condition(&args[2..])?),
+                Some(other) => return Err(format!("unexpected '{}'", other)),
+                None => None,
+            };
+            let id = gb.debugger.add_breakpoint(address, bank, condition);
+            println!("Breakpoint {} at {}", id, describe(gb, address, bank));
+        }
+        "w" | "watch" => {
+            let address = parse_address(gb, args.first().ok_or("expected an address")?)?;
+            let kind = args.get(1).copied().unwrap_or("w");
+            if kind.is_empty() || !kind.chars().all(|c| "rwx".contains(c)) {
+                return Err(format!("invalid watchpoint kind '{}'", kind));
+            }
+            let id = gb.debugger.add_watchpoint(address, kind.contains('r'), kind.contains('w'), kind.contains('x'));
+            println!("Watchpoint {} at {} ({})", id, describe(gb, address, None), kind);
+        }
+        "d" | "delete" => {
+            let id = args.first().ok_or("expected an id")?;
+            let id = id.parse().map_err(|_| format!("invalid id '{}'", id))?;
+            if !gb.debugger.delete(id) {
+                return Err(format!("no breakpoint or watchpoint {}", id));
+            }
+        }
+        "i" | "info" => {
+            for b in gb.debugger.breakpoints() {
+                let location = describe(gb, b.address, b.bank);
+                match &b.condition {
+                    Some(c) => println!("{}: break {} if {}", b.id, location, c),
+                    None => println!("{}: break {}", b.id, location),
+                }
+            }
+            for w in gb.debugger.watchpoints() {
+                let kind: String = [(w.read, 'r'), (w.write, 'w'), (w.execute, 'x')].iter()
+                    .filter(|(enabled, _)| *enabled)
+                    .map(|(_, c)| *c)
+                    .collect();
+                println!("{}: watch {} ({})", w.id, describe(gb, w.address, None), kind);
+            }
+        }
+        "bt" | "backtrace" => {
+            let pc = gb.cpu.registers().pc;
+            println!("#0 {}", describe(gb, pc, None));
+            for (i, frame) in gb.debugger.call_stack().iter().rev().enumerate() {
+                println!("#{} {} (called {})", i + 1, describe(gb, frame.return_address, None),
+                         describe(gb, frame.entry, None));
+            }
+        }
+        "r" | "regs" => print_registers(gb),
+        "l" | "list" => {
+            let mut address = match args.first() {
+                Some(address) => parse_address(gb, address)?,
+                None => gb.cpu.registers().pc,
+            };
+            let count = match args.get(1) {
+                Some(n) => parse_count(n)?,
+                None => 10,
+            };
+            for _ in 0..count {
+                let instruction = disassemble_at(gb, address);
+                print_instruction(gb, &instruction);
+                address = instruction.next_address();
+            }
+        }
+        "x" => {
+            let (address, bank) = parse_banked_address(gb, args.first().ok_or("expected an address")?)?;
+            let length = match args.get(1) {
+                Some(n) => parse_count(n)?,
+                None => 64,
+            };
+            for row in (0..length).step_by(16) {
+                let start = address.wrapping_add(row as u16);
+                let bytes = (0..16.min(length - row))
+                    .map(|i| gb.emulator.read_banked(bank, start.wrapping_add(i as u16)))
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(|| format!("no bank {:X} at ${:04X}", bank, start))?;
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                let text: String = bytes.iter()
+                    .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                    .collect();
+                println!("{:02X}:{:04X}: {:<47}  {}", bank, start, hex.join(" "), text);
+            }
+        }
+        "io" => {
+            for (name, address) in IO_REGISTERS.iter() {
+                println!("{:>4} (${:04X}) = ${:02X}", name, address, gb.emulator.read_mapped(*address));
+            }
+        }
+        "set" => {
+            let register: Register = args.first().ok_or("expected a register")?.parse()?;
+            let value = parse_number(args.get(1).ok_or("expected a value")?)?;
+            register.write(&mut gb.cpu, value);
+        }
+        "poke" => {
+            let address = parse_address(gb, args.first().ok_or("expected an address")?)?;
+            if args.len() < 2 {
+                return Err("expected a value".to_string());
+            }
+            for (i, value) in args[1..].iter().enumerate() {
+                let value = parse_number(value)?;
+                gb.emulator.write_mapped(address.wrapping_add(i as u16), value as u8);
+            }
+        }
+        "search" => search(gb, args)?,
+        "cheat" => cheat(gb, args)?,
+        "q" | "quit" => return Ok(Some(ConsoleResult::Quit)),
+        _ => return Err(format!("unknown command '{}', try 'help'", command)),
+    }
+    Ok(None)
+}
+
+fn search(gb: &mut GameBoy, args: &[&str]) -> Result<(), String> {
+    let value = || -> Result<u16, String> { parse_number(args.get(1).ok_or("expected a value")?) };
+    let filter = match args.first().copied().unwrap_or("list") {
+        "new" => {
+            let size = match args.get(1) {
+                Some(size) => size.parse()?,
+                None => ValueSize::Byte,
+            };
+            let search = MemorySearch::new(&gb.emulator, size);
+            println!("Searching {} addresses", search.candidates().len());
+            gb.search = Some(search);
+            return Ok(());
+        }
+        "list" => None,
+        "==" => Some(Filter::Equal(value()?)),
+        "!=" => Some(Filter::NotEqual(value()?)),
+        "changed" => Some(Filter::Changed),
+        "unchanged" => Some(Filter::Unchanged),
+        "inc" => Some(Filter::Increased),
+        "dec" => Some(Filter::Decreased),
+        other => return Err(format!("unknown search '{}'", other)),
+    };
+
+    let search = gb.search.as_mut().ok_or("no search in progress, start one with 'search new'")?;
+    if let Some(filter) = filter {
+        search.filter(&gb.emulator, filter);
+    }
+    let candidates = search.candidates().to_vec();
+    let digits = match search.size() {
+        ValueSize::Byte => 2,
+        ValueSize::Word => 4,
+    };
+    println!("{} addresses left", candidates.len());
+    // Listing thousands of addresses right after starting isn't useful
+    const MAX_LISTED: usize = 20;
+    for candidate in candidates.iter().take(MAX_LISTED) {
+        println!("  {} = ${:0width$X}", describe(gb, candidate.address, None), candidate.value, width = digits);
+    }
+    if candidates.len() > MAX_LISTED {
+        println!("  ...");
+    }
+    Ok(())
+}
+
+fn cheat(gb: &mut GameBoy, args: &[&str]) -> Result<(), String> {
+    // Cheats are numbered from 1 in the console
+    let id = || -> Result<usize, String> {
+        let id = args.get(1).ok_or("expected an id")?;
+        match id.parse::<usize>() {
+            Ok(id) if id >= 1 && id <= gb.cheats.cheats().len() => Ok(id - 1),
+            _ => Err(format!("no cheat {}", id)),
+        }
+    };
+    match args.first().copied().unwrap_or("list") {
+        "list" => {
+            if !gb.cheats.all_enabled() {
+                println!("All cheats are disabled");
+            }
+            for (i, cheat) in gb.cheats.cheats().iter().enumerate() {
+                println!("{}: {}", i + 1, cheat);
+            }
+        }
+        "add" => {
+            let code = args.get(1).ok_or("expected a code")?;
+            let index = gb.cheats.add(code, &args[2..].join(" "))?;
+            println!("Cheat {}: {}", index + 1, gb.cheats.cheats()[index]);
+        }
+        kind @ "on" | kind @ "off" => {
+            let index = id()?;
+            gb.cheats.set_enabled(index, kind == "on");
+        }
+        "del" => {
+            let index = id()?;
+            gb.cheats.remove(index);
+        }
+        other => return Err(format!("unknown cheat command '{}'", other)),
+    }
+    Ok(())
+}
+
+fn parse_number(s: &str) -> Result<u16, String> {
+    let digits = s.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid number '{}'", s))
+}
+
+/// Parse a count, which unlike addresses and values is decimal.
+fn parse_count(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("invalid count '{}'", s))
+}
+
+/// Parse a label or an address. Labels in the switchable ROM bank also give their bank.
+///
+/// Labels come first, so one that looks like hex (`Fade`) still works; a `$` or `0x`
+/// prefix makes it a number.
+fn parse_location(gb: &GameBoy, s: &str) -> Result<(u16, Option<u16>), String> {
+    match gb.symbols.find(s) {
+        Some(symbol) if (0x4000..=0x7FFF).contains(&symbol.address) => Ok((symbol.address, Some(symbol.bank))),
+        Some(symbol) => Ok((symbol.address, None)),
+        None => parse_number(s).map(|address| (address, None))
+            .map_err(|_| format!("invalid address or unknown label '{}'", s)),
+    }
+}
+
+fn parse_address(gb: &GameBoy, s: &str) -> Result<u16, String> {
+    parse_location(gb, s).map(|(address, _)| address)
+}
+
+/// Parse `BANK:ADDR`, or an address or label in the bank currently mapped there.
+fn parse_banked_address(gb: &GameBoy, s: &str) -> Result<(u16, u16), String> {
+    let mut parts = s.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(bank), Some(address)) => Ok((parse_address(gb, address)?, parse_number(bank)?)),
+        _ => {
+            let (address, bank) = parse_location(gb, s)?;
+            Ok((address, bank.unwrap_or_else(|| gb.emulator.mapped_bank(address))))
+        }
+    }
+}
+
+/// `$ADDR`, followed by the nearest label if there is one, e.g. `$0153 <Main+$3>`.
+/// Without a `bank`, the label is looked up in whatever bank is mapped at `address`.
+fn describe(gb: &GameBoy, address: u16, bank: Option<u16>) -> String {
+    let bank = bank.unwrap_or_else(|| gb.emulator.mapped_bank(address));
+    match gb.symbols.describe(bank, address) {
+        Some(label) => format!("${:04X} <{}>", address, label),
+        None => format!("${:04X}", address),
+    }
+}
+
+fn parse_condition(args: &[&str]) -> Result<Condition, String> {
+    match args {
+        [register, comparison, value] => Ok(Condition {
+            register: register.parse()?,
+            comparison: comparison.parse()?,
+            value: parse_number(value)?,
+        }),
+        _ => Err("expected a condition like 'a == 10'".to_string()),
+    }
+}
+
+fn print_stop_reason(reason: StopReason) {
+    match reason {
+        StopReason::Requested => println!("Paused"),
+        StopReason::Breakpoint(id) => println!("Breakpoint {}", id),
+        StopReason::Watchpoint(id, Some(access)) => {
+            let kind = if access.write { "write" } else { "read" };
+            println!("Watchpoint {}: {} ${:02X} at ${:04X}", id, kind, access.value, access.address);
+        }
+        StopReason::Watchpoint(id, None) => println!("Watchpoint {}: execute", id),
+        StopReason::Step => (),
+        StopReason::Scanline(line) => println!("Reached scanline {}", line),
+        StopReason::VBlank => println!("Reached VBlank"),
+    }
+}
+
+fn print_location(gb: &GameBoy) {
+    print_instruction(gb, &disassemble_at(gb, gb.cpu.registers().pc));
+}
+
+fn disassemble_at(gb: &GameBoy, address: u16) -> Instruction {
+    disassemble(address, |address| gb.emulator.read_mapped(address))
+}
+
+fn print_instruction(gb: &GameBoy, instruction: &Instruction) {
+    let bank = gb.emulator.mapped_bank(instruction.address);
+    if let Some(symbol) = gb.symbols.get(bank, instruction.address) {
+        println!("{}:", symbol.name);
+    }
+    let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    let text = instruction.text_with_symbols(&gb.symbols, |address| gb.emulator.mapped_bank(address));
+    println!("${:04X}: {:<9} {}", instruction.address, bytes.join(" "), text);
+}
+
+fn print_registers(gb: &GameBoy) {
+    let cpu = &gb.cpu;
+    let regs = cpu.registers();
+    let flags: String = [(regs.zero(), 'Z'), (regs.sub(), 'N'), (regs.half_carry(), 'H'), (regs.carry(), 'C')]
+        .iter()
+        .map(|&(set, c)| if set { c } else { '-' })
+        .collect();
+    println!("AF=${:04X} BC=${:04X} DE=${:04X} HL=${:04X} SP=${:04X} PC=${:04X}",
+             Register::AF.read(cpu), Register::BC.read(cpu), Register::DE.read(cpu),
+             Register::HL.read(cpu), regs.sp, regs.pc);
+    println!("Flags {}  IME={}  halted={}  LY={}",
+             flags, cpu.interrupts_enabled() as u8, cpu.halted() as u8, gb.emulator.ppu.current_line());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+    use crate::symbols::Symbol;
+
+    fn gameboy_with_labels(labels: &[(u16, u16, &str)]) -> GameBoy {
+        let mut gb = GameBoy::new(Model::Dmg);
+        for &(bank, address, name) in labels {
+            gb.symbols.insert(Symbol { bank, address, name: name.to_string() });
+        }
+        gb
+    }
+
+    #[test]
+    fn parses_numbers() {
+        let gb = gameboy_with_labels(&[]);
+        assert_eq!(parse_location(&gb, "150"), Ok((0x0150, None)));
+        assert_eq!(parse_location(&gb, "$C000"), Ok((0xC000, None)));
+        assert_eq!(parse_location(&gb, "0xff80"), Ok((0xFF80, None)));
+        assert!(parse_location(&gb, "Nowhere").is_err());
+    }
+
+    #[test]
+    fn labels_that_look_like_hex_are_labels() {
+        let gb = gameboy_with_labels(&[(0, 0x0200, "Fade"), (2, 0x4100, "add")]);
+        assert_eq!(parse_location(&gb, "Fade"), Ok((0x0200, None)));
+        assert_eq!(parse_location(&gb, "add"), Ok((0x4100, Some(2))));
+        assert_eq!(parse_location(&gb, "$add"), Ok((0x0ADD, None)));
+        assert_eq!(parse_location(&gb, "0xFade"), Ok((0xFADE, None)));
+    }
+
+    #[test]
+    fn parses_banked_addresses() {
+        let gb = gameboy_with_labels(&[(3, 0x4000, "Bank3"), (1, 0xD000, "wBuffer")]);
+        assert_eq!(parse_banked_address(&gb, "2:4000"), Ok((0x4000, 2)));
+        assert_eq!(parse_banked_address(&gb, "Bank3"), Ok((0x4000, 3)));
+        assert_eq!(parse_banked_address(&gb, "wBuffer"), Ok((0xD000, 1)));
+        assert_eq!(parse_banked_address(&gb, "C000"), Ok((0xC000, 0)));
+    }
+
+    #[test]
+    fn counts_are_decimal() {
+        assert_eq!(parse_count("10"), Ok(10));
+        assert!(parse_count("$10").is_err());
+        assert!(parse_count("1F").is_err());
+
+        let mut gb = gameboy_with_labels(&[]);
+        assert_eq!(run_command(&mut gb, "x C000 32"), Ok(None));
+        assert_eq!(run_command(&mut gb, "x C000 20"), Ok(None));
+        assert!(run_command(&mut gb, "x C000 2A").is_err());
+        assert!(run_command(&mut gb, "list 150 A").is_err());
+    }
+}
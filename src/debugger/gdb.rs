@@ -0,0 +1,460 @@
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+use crate::debugger::{Register, StopReason};
+use crate::gameboy::GameBoy;
+
+/// Registers in the order gdb numbers them, as described by `TARGET_XML`.
+const REGISTERS: [Register; 6] = [
+    Register::AF, Register::BC, Register::DE, Register::HL, Register::SP, Register::PC,
+];
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.feox.sm83">
+    <reg name="af" bitsize="16" type="uint16" regnum="0"/>
+    <reg name="bc" bitsize="16" type="uint16"/>
+    <reg name="de" bitsize="16" type="uint16"/>
+    <reg name="hl" bitsize="16" type="uint16"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// Byte gdb sends to interrupt a running target.
+const INTERRUPT: u8 = 0x03;
+/// Largest packet we accept and send, advertised to gdb in `qSupported`
+const PACKET_SIZE: usize = 0x1000;
+
+/// A gdb remote serial protocol server, driving the same `Debugger` as the console.
+///
+/// The frontend calls `poll` between frames. While gdb is attached and the
+/// machine is stopped, `poll` blocks serving requests until gdb resumes it.
+pub struct GdbStub {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    /// Whether gdb is waiting for the machine to stop
+    running: bool,
+    /// Breakpoints and watchpoints set by gdb: (`Z` type, address, debugger id)
+    points: Vec<(u8, u16, usize)>,
+}
+
+enum Packet {
+    Command(String),
+    Interrupt,
+}
+
+impl GdbStub {
+    /// Listen for gdb on `localhost:port`.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            stream: None,
+            running: false,
+            points: Vec::new(),
+        })
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Accept a connection, report stops and serve requests while stopped.
+    pub fn poll(&mut self, gb: &mut GameBoy) {
+        if let Err(e) = self.try_poll(gb) {
+            eprintln!("gdb connection closed: {}", e);
+            self.detach(gb);
+        }
+    }
+
+    fn try_poll(&mut self, gb: &mut GameBoy) -> io::Result<()> {
+        if self.stream.is_none() {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    println!("gdb attached from {}", address);
+                    stream.set_nodelay(true)?;
+                    self.stream = Some(stream);
+                    self.running = false;
+                    gb.debugger.pause();
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if self.running {
+            if let Some(Packet::Interrupt) = self.read_packet(false)? {
+                gb.debugger.pause();
+            }
+            if !gb.debugger.is_paused() {
+                return Ok(());
+            }
+            self.running = false;
+            let reply = stop_reply(gb);
+            self.send(&reply)?;
+        }
+
+        while self.stream.is_some() && !self.running {
+            match self.read_packet(true)? {
+                Some(Packet::Command(command)) => self.handle(gb, &command)?,
+                Some(Packet::Interrupt) | None => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn detach(&mut self, gb: &mut GameBoy) {
+        self.stream = None;
+        self.running = false;
+        for (_, _, id) in self.points.drain(..) {
+            gb.debugger.delete(id);
+        }
+        if gb.debugger.is_paused() {
+            gb.debugger.resume_continue(&gb.cpu);
+        }
+    }
+
+    fn handle(&mut self, gb: &mut GameBoy, command: &str) -> io::Result<()> {
+        if command.is_empty() || !command.is_char_boundary(1) {
+            return self.send("");
+        }
+        let (kind, args) = command.split_at(1);
+        let reply = match kind {
+            "?" => stop_reply(gb),
+            "g" => REGISTERS.iter().map(|r| hex_word(r.read(&gb.cpu))).collect(),
+            "G" => {
+                for (i, register) in REGISTERS.iter().enumerate() {
+                    match args.get(i * 4..i * 4 + 4).and_then(parse_hex_word) {
+                        Some(value) => register.write(&mut gb.cpu, value),
+                        None => return self.send("E01"),
+                    }
+                }
+                "OK".to_string()
+            }
+            "p" => match parse_hex(args).and_then(|n| REGISTERS.get(n)) {
+                Some(register) => hex_word(register.read(&gb.cpu)),
+                None => "E01".to_string(),
+            },
+            "P" => {
+                let mut parts = args.splitn(2, '=');
+                let register = parts.next().and_then(parse_hex).and_then(|n| REGISTERS.get(n));
+                match (register, parts.next().and_then(parse_hex_word)) {
+                    (Some(register), Some(value)) => {
+                        register.write(&mut gb.cpu, value);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            // Replies are two hex digits per byte, so long reads are cut short
+            // to fit a packet; gdb asks again for the rest
+            "m" => match parse_address_length(args) {
+                Some((address, length)) => (0..length.min(PACKET_SIZE / 2))
+                    .map(|i| format!("{:02x}", gb.emulator.read_mapped(address.wrapping_add(i as u16))))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let mut parts = args.splitn(2, ':');
+                let range = parts.next().and_then(parse_address_length);
+                let data = parts.next().and_then(parse_hex_bytes);
+                match (range, data) {
+                    (Some((address, length)), Some(data)) if data.len() == length => {
+                        for (i, byte) in data.into_iter().enumerate() {
+                            gb.emulator.write_mapped(address.wrapping_add(i as u16), byte);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "Z" | "z" => self.set_point(gb, kind == "Z", args),
+            "c" => {
+                if let Some(address) = parse_hex(args) {
+                    Register::PC.write(&mut gb.cpu, address as u16);
+                }
+                gb.debugger.resume_continue(&gb.cpu);
+                self.running = true;
+                return Ok(());
+            }
+            "s" => {
+                gb.debugger.resume_step(&gb.cpu, 1);
+                self.running = true;
+                return Ok(());
+            }
+            "D" | "k" => {
+                self.send("OK").ok();
+                println!("gdb detached");
+                self.detach(gb);
+                return Ok(());
+            }
+            "H" => "OK".to_string(),
+            "q" => query(args),
+            _ => String::new(),
+        };
+        self.send(&reply)
+    }
+
+    /// Handle `Z`/`z` packets: `type,address,kind`.
+    fn set_point(&mut self, gb: &mut GameBoy, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+        let kind = parts.next().and_then(|k| k.parse::<u8>().ok());
+        let address = parts.next().and_then(parse_hex).map(|a| a as u16);
+        let (kind, address) = match (kind, address) {
+            (Some(kind), Some(address)) if kind <= 4 => (kind, address),
+            _ => return String::new(),
+        };
+
+        if insert {
+            let id = match kind {
+                // software and hardware breakpoints
+                0 | 1 => gb.debugger.add_breakpoint(address, None, None),
+                2 => gb.debugger.add_watchpoint(address, false, true, false),
+                3 => gb.debugger.add_watchpoint(address, true, false, false),
+                _ => gb.debugger.add_watchpoint(address, true, true, false),
+            };
+            self.points.push((kind, address, id));
+        } else if let Some(i) = self.points.iter().position(|&(k, a, _)| k == kind && a == address) {
+            let (_, _, id) = self.points.remove(i);
+            gb.debugger.delete(id);
+        }
+        "OK".to_string()
+    }
+
+    /// Read a packet, acknowledging it. Without `block`, returns `None` if nothing has arrived.
+    fn read_packet(&mut self, block: bool) -> io::Result<Option<Packet>> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+        stream.set_nonblocking(!block)?;
+        let first = match read_byte(stream) {
+            Ok(byte) => byte,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        stream.set_nonblocking(false)?;
+
+        match first {
+            INTERRUPT => Ok(Some(Packet::Interrupt)),
+            b'$' => {
+                let mut data = Vec::new();
+                loop {
+                    match read_byte(stream)? {
+                        b'#' => break,
+                        _ if data.len() == PACKET_SIZE => {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData, "packet too long"));
+                        }
+                        byte => data.push(byte),
+                    }
+                }
+                let checksum = [read_byte(stream)?, read_byte(stream)?];
+                let valid = std::str::from_utf8(&checksum).ok()
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+                    == Some(checksum_of(&data));
+                if !valid {
+                    stream.write_all(b"-")?;
+                    return Ok(None);
+                }
+                stream.write_all(b"+")?;
+                Ok(Some(Packet::Command(String::from_utf8_lossy(&data).into_owned())))
+            }
+            // acknowledgements of our own packets
+            _ => Ok(None),
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        if let Some(stream) = &mut self.stream {
+            stream.write_all(packet(data).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn stop_reply(gb: &GameBoy) -> String {
+    match gb.debugger.stop_reason() {
+        Some(StopReason::Requested) => "S02".to_string(),
+        Some(StopReason::Watchpoint(_, Some(access))) => {
+            let kind = if access.write { "watch" } else { "rwatch" };
+            format!("T05{}:{:04x};", kind, access.address)
+        }
+        _ => "S05".to_string(),
+    }
+}
+
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+    } else if args == "Attached" {
+        "1".to_string()
+    } else if args == "C" {
+        "QC1".to_string()
+    } else if args == "fThreadInfo" {
+        "m1".to_string()
+    } else if args == "sThreadInfo" {
+        "l".to_string()
+    } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        match parse_address_length(range) {
+            Some((offset, length)) => {
+                // Leave room for the `m` or `l` in front
+                let offset = (offset as usize).min(TARGET_XML.len());
+                let end = (offset + length.min(PACKET_SIZE - 1)).min(TARGET_XML.len());
+                let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                format!("{}{}", more, &TARGET_XML[offset..end])
+            }
+            None => "E01".to_string(),
+        }
+    } else {
+        String::new()
+    }
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<u8> {
+    let mut byte = [0];
+    match stream.read(&mut byte)? {
+        0 => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "gdb disconnected")),
+        _ => Ok(byte[0]),
+    }
+}
+
+/// Frame `data` as a packet: `$data#checksum`.
+fn packet(data: &str) -> String {
+    format!("${}#{:02x}", data, checksum_of(data.as_bytes()))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Registers are sent as little endian hex.
+fn hex_word(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+
+fn parse_hex_word(s: &str) -> Option<u16> {
+    match parse_hex_bytes(s)?[..] {
+        [lo, hi] => Some((hi as u16) << 8 | lo as u16),
+        _ => None,
+    }
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes().chunks(2)
+        .map(|pair| std::str::from_utf8(pair).ok()
+            .filter(|pair| pair.len() == 2)
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+/// Parse `address,length`.
+fn parse_address_length(s: &str) -> Option<(u16, usize)> {
+    let mut parts = s.splitn(2, ',');
+    let address = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let length = parse_hex(parts.next()?)?;
+    Some((address, length))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::input_gameboy;
+
+    /// Send `packets` to a stub attached to `gb`, followed by a detach, and return
+    /// everything it sent back.
+    fn exchange(gb: &mut GameBoy, packets: &[&str]) -> String {
+        let mut stub = GdbStub::bind(0).unwrap();
+        let port = stub.listener.local_addr().unwrap().port();
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        for &data in packets {
+            client.write_all(data.as_bytes()).unwrap();
+        }
+        client.write_all(packet("D").as_bytes()).unwrap();
+        stub.poll(gb);
+        assert!(!stub.is_attached());
+        drop(stub);
+
+        let mut output = String::new();
+        client.read_to_string(&mut output).unwrap();
+        output
+    }
+
+    /// The replies in `output`, checking their checksums and leaving out acknowledgements.
+    fn replies(output: &str) -> Vec<&str> {
+        output.split('$').skip(1).map(|framed| {
+            let (data, checksum) = framed.split_at(framed.find('#').unwrap());
+            assert_eq!(&checksum[1..3], format!("{:02x}", checksum_of(data.as_bytes())));
+            data
+        }).collect()
+    }
+
+    #[test]
+    fn frames_packets() {
+        assert_eq!(packet("OK"), "$OK#9a");
+        assert_eq!(packet(""), "$#00");
+
+        let mut gb = input_gameboy();
+        let output = exchange(&mut gb, &["$m100,4#00", &packet("m100,4")]);
+        // The bad checksum is refused and gets no reply
+        assert!(output.starts_with("-+$2100803e#"));
+        assert_eq!(replies(&output), ["2100803e", "OK"]);
+    }
+
+    #[test]
+    fn clamps_memory_reads_to_a_packet() {
+        let mut gb = input_gameboy();
+        let output = exchange(&mut gb, &[&packet("m0,ffff"), &packet("mc000"), &packet("m1c000,1")]);
+        let replies = replies(&output);
+        assert_eq!(replies[0].len(), PACKET_SIZE);
+        assert_eq!(&replies[1..], ["E01", "E01", "OK"]);
+    }
+
+    #[test]
+    fn parses_arguments() {
+        assert_eq!(parse_address_length("c000,10"), Some((0xC000, 16)));
+        assert_eq!(parse_address_length("ffff,0"), Some((0xFFFF, 0)));
+        assert_eq!(parse_address_length("10000,1"), None);
+        assert_eq!(parse_address_length("c000"), None);
+        assert_eq!(parse_address_length("c000,"), None);
+
+        assert_eq!(parse_hex_word("3412"), Some(0x1234));
+        assert_eq!(parse_hex_word(&hex_word(0xBEEF)), Some(0xBEEF));
+        assert_eq!(parse_hex_word("12"), None);
+        assert_eq!(parse_hex_word("123456"), None);
+        assert_eq!(parse_hex_word("12g4"), None);
+    }
+
+    #[test]
+    fn pages_the_target_description() {
+        assert_eq!(query("Supported:multiprocess+"), "PacketSize=1000;qXfer:features:read+");
+
+        let mut xml = String::new();
+        let mut offset = 0;
+        loop {
+            let reply = query(&format!("Xfer:features:read:target.xml:{:x},{:x}", offset, 0x40));
+            let (more, data) = reply.split_at(1);
+            assert!(data.len() <= 0x40);
+            xml.push_str(data);
+            offset += data.len();
+            if more == "l" {
+                break;
+            }
+            assert_eq!(more, "m");
+        }
+        assert_eq!(xml, TARGET_XML);
+
+        // Past the end, and with a length too long for a packet
+        assert_eq!(query(&format!("Xfer:features:read:target.xml:{:x},10", TARGET_XML.len() + 5)), "l");
+        let reply = query("Xfer:features:read:target.xml:0,ffffffffffffffff");
+        assert_eq!(reply, format!("l{}", TARGET_XML));
+    }
+}
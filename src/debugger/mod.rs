@@ -0,0 +1,436 @@
+mod console;
+mod gdb;
+
+pub use console::{console, ConsoleResult};
+pub use gdb::GdbStub;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cpu::{Cpu, Reg16};
+use crate::disasm::disassemble;
+use crate::emulator::{Emulator, Access};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Register {
+    A, F, B, C, D, E, H, L,
+    AF, BC, DE, HL, SP, PC,
+}
+
+impl Register {
+    pub fn read(self, cpu: &Cpu) -> u16 {
+        let regs = cpu.registers();
+        match self {
+            Register::A => regs.a as u16,
+            Register::F => regs.f as u16,
+            Register::B => regs.b as u16,
+            Register::C => regs.c as u16,
+            Register::D => regs.d as u16,
+            Register::E => regs.e as u16,
+            Register::H => regs.h as u16,
+            Register::L => regs.l as u16,
+            Register::AF => regs.read_pair(Reg16::AF),
+            Register::BC => regs.read_pair(Reg16::BC),
+            Register::DE => regs.read_pair(Reg16::DE),
+            Register::HL => regs.read_pair(Reg16::HL),
+            Register::SP => regs.sp,
+            Register::PC => regs.pc,
+        }
+    }
+
+    pub fn write(self, cpu: &mut Cpu, value: u16) {
+        let regs = cpu.registers_mut();
+        match self {
+            Register::A => regs.a = value as u8,
+            Register::F => regs.f = value as u8 & 0xF0,
+            Register::B => regs.b = value as u8,
+            Register::C => regs.c = value as u8,
+            Register::D => regs.d = value as u8,
+            Register::E => regs.e = value as u8,
+            Register::H => regs.h = value as u8,
+            Register::L => regs.l = value as u8,
+            Register::AF => regs.write_pair(Reg16::AF, value),
+            Register::BC => regs.write_pair(Reg16::BC, value),
+            Register::DE => regs.write_pair(Reg16::DE, value),
+            Register::HL => regs.write_pair(Reg16::HL, value),
+            Register::SP => regs.sp = value,
+            Register::PC => regs.pc = value,
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "a" => Ok(Register::A),
+            "f" => Ok(Register::F),
+            "b" => Ok(Register::B),
+            "c" => Ok(Register::C),
+            "d" => Ok(Register::D),
+            "e" => Ok(Register::E),
+            "h" => Ok(Register::H),
+            "l" => Ok(Register::L),
+            "af" => Ok(Register::AF),
+            "bc" => Ok(Register::BC),
+            "de" => Ok(Register::DE),
+            "hl" => Ok(Register::HL),
+            "sp" => Ok(Register::SP),
+            "pc" => Ok(Register::PC),
+            _ => Err(format!("unknown register '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "==" => Ok(Comparison::Equal),
+            "!=" => Ok(Comparison::NotEqual),
+            "<" => Ok(Comparison::Less),
+            "<=" => Ok(Comparison::LessOrEqual),
+            ">" => Ok(Comparison::Greater),
+            ">=" => Ok(Comparison::GreaterOrEqual),
+            _ => Err(format!("unknown comparison '{}'", s)),
+        }
+    }
+}
+
+/// A condition on a register value, e.g. `a == 0x10`.
+#[derive(Debug, Copy, Clone)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        let current = self.register.read(cpu);
+        match self.comparison {
+            Comparison::Equal => current == self.value,
+            Comparison::NotEqual => current != self.value,
+            Comparison::Less => current < self.value,
+            Comparison::LessOrEqual => current <= self.value,
+            Comparison::Greater => current > self.value,
+            Comparison::GreaterOrEqual => current >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.comparison {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        };
+        write!(w, "{:?} {} ${:X}", self.register, op, self.value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub address: u16,
+    /// Only break while this ROM bank is mapped at `address`
+    pub bank: Option<u16>,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub id: usize,
+    pub address: u16,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+/// A subroutine call or interrupt that hasn't returned yet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Address the subroutine was entered at
+    pub entry: u16,
+    pub return_address: u16,
+    /// Stack pointer just after the return address was pushed
+    sp: u16,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// Execution was paused from outside, e.g. by the frontend
+    Requested,
+    Breakpoint(usize),
+    Watchpoint(usize, Option<Access>),
+    Step,
+    Scanline(u8),
+    VBlank,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RunMode {
+    Continue,
+    Step(u32),
+    /// Run until the instruction after a CALL or RST is reached with the stack unwound
+    StepOver { return_address: u16, sp: u16 },
+    /// Run until a return pops the stack above `sp`
+    StepOut { sp: u16 },
+    Scanline(u8),
+    VBlank,
+}
+
+/// Breakpoints, watchpoints and stepping.
+///
+/// `GameBoy` consults the debugger around every instruction; while the debugger
+/// is paused, the machine doesn't advance.
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    next_id: usize,
+    mode: RunMode,
+    stopped: Option<StopReason>,
+    /// Breakpoints at the address execution resumes from are skipped once,
+    /// otherwise continuing from a breakpoint would immediately hit it again
+    resume_pc: Option<u16>,
+    last_opcode: u8,
+    last_pc: u16,
+    last_sp: u16,
+    last_line: u8,
+    call_stack: Vec<Frame>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            next_id: 1,
+            mode: RunMode::Continue,
+            stopped: None,
+            resume_pc: None,
+            last_opcode: 0,
+            last_pc: 0,
+            last_sp: 0,
+            last_line: 0,
+            call_stack: Vec::new(),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.stopped.is_some()
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stopped
+    }
+
+    pub fn pause(&mut self) {
+        if self.stopped.is_none() {
+            self.stopped = Some(StopReason::Requested);
+        }
+    }
+
+    fn resume(&mut self, cpu: &Cpu, mode: RunMode) {
+        self.stopped = None;
+        self.resume_pc = Some(cpu.registers().pc);
+        self.mode = mode;
+    }
+
+    pub fn resume_continue(&mut self, cpu: &Cpu) {
+        self.resume(cpu, RunMode::Continue);
+    }
+
+    /// Execute `count` instructions, then pause again.
+    pub fn resume_step(&mut self, cpu: &Cpu, count: u32) {
+        self.resume(cpu, RunMode::Step(count.max(1)));
+    }
+
+    /// Like `resume_step`, but runs a called subroutine to completion.
+    pub fn resume_step_over(&mut self, cpu: &Cpu, emulator: &Emulator) {
+        let instruction = disassemble(cpu.registers().pc, |address| emulator.read_mapped(address));
+        if !instruction.is_call() {
+            return self.resume_step(cpu, 1);
+        }
+        self.resume(cpu, RunMode::StepOver {
+            return_address: instruction.next_address(),
+            sp: cpu.registers().sp,
+        });
+    }
+
+    /// Run until the current subroutine returns.
+    pub fn resume_step_out(&mut self, cpu: &Cpu) {
+        self.resume(cpu, RunMode::StepOut { sp: cpu.registers().sp });
+    }
+
+    /// Run until LY changes to `line`.
+    pub fn resume_to_scanline(&mut self, cpu: &Cpu, line: u8) {
+        self.resume(cpu, RunMode::Scanline(line));
+    }
+
+    /// Run until the PPU enters VBlank.
+    pub fn resume_to_vblank(&mut self, cpu: &Cpu) {
+        self.resume(cpu, RunMode::VBlank);
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16, bank: Option<u16>, condition: Option<Condition>) -> usize {
+        let id = self.next_id();
+        self.breakpoints.push(Breakpoint { id, address, bank, condition });
+        id
+    }
+
+    pub fn add_watchpoint(&mut self, address: u16, read: bool, write: bool, execute: bool) -> usize {
+        let id = self.next_id();
+        self.watchpoints.push(Watchpoint { id, address, read, write, execute });
+        id
+    }
+
+    /// Remove the breakpoint or watchpoint with the given id.
+    pub fn delete(&mut self, id: usize) -> bool {
+        let count = self.breakpoints.len() + self.watchpoints.len();
+        self.breakpoints.retain(|b| b.id != id);
+        self.watchpoints.retain(|w| w.id != id);
+        count != self.breakpoints.len() + self.watchpoints.len()
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Calls and interrupts that haven't returned yet, innermost last.
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
+    fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Called before every instruction. Returns true if the instruction must not run.
+    pub fn before_step(&mut self, cpu: &Cpu, emulator: &mut Emulator) -> bool {
+        if self.stopped.is_some() {
+            return true;
+        }
+        emulator.record_accesses = self.watchpoints.iter().any(|w| w.read || w.write);
+
+        let pc = cpu.registers().pc;
+        self.last_opcode = emulator.read_mapped(pc);
+        self.last_pc = pc;
+        self.last_sp = cpu.registers().sp;
+        if self.resume_pc.take() == Some(pc) {
+            return false;
+        }
+
+        let bank = emulator.mapped_bank(pc);
+        if let Some(b) = self.breakpoints.iter().find(|b| {
+            b.address == pc && b.bank.iter().all(|&wanted| wanted == bank) && b.condition.iter().all(|c| c.holds(cpu))
+        }) {
+            self.stopped = Some(StopReason::Breakpoint(b.id));
+        } else if let Some(w) = self.watchpoints.iter().find(|w| w.execute && w.address == pc) {
+            self.stopped = Some(StopReason::Watchpoint(w.id, None));
+        }
+        self.stopped.is_some()
+    }
+
+    /// Called after every instruction to check watchpoints and stepping targets.
+    pub fn after_step(&mut self, cpu: &Cpu, emulator: &mut Emulator) {
+        if emulator.record_accesses {
+            for access in emulator.take_accesses() {
+                let hit = self.watchpoints.iter().find(|w| {
+                    w.address == access.address && (w.read && !access.write || w.write && access.write)
+                });
+                if let Some(w) = hit {
+                    self.stopped = Some(StopReason::Watchpoint(w.id, Some(access)));
+                }
+            }
+        }
+
+        self.track_calls(cpu, emulator);
+
+        let regs = cpu.registers();
+        let line = emulator.ppu.current_line();
+        let line_changed = line != self.last_line;
+        self.last_line = line;
+        if self.stopped.is_some() {
+            return;
+        }
+
+        let done = match self.mode {
+            RunMode::Continue => false,
+            RunMode::Step(1) => true,
+            RunMode::Step(n) => {
+                self.mode = RunMode::Step(n - 1);
+                false
+            }
+            RunMode::StepOver { return_address, sp } => regs.pc == return_address && regs.sp >= sp,
+            RunMode::StepOut { sp } => {
+                // RET cc / RET / RETI
+                let returned = matches!(self.last_opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9);
+                returned && regs.sp > sp
+            }
+            RunMode::Scanline(target) => line_changed && line == target,
+            RunMode::VBlank => line_changed && line == 144,
+        };
+        if done {
+            self.stopped = Some(match self.mode {
+                RunMode::Scanline(line) => StopReason::Scanline(line),
+                RunMode::VBlank => StopReason::VBlank,
+                _ => StopReason::Step,
+            });
+            self.mode = RunMode::Continue;
+        }
+    }
+
+    fn track_calls(&mut self, cpu: &Cpu, emulator: &Emulator) {
+        let regs = cpu.registers();
+        // Returns, and anything else that unwinds the stack, end the calls they unwind
+        while let Some(frame) = self.call_stack.last() {
+            if regs.sp <= frame.sp {
+                break;
+            }
+            self.call_stack.pop();
+        }
+
+        let interrupted = matches!(regs.pc, 0x40 | 0x48 | 0x50 | 0x58 | 0x60) && regs.sp < self.last_sp;
+        let called = match self.last_opcode {
+            // CALL cc,nn / CALL nn, unless the condition failed
+            0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => regs.pc != self.last_pc.wrapping_add(3),
+            // RST n
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => true,
+            _ => false,
+        };
+        if interrupted || called {
+            let return_address = emulator.read_mapped(regs.sp) as u16
+                | (emulator.read_mapped(regs.sp.wrapping_add(1)) as u16) << 8;
+            self.call_stack.push(Frame { entry: regs.pc, return_address, sp: regs.sp });
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::cpu::Cpu;
+use crate::symbols::Symbols;
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    /// The opcode (including any CB prefix) followed by its operands
+    pub bytes: Vec<u8>,
+    /// Assembly text, e.g. `LD A,(HL+)`
+    pub text: String,
+    /// Destination of a jump, call or restart, when it doesn't depend on registers
+    pub target: Option<u16>,
+    /// Address written in the instruction: a jump target or a memory operand
+    pub reference: Option<u16>,
+}
+
+impl Instruction {
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    /// Address of the instruction that follows this one in memory.
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.length())
+    }
+
+    /// Whether this is a CALL or RST, i.e. something that returns to `next_address`.
+    pub fn is_call(&self) -> bool {
+        self.text.starts_with("CALL") || self.text.starts_with("RST")
+    }
+
+    /// The text with the address it refers to replaced by a label, when there is one.
+    /// `bank` gives the bank mapped at an address.
+    pub fn text_with_symbols(&self, symbols: &Symbols, bank: impl Fn(u16) -> u16) -> String {
+        let label = self.reference
+            .and_then(|address| symbols.describe(bank(address), address).map(|label| (address, label)));
+        match label {
+            Some((address, label)) => self.text.replace(&format!("${:04X}", address), &label),
+            None => self.text.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        w.write_str(&self.text)
+    }
+}
+
+/// Operand placeholders used in the opcode table, with their size in bytes.
+const OPERANDS: [(&str, u16); 6] = [
+    ("d8", 1), ("d16", 2), ("a8", 1), ("a16", 2), ("r8", 1), ("s8", 1),
+];
+
+/// Decode the instruction at `address`, fetching its bytes with `read`.
+pub fn disassemble(address: u16, mut read: impl FnMut(u16) -> u8) -> Instruction {
+    let opcode = read(address);
+    let mut bytes = vec![opcode];
+    let mnemonic = if opcode == 0xCB {
+        let opcode = read(address.wrapping_add(1));
+        bytes.push(opcode);
+        Cpu::prefixed_mnemonic(opcode)
+    } else {
+        Cpu::mnemonic(opcode)
+    };
+
+    if mnemonic.is_empty() {
+        return Instruction { address, bytes, text: format!("DB ${:02X}", opcode), target: None, reference: None };
+    }
+
+    let operand = OPERANDS.iter().find(|(name, _)| mnemonic.contains(name));
+    let (name, size) = match operand {
+        Some(&operand) => operand,
+        None => {
+            let target = if mnemonic.starts_with("RST") { Some((opcode & 0x38) as u16) } else { None };
+            return Instruction { address, bytes, text: mnemonic.to_string(), target, reference: target };
+        }
+    };
+
+    let mut value = 0;
+    for i in 0..size {
+        let byte = read(address.wrapping_add(bytes.len() as u16));
+        bytes.push(byte);
+        value |= (byte as u16) << (8 * i);
+    }
+    let next_address = address.wrapping_add(bytes.len() as u16);
+
+    let mut target = None;
+    let mut reference = None;
+    let operand = match name {
+        "d8" => format!("${:02X}", value),
+        "a8" => {
+            reference = Some(0xFF00 | value);
+            format!("${:04X}", 0xFF00 | value)
+        }
+        "d16" => format!("${:04X}", value),
+        "a16" => {
+            if mnemonic.starts_with("JP") || mnemonic.starts_with("CALL") {
+                target = Some(value);
+            }
+            reference = Some(value);
+            format!("${:04X}", value)
+        }
+        "r8" => {
+            let destination = next_address.wrapping_add(value as i8 as u16);
+            target = Some(destination);
+            reference = target;
+            format!("${:04X}", destination)
+        }
+        "s8" if (value as i8) < 0 => format!("-${:02X}", -(value as i8 as i16)),
+        "s8" => format!("${:02X}", value),
+        _ => unreachable!(),
+    };
+    let text = mnemonic.replace(name, &operand).replace("+-", "-");
+    Instruction { address, bytes, text, target, reference }
+}
+
+/// Write a linear disassembly of `rom[start..end]`, one instruction per line,
+/// with a line for each label from `symbols`.
+///
+/// Addresses are shown as `bank:address`, where the address is where the bytes
+/// appear in the CPU's address space when their bank is mapped in.
+pub fn disassemble_rom(rom: &[u8], start: usize, end: usize, symbols: &Symbols, w: &mut dyn Write) -> io::Result<()> {
+    const BANK_SIZE: usize = 0x4000;
+
+    let end = end.min(rom.len());
+    let mut offset = start;
+    while offset < end {
+        let bank = offset / BANK_SIZE;
+        let base = if bank == 0 { 0 } else { (bank - 1) * BANK_SIZE };
+        let address = (offset - base) as u16;
+        let instruction = disassemble(address, |address| {
+            rom.get(base + address as usize).copied().unwrap_or(0xFF)
+        });
+
+        if let Some(symbol) = symbols.get(bank as u16, address) {
+            writeln!(w, "{}:", symbol.name)?;
+        }
+        // Assume code refers to its own bank, or bank 1 for code in bank 0,
+        // and to the only WRAM bank a DMG has
+        let text = instruction.text_with_symbols(symbols, |address| match address {
+            0x4000..=0x7FFF => bank.max(1) as u16,
+            0xD000..=0xDFFF => 1,
+            _ => 0,
+        });
+        let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        writeln!(w, "{:02X}:{:04X}  {:<9} {}", bank, address, bytes.join(" "), text)?;
+        offset += instruction.bytes.len();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::Symbol;
+
+    fn decode(bytes: &[u8]) -> Instruction {
+        disassemble(0x0150, |address| bytes.get((address - 0x0150) as usize).copied().unwrap_or(0))
+    }
+
+    #[test]
+    fn decodes_operands() {
+        assert_eq!(decode(&[0x3E, 0x42]).text, "LD A,$42");
+        assert_eq!(decode(&[0xE0, 0x40]).text, "LDH ($FF40),A");
+        assert_eq!(decode(&[0xCB, 0x7C]).text, "BIT 7,H");
+
+        let call = decode(&[0xCD, 0x34, 0x12]);
+        assert_eq!((call.text.as_str(), call.length(), call.target), ("CALL $1234", 3, Some(0x1234)));
+        assert!(call.is_call());
+
+        let jr = decode(&[0x18, 0xFE]);
+        assert_eq!((jr.text.as_str(), jr.target), ("JR $0150", Some(0x0150)));
+        assert_eq!(decode(&[0xE8, 0xFC]).text, "ADD SP,-$04");
+        assert_eq!(decode(&[0xD3]).text, "DB $D3");
+    }
+
+    #[test]
+    fn stop_takes_a_padding_byte() {
+        let stop = decode(&[0x10, 0x00]);
+        assert_eq!((stop.text.as_str(), stop.length()), ("STOP $00", 2));
+
+        let mut listing = Vec::new();
+        disassemble_rom(&[0x10, 0x00, 0x3E, 0x01], 0, 4, &Symbols::new(), &mut listing).unwrap();
+        assert_eq!(String::from_utf8(listing).unwrap(),
+                   "00:0000  10 00     STOP $00\n00:0002  3E 01     LD A,$01\n");
+    }
+
+    #[test]
+    fn listing_shows_wram_labels() {
+        let mut symbols = Symbols::new();
+        symbols.insert(Symbol { bank: 0, address: 0xC000, name: "wFlags".to_string() });
+        symbols.insert(Symbol { bank: 1, address: 0xD000, name: "wBuffer".to_string() });
+        let mut listing = Vec::new();
+        disassemble_rom(&[0xEA, 0x00, 0xC0, 0xEA, 0x01, 0xD0], 0, 6, &symbols, &mut listing).unwrap();
+        assert_eq!(String::from_utf8(listing).unwrap(),
+                   "00:0000  EA 00 C0  LD (wFlags),A\n00:0003  EA 01 D0  LD (wBuffer+$1),A\n");
+    }
+}
@@ -0,0 +1,493 @@
+use crate::savestate::{self, SaveState};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+const UNDEFINED_BYTE: u8 = 0xFF;
+/// M-cycles in one second of emulated time
+const CYCLES_PER_SECOND: u32 = 1 << 20;
+
+/// The memory bank controllers that are emulated, from the cartridge type in the header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MbcKind {
+    None,
+    Mbc1,
+    Mbc3,
+    Mbc5,
+}
+
+/// A cartridge's memory bank controller and the RAM behind it.
+pub struct Mbc {
+    kind: MbcKind,
+    /// One less than the number of ROM banks, which is always a power of two
+    rom_bank_mask: u16,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    /// The switchable ROM bank register, without MBC1's upper bits
+    rom_bank: u16,
+    /// The RAM bank register, which also holds MBC1's upper ROM bank bits
+    /// and MBC3's clock register select
+    ram_bank: u8,
+    /// MBC1's banking mode, which applies the upper bits to the fixed bank and RAM
+    advanced_banking: bool,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc {
+    /// A cartridge with 32 KiB of ROM and nothing else.
+    pub fn none() -> Self {
+        Self {
+            kind: MbcKind::None,
+            rom_bank_mask: 1,
+            ram: Vec::new(),
+            ram_enabled: true,
+            rom_bank: 1,
+            ram_bank: 0,
+            advanced_banking: false,
+            rtc: None,
+        }
+    }
+
+    /// The controller and RAM described by the header of `rom`, whose size must already
+    /// be padded to a power of two banks.
+    pub fn from_header(rom: &[u8]) -> std::io::Result<Self> {
+        let header = |address: usize| rom.get(address).copied().unwrap_or(0);
+        let (kind, has_rtc) = match header(0x147) {
+            0x00 | 0x08 | 0x09 => (MbcKind::None, false),
+            0x01..=0x03 => (MbcKind::Mbc1, false),
+            0x0F | 0x10 => (MbcKind::Mbc3, true),
+            0x11..=0x13 => (MbcKind::Mbc3, false),
+            0x19..=0x1E => (MbcKind::Mbc5, false),
+            cartridge_type => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                format!("cartridge type {:02X} isn't supported (only ROM only, MBC1, MBC3 and MBC5 cartridges are)",
+                        cartridge_type))),
+        };
+        if kind == MbcKind::None && rom.len() > 2 * ROM_BANK_SIZE {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                format!("ROM is {} bytes, but its header says it has no memory bank controller", rom.len())));
+        }
+        let ram_size = match header(0x149) {
+            0x01 => 0x800,
+            0x02 => RAM_BANK_SIZE,
+            0x03 => RAM_BANK_SIZE * 4,
+            0x04 => RAM_BANK_SIZE * 16,
+            0x05 => RAM_BANK_SIZE * 8,
+            _ => 0,
+        };
+        Ok(Self {
+            kind,
+            rom_bank_mask: (rom.len() / ROM_BANK_SIZE - 1) as u16,
+            ram: vec![0; ram_size],
+            ram_enabled: kind == MbcKind::None,
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+            ..Self::none()
+        })
+    }
+
+    /// The ROM bank mapped at 4000-7FFF.
+    pub fn rom_bank(&self) -> u16 {
+        let bank = match self.kind {
+            MbcKind::Mbc1 => (self.ram_bank as u16) << 5 | self.rom_bank,
+            _ => self.rom_bank,
+        };
+        bank & self.rom_bank_mask
+    }
+
+    /// The RAM bank mapped at A000-BFFF. For MBC3, values from 08 up select a clock register.
+    pub fn ram_bank(&self) -> u16 {
+        match self.kind {
+            MbcKind::None => 0,
+            MbcKind::Mbc1 if !self.advanced_banking => 0,
+            _ => self.ram_bank as u16,
+        }
+    }
+
+    /// Where in the ROM the byte at `address` in 0000-7FFF comes from.
+    pub fn rom_offset(&self, address: u16) -> usize {
+        let bank = match address {
+            0x0000..=0x3FFF if self.kind == MbcKind::Mbc1 && self.advanced_banking
+                => (self.ram_bank as u16) << 5 & self.rom_bank_mask,
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank(),
+        };
+        bank as usize * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1))
+    }
+
+    /// Handle a write to the ROM area, which sets the controller's registers.
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match (self.kind, address) {
+            (MbcKind::None, _) => (),
+            (_, 0x0000..=0x1FFF) => self.ram_enabled = value & 0x0F == 0x0A,
+
+            // Bank 0 can't be selected in the switchable area, except on MBC5
+            (MbcKind::Mbc1, 0x2000..=0x3FFF) => self.rom_bank = (value & 0x1F).max(1) as u16,
+            (MbcKind::Mbc1, 0x4000..=0x5FFF) => self.ram_bank = value & 0x03,
+            (MbcKind::Mbc1, 0x6000..=0x7FFF) => self.advanced_banking = value & 1 != 0,
+
+            (MbcKind::Mbc3, 0x2000..=0x3FFF) => self.rom_bank = (value & 0x7F).max(1) as u16,
+            (MbcKind::Mbc3, 0x4000..=0x5FFF) => self.ram_bank = value & 0x0F,
+            (MbcKind::Mbc3, 0x6000..=0x7FFF) => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(value);
+                }
+            }
+
+            (MbcKind::Mbc5, 0x2000..=0x2FFF) => self.rom_bank = self.rom_bank & 0x100 | value as u16,
+            (MbcKind::Mbc5, 0x3000..=0x3FFF) => self.rom_bank = self.rom_bank & 0xFF | (value as u16 & 1) << 8,
+            (MbcKind::Mbc5, 0x4000..=0x5FFF) => self.ram_bank = value & 0x0F,
+            _ => (),
+        }
+    }
+
+    pub fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return UNDEFINED_BYTE;
+        }
+        if let Some(register) = self.rtc_register() {
+            return self.rtc.as_ref().map_or(UNDEFINED_BYTE, |rtc| rtc.latched[register]);
+        }
+        self.ram_offset(self.ram_bank(), address).map_or(UNDEFINED_BYTE, |offset| self.ram[offset])
+    }
+
+    pub fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(register) = self.rtc_register() {
+            if let Some(rtc) = &mut self.rtc {
+                rtc.write(register, value);
+            }
+            return;
+        }
+        if let Some(offset) = self.ram_offset(self.ram_bank(), address) {
+            self.ram[offset] = value;
+        }
+    }
+
+    /// Read from a RAM bank whether or not it's mapped or enabled, for debugging tools.
+    pub fn read_ram_bank(&self, bank: u16, address: u16) -> Option<u8> {
+        let offset = bank as usize * RAM_BANK_SIZE + (address - 0xA000) as usize;
+        self.ram.get(offset).copied()
+    }
+
+    /// Advance the clock, if the cartridge has one.
+    pub fn step(&mut self, cycles: u32) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.step(cycles);
+        }
+    }
+
+    /// The MBC3 clock register selected instead of a RAM bank, if any.
+    fn rtc_register(&self) -> Option<usize> {
+        match (self.kind, self.ram_bank) {
+            (MbcKind::Mbc3, 0x08..=0x0C) => Some(self.ram_bank as usize - 0x08),
+            _ => None,
+        }
+    }
+
+    /// Cartridges with less RAM than the banks can address see it repeated.
+    fn ram_offset(&self, bank: u16, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let offset = bank as usize * RAM_BANK_SIZE + (address - 0xA000) as usize;
+        Some(offset % self.ram.len())
+    }
+}
+
+impl SaveState for Mbc {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        w.write_all(&self.ram)?;
+        savestate::write_bool(w, self.ram_enabled)?;
+        savestate::write_u16(w, self.rom_bank)?;
+        savestate::write_u8(w, self.ram_bank)?;
+        savestate::write_bool(w, self.advanced_banking)?;
+        match &self.rtc {
+            Some(rtc) => rtc.save_state(w),
+            None => Ok(()),
+        }
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        r.read_exact(&mut self.ram)?;
+        self.ram_enabled = savestate::read_bool(r)?;
+        self.rom_bank = savestate::read_u16(r)?;
+        self.ram_bank = savestate::read_u8(r)?;
+        self.advanced_banking = savestate::read_bool(r)?;
+        match &mut self.rtc {
+            Some(rtc) => rtc.load_state(r),
+            None => Ok(()),
+        }
+    }
+}
+
+/// MBC3's real-time clock.
+///
+/// It counts emulated time rather than wall-clock time, so it stops while the
+/// emulator is paused and runs identically when a movie is replayed.
+struct Rtc {
+    /// Seconds, minutes, hours, the low 8 bits of the day counter, and its
+    /// 9th bit along with the halt and day counter carry flags
+    registers: [u8; 5],
+    /// The registers as of the last latch, which is what reads see
+    latched: [u8; 5],
+    /// Set by writing 00 to the latch register, so that a following 01 latches
+    latch_armed: bool,
+    cycles: u32,
+}
+
+impl Rtc {
+    fn new() -> Self {
+        Self {
+            registers: [0; 5],
+            latched: [0; 5],
+            latch_armed: false,
+            cycles: 0,
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.registers[4] & 1 << 6 != 0
+    }
+
+    fn step(&mut self, cycles: u32) {
+        if self.halted() {
+            return;
+        }
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_SECOND {
+            self.cycles -= CYCLES_PER_SECOND;
+            self.tick();
+        }
+    }
+
+    /// Advance by one second. Registers set out of range count up to the
+    /// largest value their bits hold before wrapping, without carrying.
+    fn tick(&mut self) {
+        let [seconds, minutes, hours, ..] = &mut self.registers;
+        *seconds = (*seconds + 1) & 0x3F;
+        if *seconds != 60 { return; }
+        *seconds = 0;
+        *minutes = (*minutes + 1) & 0x3F;
+        if *minutes != 60 { return; }
+        *minutes = 0;
+        *hours = (*hours + 1) & 0x1F;
+        if *hours != 24 { return; }
+        *hours = 0;
+
+        let days = ((self.registers[4] as u16 & 1) << 8 | self.registers[3] as u16) + 1;
+        self.registers[3] = days as u8;
+        self.registers[4] = self.registers[4] & !1 | (days >> 8 & 1) as u8;
+        if days == 0x200 {
+            // The day counter overflowed; the carry flag stays set until cleared
+            self.registers[4] |= 1 << 7;
+        }
+    }
+
+    fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 1 {
+            self.latched = self.registers;
+        }
+        self.latch_armed = value == 0;
+    }
+
+    fn write(&mut self, register: usize, value: u8) {
+        const MASKS: [u8; 5] = [0x3F, 0x3F, 0x1F, 0xFF, 0xC1];
+        self.registers[register] = value & MASKS[register];
+        self.latched[register] = self.registers[register];
+        if register == 0 {
+            // Writing the seconds restarts the current second
+            self.cycles = 0;
+        }
+    }
+}
+
+impl SaveState for Rtc {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        w.write_all(&self.registers)?;
+        w.write_all(&self.latched)?;
+        savestate::write_bool(w, self.latch_armed)?;
+        savestate::write_u32(w, self.cycles)
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        r.read_exact(&mut self.registers)?;
+        r.read_exact(&mut self.latched)?;
+        self.latch_armed = savestate::read_bool(r)?;
+        self.cycles = savestate::read_u32(r)?;
+        if self.cycles >= CYCLES_PER_SECOND {
+            return Err(savestate::invalid_data("invalid clock cycle count"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ROM of `banks` 16 KiB banks, each filled with its bank number, with the given header.
+    fn rom(cartridge_type: u8, ram_size: u8, banks: usize) -> Vec<u8> {
+        let mut rom: Vec<u8> = (0..banks).flat_map(|bank| vec![bank as u8; ROM_BANK_SIZE]).collect();
+        rom[0x147] = cartridge_type;
+        rom[0x149] = ram_size;
+        rom
+    }
+
+    fn mapped(mbc: &Mbc, rom: &[u8], address: u16) -> u8 {
+        rom[mbc.rom_offset(address)]
+    }
+
+    #[test]
+    fn refuses_unsupported_cartridges() {
+        // MBC2
+        let error = Mbc::from_header(&rom(0x05, 0, 2)).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn mbc1_switches_rom_banks() {
+        let rom = rom(0x01, 0, 128);
+        let mut mbc = Mbc::from_header(&rom).unwrap();
+        assert_eq!(mbc.kind, MbcKind::Mbc1);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 1);
+
+        mbc.write_register(0x2000, 0x05);
+        assert_eq!(mapped(&mbc, &rom, 0x7FFF), 5);
+        assert_eq!(mbc.rom_bank(), 5);
+        // Bank 0 selects bank 1, and so does 20 through the lower register
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 1);
+        mbc.write_register(0x2000, 0xE0);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 1);
+
+        // The upper bits come from the RAM bank register
+        mbc.write_register(0x2000, 0x02);
+        mbc.write_register(0x4000, 0x03);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 0x62);
+        assert_eq!(mapped(&mbc, &rom, 0x0000), 0);
+        // And apply to the fixed bank in advanced banking mode
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(mapped(&mbc, &rom, 0x0000), 0x60);
+    }
+
+    #[test]
+    fn mbc1_banks_ram_in_advanced_mode() {
+        let mut mbc = Mbc::from_header(&rom(0x03, 0x03, 4)).unwrap();
+        assert_eq!(mbc.read_ram(0xA000), UNDEFINED_BYTE);
+        mbc.write_ram(0xA000, 0x12);
+        mbc.write_register(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+
+        mbc.write_ram(0xA000, 0x12);
+        mbc.write_register(0x4000, 0x02);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(mbc.ram_bank(), 2);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_ram(0xA000, 0x34);
+        assert_eq!(mbc.read_ram_bank(0, 0xA000), Some(0x12));
+        assert_eq!(mbc.read_ram_bank(2, 0xA000), Some(0x34));
+        assert_eq!(mbc.read_ram_bank(4, 0xA000), None);
+
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), UNDEFINED_BYTE);
+    }
+
+    #[test]
+    fn small_rom_wraps_bank_numbers() {
+        let rom = rom(0x01, 0, 4);
+        let mut mbc = Mbc::from_header(&rom).unwrap();
+        mbc.write_register(0x2000, 0x07);
+        assert_eq!(mbc.rom_bank(), 3);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 3);
+    }
+
+    #[test]
+    fn mbc3_switches_rom_and_ram_banks() {
+        let rom = rom(0x13, 0x03, 128);
+        let mut mbc = Mbc::from_header(&rom).unwrap();
+        mbc.write_register(0x2000, 0x7F);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 0x7F);
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 1);
+
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x03);
+        mbc.write_ram(0xBFFF, 0x56);
+        assert_eq!(mbc.read_ram_bank(3, 0xBFFF), Some(0x56));
+        // Without a clock, its registers read as nothing
+        mbc.write_register(0x4000, 0x08);
+        assert_eq!(mbc.read_ram(0xA000), UNDEFINED_BYTE);
+    }
+
+    #[test]
+    fn mbc3_clock_counts_emulated_time() {
+        let mut mbc = Mbc::from_header(&rom(0x10, 0x03, 4)).unwrap();
+        mbc.write_register(0x0000, 0x0A);
+        let latch = |mbc: &mut Mbc| {
+            mbc.write_register(0x6000, 0x00);
+            mbc.write_register(0x6000, 0x01);
+        };
+        let read = |mbc: &mut Mbc, register: u8| {
+            mbc.write_register(0x4000, register);
+            mbc.read_ram(0xA000)
+        };
+
+        mbc.step(CYCLES_PER_SECOND * 61);
+        assert_eq!(read(&mut mbc, 0x08), 0);
+        latch(&mut mbc);
+        assert_eq!(read(&mut mbc, 0x08), 1);
+        assert_eq!(read(&mut mbc, 0x09), 1);
+
+        // 23:59:59 on day 511 rolls over to day 0 and sets the carry flag
+        for &(register, value) in &[(0x08, 59), (0x09, 59), (0x0A, 23), (0x0B, 0xFF), (0x0C, 0x01)] {
+            mbc.write_register(0x4000, register);
+            mbc.write_ram(0xA000, value);
+        }
+        mbc.step(CYCLES_PER_SECOND);
+        latch(&mut mbc);
+        assert_eq!([read(&mut mbc, 0x08), read(&mut mbc, 0x09), read(&mut mbc, 0x0A)], [0, 0, 0]);
+        assert_eq!(read(&mut mbc, 0x0B), 0);
+        assert_eq!(read(&mut mbc, 0x0C), 0x80);
+
+        // Halted, the clock doesn't count
+        mbc.write_ram(0xA000, 0x40);
+        mbc.step(CYCLES_PER_SECOND * 5);
+        latch(&mut mbc);
+        assert_eq!(read(&mut mbc, 0x08), 0);
+    }
+
+    #[test]
+    fn mbc5_selects_any_rom_bank() {
+        let rom = rom(0x19, 0, 512);
+        let mut mbc = Mbc::from_header(&rom).unwrap();
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.rom_bank(), 0);
+        mbc.write_register(0x2000, 0x34);
+        mbc.write_register(0x3000, 0x01);
+        assert_eq!(mbc.rom_bank(), 0x134);
+        assert_eq!(mapped(&mbc, &rom, 0x4000), 0x34);
+        mbc.write_register(0x2000, 0xFF);
+        assert_eq!(mbc.rom_bank(), 0x1FF);
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut mbc = Mbc::from_header(&rom(0x10, 0x03, 8)).unwrap();
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x2000, 0x05);
+        mbc.write_register(0x4000, 0x02);
+        mbc.write_ram(0xA123, 0x77);
+        mbc.step(CYCLES_PER_SECOND * 3 + 5);
+
+        let mut state = Vec::new();
+        mbc.save_state(&mut state).unwrap();
+        let mut loaded = Mbc::from_header(&rom(0x10, 0x03, 8)).unwrap();
+        loaded.load_state(&mut &state[..]).unwrap();
+        let mut reloaded = Vec::new();
+        loaded.save_state(&mut reloaded).unwrap();
+        assert_eq!(state, reloaded);
+        assert_eq!(loaded.rom_bank(), 5);
+        assert_eq!(loaded.read_ram(0xA123), 0x77);
+    }
+}
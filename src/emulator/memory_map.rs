@@ -1,5 +1,4 @@
-use crate::emulator::Emulator;
-use crate::ppu::Ppu;
+use crate::emulator::{Emulator, Access};
 
 const UNDEFINED_BYTE: u8 = 0xFF;
 
@@ -8,17 +7,17 @@ impl Emulator {
         match address {
             0x0000 ..= 0x00FF => {
                 if self.booted {
-                    self.rom[address as usize]
+                    self.read_rom(address)
                 } else {
                     self.boot_rom[address as usize]
                 }
             }
-            0x0100 ..= 0x7FFF => self.rom[address as usize],
+            0x0100 ..= 0x7FFF => self.read_rom(address),
 
             0x8000 ..= 0x9FFF => self.ppu.read_vram(address - 0x8000),
             0xFE00 ..= 0xFE9F => self.ppu.read_oam(address - 0xFE00),
 
-            0xA000 ..= 0xBFFF => self.cram[(address - 0xA000) as usize],
+            0xA000 ..= 0xBFFF => self.mbc.read_ram(address),
             0xC000 ..= 0xDFFF => self.wram[(address - 0xC000) as usize],
             // Echo ram, mapped to WRAM
             0xE000 ..= 0xFDFF => self.wram[(address - 0xE000) as usize],
@@ -31,20 +30,41 @@ impl Emulator {
         }
     }
 
+    fn read_rom(&self, address: u16) -> u8 {
+        let value = self.rom[self.mbc.rom_offset(address)];
+        self.rom_patches.iter().fold(value, |value, patch| patch.apply(address, value))
+    }
+
+    /// Read from a specific bank rather than the one currently mapped, for debugging tools.
+    /// Returns `None` for banks the cartridge doesn't have; areas without banks only have bank 0.
+    pub fn read_banked(&self, bank: u16, address: u16) -> Option<u8> {
+        match address {
+            0x4000 ..= 0x7FFF => {
+                let offset = bank as usize * 0x4000 + (address - 0x4000) as usize;
+                self.rom.get(offset).copied()
+            }
+            0xA000 ..= 0xBFFF => self.mbc.read_ram_bank(bank, address),
+            _ if bank == 0 => Some(self.read_mapped(address)),
+            _ => None,
+        }
+    }
+
     pub fn write_cycle(&mut self, address: u16, value: u8) {
         self.step();
+        if self.record_accesses {
+            self.accesses.push(Access { address, value, write: true });
+        }
         self.write_mapped(address, value);
     }
 
     pub fn write_mapped(&mut self, address: u16, value: u8) {
         match address {
-            0x0000 ..= 0x7FFF => eprintln!("Writing {:#04X} to ROM {:#06X}. \
-                            Either this is a bug, or this cart uses a memory mapper.", value, address),
+            0x0000 ..= 0x7FFF => self.mbc.write_register(address, value),
 
             0x8000 ..= 0x9FFF => self.ppu.write_vram(address - 0x8000, value),
             0xFE00 ..= 0xFE9F => self.ppu.write_oam(address - 0xFE00, value),
 
-            0xA000 ..= 0xBFFF => self.cram[(address - 0xA000) as usize] = value,
+            0xA000 ..= 0xBFFF => self.mbc.write_ram(address, value),
             0xC000 ..= 0xDFFF => self.wram[(address - 0xC000) as usize] = value,
             // Echo ram, mapped to WRAM
             0xE000 ..= 0xFDFF => self.wram[(address - 0xE000) as usize] = value,
@@ -108,14 +128,14 @@ impl Emulator {
             0xFF10..=0xFF3F => (),
 
             0xFF40 => self.ppu.control_from_byte(value),
-            0xFF41 => self.ppu.stat_from_byte(value),
+            0xFF41 => self.ppu.stat_from_byte(value, &mut self.interrupts),
             0xFF42 => self.ppu.scroll_y = value,
             0xFF43 => self.ppu.scroll_x = value,
             0xFF44 => self.ppu.reset_current_line(),
             0xFF46 => self.init_dma_transfer(value),
-            0xFF47 => self.ppu.palette = Ppu::palette_from_byte(value),
-            0xFF48 => self.ppu.obj_palette0 = Ppu::palette_from_byte(value),
-            0xFF49 => self.ppu.obj_palette1 = Ppu::palette_from_byte(value),
+            0xFF47 => self.ppu.bg_palette_from_byte(value),
+            0xFF48 => self.ppu.obj_palette0_from_byte(value),
+            0xFF49 => self.ppu.obj_palette1_from_byte(value),
 
             0xFF50 => self.booted = true,
             0xFFFF => self.interrupts.flags_from_byte(value),
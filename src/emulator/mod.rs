@@ -1,11 +1,30 @@
+mod mbc;
 mod memory_map;
 
+use crate::cheats::RomPatch;
+use mbc::Mbc;
 use crate::ppu::Ppu;
 use crate::interrupts::InterruptQueue;
 use crate::joypad::{Joypad, Button};
 use crate::timer::Timer;
+use crate::model::Model;
+use crate::savestate::{self, SaveState};
+
+/// A memory access made by the CPU.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Access {
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+/// Size of the largest cartridge ROM, 512 banks of 16 KiB.
+pub const MAX_ROM_SIZE: usize = 8 * 1024 * 1024;
+/// Size of the ROM area, the most a cartridge without a memory bank controller can have.
+const MAX_UNBANKED_ROM_SIZE: usize = 0x8000;
 
 pub struct Emulator {
+    model: Model,
     cycles: u32,
     timer: Timer,
     pub ppu: Ppu,
@@ -13,39 +32,68 @@ pub struct Emulator {
     pub joypad: Joypad,
     booted: bool,
     boot_rom: [u8; 0x100],
-    rom: [u8; 0x7FFF - 0x0000 + 1],
-    cram: [u8; 0xBFFF - 0xA000 + 1],
+    rom: Vec<u8>,
+    /// The cartridge's memory bank controller, which also holds its RAM
+    mbc: Mbc,
     wram: [u8; 0xDFFF - 0xC000 + 1],
     hram: [u8; 0xFFFF - 0xFF80 + 1],
+    /// Record CPU memory accesses so debugging tools can inspect them with `take_accesses`
+    pub record_accesses: bool,
+    accesses: Vec<Access>,
+    /// Game Genie codes applied to ROM reads
+    rom_patches: Vec<RomPatch>,
 }
 
 impl Default for Emulator {
     fn default() -> Self {
-        Self::new()
+        Self::new(Model::default())
     }
 }
 
 impl Emulator {
-    pub fn new() -> Emulator {
+    pub fn new(model: Model) -> Emulator {
         Emulator {
+            model,
             cycles: 0,
-            ppu: Ppu::new(),
+            ppu: Ppu::new(model),
             joypad: Joypad::new(),
             timer: Timer::new(),
             booted: false,
             interrupts: InterruptQueue::new(),
             boot_rom: [0; 0x100],
-            rom: [0; 0x8000],
-            cram: [0; 0x2000],
+            rom: vec![0; MAX_UNBANKED_ROM_SIZE],
+            mbc: Mbc::none(),
             wram: [0; 0x2000],
             hram: [0; 0x80],
+            record_accesses: false,
+            accesses: Vec::new(),
+            rom_patches: Vec::new(),
         }
     }
 
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Unmap the boot ROM and set up the IO registers the way the boot ROM leaves them.
+    pub fn skip_boot(&mut self) {
+        self.write_ioreg(0xFF40, 0x91);
+        self.write_ioreg(0xFF47, 0xFC);
+        self.write_ioreg(0xFF48, 0xFF);
+        self.write_ioreg(0xFF49, 0xFF);
+        self.booted = true;
+    }
+
+    /// CRC32 of the loaded ROM, used to tell which game a save state or movie belongs to.
+    pub fn rom_checksum(&self) -> u32 {
+        crc32fast::hash(&self.rom)
+    }
+
     pub fn catch_up_cycles(&mut self) -> u32 {
         let cycles = self.cycles;
         self.ppu.step(cycles, &mut self.interrupts);
         self.timer.step(cycles, &mut self.interrupts);
+        self.mbc.step(cycles);
         self.cycles = 0;
         cycles
     }
@@ -54,17 +102,63 @@ impl Emulator {
         self.cycles += 1;
     }
 
+    /// Corrupt OAM as a 16-bit increment or decrement of an address in it does on the DMG,
+    /// accounting for the cycles the PPU hasn't caught up with yet.
+    pub fn corrupt_oam(&mut self) {
+        self.ppu.corrupt_oam(self.cycles);
+    }
+
     pub fn load_bootrom<T: std::io::Read>(&mut self, src: &mut T) -> Result<usize, std::io::Error> {
         src.read(&mut self.boot_rom)
     }
 
-    pub fn load_rom<T: std::io::Read>(&mut self, src: &mut T) -> Result<usize, std::io::Error> {
-        src.read(&mut self.rom)
+    /// Load a cartridge ROM of any size up to `MAX_ROM_SIZE`, with the memory bank
+    /// controller and RAM its header asks for.
+    ///
+    /// ROMs are padded with zeros to a power of two banks of at least 32 KiB, the way
+    /// a controller's bank numbers wrap around. Cartridges with a controller that isn't
+    /// emulated, or larger than 32 KiB without one, are refused rather than run with
+    /// the wrong bank mapped.
+    pub fn load_rom(&mut self, rom: &[u8]) -> std::io::Result<()> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                format!("ROM is {} bytes, larger than any cartridge ({} bytes)", rom.len(), MAX_ROM_SIZE)));
+        }
+        let mut rom = rom.to_vec();
+        rom.resize(rom.len().next_power_of_two().max(MAX_UNBANKED_ROM_SIZE), 0);
+        self.mbc = Mbc::from_header(&rom)?;
+        self.rom = rom;
+        Ok(())
+    }
+
+    /// The bank mapped at `address`, numbered the way symbol files do: the ROM bank
+    /// for 4000-7FFF, the cartridge RAM bank for A000-BFFF, the WRAM bank for D000-DFFF,
+    /// and 0 for everything else. Without CGB WRAM banking, WRAM bank 1 is always mapped.
+    pub fn mapped_bank(&self, address: u16) -> u16 {
+        match address {
+            0x4000..=0x7FFF => self.mbc.rom_bank(),
+            0xA000..=0xBFFF => self.mbc.ram_bank(),
+            0xD000..=0xDFFF => 1,
+            _ => 0,
+        }
     }
 
     pub fn read_cycle(&mut self, address: u16) -> u8 {
         self.step();
-        self.read_mapped(address)
+        let value = self.read_mapped(address);
+        if self.record_accesses {
+            self.accesses.push(Access { address, value, write: false });
+        }
+        value
+    }
+
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        self.rom_patches = patches;
+    }
+
+    /// Return and forget the accesses recorded since the last call.
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        std::mem::take(&mut self.accesses)
     }
 
     pub fn joypad_press(&mut self, pressed: Button) {
@@ -75,6 +169,14 @@ impl Emulator {
         self.joypad.clear_button(pressed);
     }
 
+    pub fn joypad_held(&self) -> u8 {
+        self.joypad.held_buttons()
+    }
+
+    pub fn joypad_set(&mut self, held: u8) {
+        self.joypad.set_held_buttons(&mut self.interrupts, held);
+    }
+
     fn init_dma_transfer(&mut self, offset: u8) {
         // TODO: This probably should not happen instantaneously
         // (Hence the name "init")
@@ -85,3 +187,93 @@ impl Emulator {
         }
     }
 }
+
+impl SaveState for Emulator {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        savestate::write_u32(w, self.cycles)?;
+        savestate::write_bool(w, self.booted)?;
+        self.mbc.save_state(w)?;
+        w.write_all(&self.wram)?;
+        w.write_all(&self.hram)?;
+        self.timer.save_state(w)?;
+        self.ppu.save_state(w)?;
+        self.interrupts.save_state(w)?;
+        self.joypad.save_state(w)
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        self.cycles = savestate::read_u32(r)?;
+        self.booted = savestate::read_bool(r)?;
+        self.mbc.load_state(r)?;
+        r.read_exact(&mut self.wram)?;
+        r.read_exact(&mut self.hram)?;
+        self.timer.load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.interrupts.load_state(r)?;
+        self.joypad.load_state(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_small_roms() {
+        let mut emulator = Emulator::default();
+        emulator.load_rom(&[0x12; 0x140]).unwrap();
+        assert_eq!(emulator.read_mapped(0x013F), 0x12);
+        assert_eq!(emulator.read_mapped(0x0140), 0x00);
+        assert_eq!(emulator.read_mapped(0x7FFF), 0x00);
+    }
+
+    #[test]
+    fn refuses_large_rom_without_mbc() {
+        let mut emulator = Emulator::default();
+        let error = emulator.load_rom(&vec![0; MAX_UNBANKED_ROM_SIZE * 2]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn ignores_writes_to_rom_without_mbc() {
+        let mut emulator = Emulator::default();
+        emulator.load_rom(&[0x56; 0x140]).unwrap();
+        emulator.write_mapped(0x2000, 0x02);
+        emulator.write_mapped(0x013F, 0x02);
+        assert_eq!(emulator.read_mapped(0x013F), 0x56);
+        assert_eq!(emulator.mapped_bank(0x4000), 1);
+    }
+
+    #[test]
+    fn switches_banks_through_the_mbc() {
+        // MBC5 with 128 KiB of RAM, and each ROM bank filled with its number
+        let mut rom: Vec<u8> = (0..64).flat_map(|bank| vec![bank as u8; 0x4000]).collect();
+        rom[0x147] = 0x1B;
+        rom[0x149] = 0x04;
+        rom.truncate(rom.len() - 1);
+        let mut emulator = Emulator::default();
+        emulator.load_rom(&rom).unwrap();
+        assert_eq!(emulator.rom.len(), 64 * 0x4000);
+
+        emulator.write_mapped(0x2000, 0x2A);
+        assert_eq!(emulator.read_mapped(0x4000), 0x2A);
+        assert_eq!(emulator.mapped_bank(0x4000), 0x2A);
+        assert_eq!(emulator.read_banked(0x3F, 0x7FFE), Some(0x3F));
+        assert_eq!(emulator.read_banked(0x40, 0x4000), None);
+
+        emulator.write_mapped(0x0000, 0x0A);
+        emulator.write_mapped(0x4000, 0x05);
+        emulator.write_mapped(0xA010, 0x99);
+        assert_eq!(emulator.mapped_bank(0xA010), 5);
+        assert_eq!(emulator.read_banked(5, 0xA010), Some(0x99));
+        assert_eq!(emulator.read_banked(0, 0xA010), Some(0x00));
+
+        let mut state = Vec::new();
+        emulator.save_state(&mut state).unwrap();
+        let mut loaded = Emulator::default();
+        loaded.load_rom(&rom).unwrap();
+        loaded.load_state(&mut &state[..]).unwrap();
+        assert_eq!(loaded.read_mapped(0x4000), 0x2A);
+        assert_eq!(loaded.read_mapped(0xA010), 0x99);
+    }
+}
@@ -0,0 +1,397 @@
+//! Pixel filters applied to the 160x144 RGB24 framebuffer before it's shown.
+
+use std::fmt;
+use std::str::FromStr;
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 144;
+
+type Pixel = [u8; 3];
+
+/// A scaler or effect turning the framebuffer into a larger image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Filter {
+    None,
+    /// Scale2x (AdvMAME2x): rounds off diagonal edges without adding colours
+    Scale2x,
+    /// Scale3x (AdvMAME3x)
+    Scale3x,
+    /// A simplified hq2x: blends colours along edges found by comparing pixels in YUV,
+    /// using a handful of rules rather than hq2x's full lookup table
+    Hq2x,
+    /// Each pixel drawn as a dot with a darker gap around it, like the DMG's LCD
+    LcdGrid,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 5] = [Filter::None, Filter::Scale2x, Filter::Scale3x, Filter::Hq2x, Filter::LcdGrid];
+
+    /// How many times larger the filtered image is in each direction.
+    pub fn scale(self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scale2x | Filter::Hq2x => 2,
+            Filter::Scale3x | Filter::LcdGrid => 3,
+        }
+    }
+
+    /// The filter after this one, for cycling through them with a hotkey.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&filter| filter == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Filter a 160x144 RGB24 frame into `output`, which is resized to
+    /// `160 * scale` by `144 * scale` RGB24 pixels.
+    pub fn apply(self, input: &[u8], output: &mut Vec<u8>) {
+        let scale = self.scale();
+        output.resize(WIDTH * scale * HEIGHT * scale * 3, 0);
+        let mut out = Output { pixels: output, width: WIDTH * scale };
+        match self {
+            Filter::None => out.pixels.copy_from_slice(input),
+            Filter::Scale2x => {
+                let padded = Padded::new(input, |pixel| pixel);
+                for_each_pixel(|x, y| scale2x(padded.block(x, y), x, y, &mut out));
+            }
+            Filter::Scale3x => {
+                let padded = Padded::new(input, |pixel| pixel);
+                for_each_pixel(|x, y| scale3x(padded.block(x, y), x, y, &mut out));
+            }
+            Filter::Hq2x => {
+                let padded = Padded::new(input, |pixel| pixel);
+                let yuv = Padded::new(input, to_yuv);
+                for_each_pixel(|x, y| hq2x(padded.block(x, y), yuv.block(x, y), x, y, &mut out));
+            }
+            Filter::LcdGrid => for_each_pixel(|x, y| {
+                let i = (y * WIDTH + x) * 3;
+                lcd_grid([input[i], input[i + 1], input[i + 2]], x, y, &mut out)
+            }),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Filter::None),
+            "scale2x" => Ok(Filter::Scale2x),
+            "scale3x" => Ok(Filter::Scale3x),
+            "hq2x" => Ok(Filter::Hq2x),
+            "lcd" => Ok(Filter::LcdGrid),
+            _ => Err(format!("unknown filter '{}' (expected one of none, scale2x, scale3x, hq2x (simplified), lcd)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Filter::None => "none",
+            Filter::Scale2x => "scale2x",
+            Filter::Scale3x => "scale3x",
+            Filter::Hq2x => "hq2x",
+            Filter::LcdGrid => "lcd",
+        })
+    }
+}
+
+/// Blends each frame with the ones before it, like the DMG's slow LCD. Games that flicker
+/// sprites on alternate frames to fake transparency rely on this.
+pub struct Ghosting {
+    /// The last frame shown, or empty before the first
+    previous: Vec<u8>,
+}
+
+impl Ghosting {
+    pub fn new() -> Self {
+        Self { previous: Vec::new() }
+    }
+
+    /// Mix `frame` with the previous result, returning the frame to show.
+    pub fn blend(&mut self, frame: &[u8]) -> &[u8] {
+        if self.previous.len() == frame.len() {
+            for (previous, &current) in self.previous.iter_mut().zip(frame) {
+                *previous = ((*previous as u16 + current as u16) / 2) as u8;
+            }
+        } else {
+            self.previous = frame.to_vec();
+        }
+        &self.previous
+    }
+}
+
+impl Default for Ghosting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Output<'a> {
+    pixels: &'a mut [u8],
+    width: usize,
+}
+
+impl Output<'_> {
+    fn set(&mut self, x: usize, y: usize, pixel: Pixel) {
+        let i = (y * self.width + x) * 3;
+        self.pixels[i..i + 3].copy_from_slice(&pixel);
+    }
+}
+
+fn for_each_pixel(mut f: impl FnMut(usize, usize)) {
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            f(x, y);
+        }
+    }
+}
+
+/// The frame converted to `T`s with a one pixel border copied from its edges,
+/// so every pixel has eight neighbours.
+struct Padded<T> {
+    pixels: Vec<T>,
+}
+
+impl<T: Copy + Default> Padded<T> {
+    const WIDTH: usize = WIDTH + 2;
+
+    fn new(input: &[u8], convert: impl Fn(Pixel) -> T) -> Self {
+        let mut pixels = Vec::with_capacity(Self::WIDTH * (HEIGHT + 2));
+        for y in 0..HEIGHT + 2 {
+            let row = y.clamp(1, HEIGHT) - 1;
+            for x in 0..Self::WIDTH {
+                let i = (row * WIDTH + x.clamp(1, WIDTH) - 1) * 3;
+                pixels.push(convert([input[i], input[i + 1], input[i + 2]]));
+            }
+        }
+        Self { pixels }
+    }
+
+    /// The 3x3 block around `(x, y)` in the frame, row by row.
+    fn block(&self, x: usize, y: usize) -> [T; 9] {
+        let mut block = [T::default(); 9];
+        for (row, chunk) in block.chunks_exact_mut(3).enumerate() {
+            let start = (y + row) * Self::WIDTH + x;
+            chunk.copy_from_slice(&self.pixels[start..start + 3]);
+        }
+        block
+    }
+}
+
+fn scale2x(block: [Pixel; 9], x: usize, y: usize, out: &mut Output) {
+    let [_, b, _, d, e, f, _, h, _] = block;
+    let (e0, e1, e2, e3) = if b != h && d != f {
+        (if d == b { d } else { e },
+         if b == f { f } else { e },
+         if d == h { d } else { e },
+         if h == f { f } else { e })
+    } else {
+        (e, e, e, e)
+    };
+    out.set(x * 2, y * 2, e0);
+    out.set(x * 2 + 1, y * 2, e1);
+    out.set(x * 2, y * 2 + 1, e2);
+    out.set(x * 2 + 1, y * 2 + 1, e3);
+}
+
+fn scale3x(block: [Pixel; 9], x: usize, y: usize, out: &mut Output) {
+    let [a, b, c, d, e, f, g, h, i] = block;
+    let block = if b != h && d != f {
+        [
+            if d == b { d } else { e },
+            if (d == b && e != c) || (b == f && e != a) { b } else { e },
+            if b == f { f } else { e },
+            if (d == b && e != g) || (d == h && e != a) { d } else { e },
+            e,
+            if (b == f && e != i) || (h == f && e != c) { f } else { e },
+            if d == h { d } else { e },
+            if (d == h && e != i) || (h == f && e != g) { h } else { e },
+            if h == f { f } else { e },
+        ]
+    } else {
+        [e; 9]
+    };
+    for (n, &pixel) in block.iter().enumerate() {
+        out.set(x * 3 + n % 3, y * 3 + n / 3, pixel);
+    }
+}
+
+type Yuv = (i32, i32, i32);
+
+fn to_yuv([r, g, b]: Pixel) -> Yuv {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    ((r * 299 + g * 587 + b * 114) / 1000, (-r * 169 - g * 331 + b * 500) / 1000 + 128,
+     (r * 500 - g * 419 - b * 81) / 1000 + 128)
+}
+
+/// Whether two colours are close enough to count as the same, using hqx's thresholds.
+fn similar((y1, u1, v1): Yuv, (y2, u2, v2): Yuv) -> bool {
+    (y1 - y2).abs() <= 48 && (u1 - u2).abs() <= 7 && (v1 - v2).abs() <= 6
+}
+
+/// Weighted average of colours, as `(colour, weight)`.
+fn mix(colours: &[(Pixel, u32)]) -> Pixel {
+    let total: u32 = colours.iter().map(|&(_, weight)| weight).sum();
+    let mut mixed = [0; 3];
+    for (channel, value) in mixed.iter_mut().enumerate() {
+        let sum: u32 = colours.iter().map(|&(colour, weight)| colour[channel] as u32 * weight).sum();
+        *value = ((sum + total / 2) / total) as u8;
+    }
+    mixed
+}
+
+/// Each output pixel comes from the centre pixel and the three neighbours towards its
+/// corner: blended with both edge neighbours when an edge cuts across the corner, with
+/// one of them where an edge ends, or with the diagonal neighbour when only it differs.
+/// Straight edges are left sharp.
+/// This is a compact set of rules in the spirit of hq2x rather than its full
+/// 256-case lookup table.
+fn hq2x(block: [Pixel; 9], yuv: [Yuv; 9], x: usize, y: usize, out: &mut Output) {
+    let centre = block[4];
+    for &(dx, dy) in &[(0usize, 0usize), (1, 0), (0, 1), (1, 1)] {
+        // Neighbours horizontally, vertically and diagonally towards this corner
+        let (s, v, d) = (3 + dx * 2, 1 + dy * 6, dx * 2 + dy * 6);
+        let (side, vertical, diagonal) = (block[s], block[v], block[d]);
+        let (side_similar, vertical_similar) = (similar(yuv[4], yuv[s]), similar(yuv[4], yuv[v]));
+        let diagonal_similar = similar(yuv[4], yuv[d]);
+        let pixel = if !side_similar && !vertical_similar && similar(yuv[s], yuv[v]) {
+            mix(&[(centre, 2), (side, 1), (vertical, 1)])
+        } else if !side_similar && vertical_similar && diagonal_similar {
+            mix(&[(centre, 3), (side, 1)])
+        } else if side_similar && !vertical_similar && diagonal_similar {
+            mix(&[(centre, 3), (vertical, 1)])
+        } else if side_similar && vertical_similar && !diagonal_similar {
+            mix(&[(centre, 7), (diagonal, 1)])
+        } else {
+            centre
+        };
+        out.set(x * 2 + dx, y * 2 + dy, pixel);
+    }
+}
+
+/// A 2x2 dot with the row below and column to the right of it darkened.
+fn lcd_grid(pixel: Pixel, x: usize, y: usize, out: &mut Output) {
+    let gap = [pixel[0] - pixel[0] / 4, pixel[1] - pixel[1] / 4, pixel[2] - pixel[2] / 4];
+    for dy in 0..3 {
+        for dx in 0..3 {
+            out.set(x * 3 + dx, y * 3 + dy, if dx == 2 || dy == 2 { gap } else { pixel });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Pixel = [0xFF, 0xFF, 0xFF];
+    const BLACK: Pixel = [0x00, 0x00, 0x00];
+
+    /// A white frame with black pixels at `black`.
+    fn frame(black: &[(usize, usize)]) -> Vec<u8> {
+        let mut frame = vec![0xFF; WIDTH * HEIGHT * 3];
+        for &(x, y) in black {
+            let i = (y * WIDTH + x) * 3;
+            frame[i..i + 3].copy_from_slice(&BLACK);
+        }
+        frame
+    }
+
+    fn pixel(output: &[u8], scale: usize, x: usize, y: usize) -> Pixel {
+        let i = (y * WIDTH * scale + x) * 3;
+        [output[i], output[i + 1], output[i + 2]]
+    }
+
+    #[test]
+    fn outputs_are_scaled() {
+        let input = frame(&[(10, 10)]);
+        for &filter in Filter::ALL.iter() {
+            let mut output = Vec::new();
+            filter.apply(&input, &mut output);
+            assert_eq!(output.len(), input.len() * filter.scale() * filter.scale(), "{}", filter);
+        }
+        let mut output = Vec::new();
+        Filter::None.apply(&input, &mut output);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn flat_frames_stay_flat() {
+        let input = frame(&[]);
+        for &filter in &[Filter::Scale2x, Filter::Scale3x, Filter::Hq2x] {
+            let mut output = Vec::new();
+            filter.apply(&input, &mut output);
+            assert!(output.iter().all(|&byte| byte == 0xFF), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn scale2x_rounds_off_diagonals() {
+        // A black diagonal line from the top right to the bottom left
+        let input = frame(&[(11, 9), (10, 10), (9, 11)]);
+        let mut output = Vec::new();
+        Filter::Scale2x.apply(&input, &mut output);
+        // The white pixel above the centre fills its corner facing the line
+        assert_eq!(pixel(&output, 2, 21, 19), BLACK);
+        assert_eq!(pixel(&output, 2, 20, 18), WHITE);
+        // An isolated pixel is only doubled
+        let input = frame(&[(50, 50)]);
+        Filter::Scale2x.apply(&input, &mut output);
+        for &(x, y) in &[(100, 100), (101, 100), (100, 101), (101, 101)] {
+            assert_eq!(pixel(&output, 2, x, y), BLACK);
+        }
+        assert_eq!(pixel(&output, 2, 102, 100), WHITE);
+    }
+
+    #[test]
+    fn scale3x_rounds_off_diagonals() {
+        let input = frame(&[(11, 9), (10, 10), (9, 11)]);
+        let mut output = Vec::new();
+        Filter::Scale3x.apply(&input, &mut output);
+        // The white pixel above the centre: its bottom right corner joins the line
+        assert_eq!(pixel(&output, 3, 32, 29), BLACK);
+        assert_eq!(pixel(&output, 3, 31, 28), WHITE);
+        assert_eq!(pixel(&output, 3, 30, 27), WHITE);
+        // The centre of a black pixel stays black
+        assert_eq!(pixel(&output, 3, 31, 31), BLACK);
+    }
+
+    #[test]
+    fn hq2x_blends_across_corners() {
+        let input = frame(&[(11, 9), (10, 10), (9, 11)]);
+        let mut output = Vec::new();
+        Filter::Hq2x.apply(&input, &mut output);
+        let corner = pixel(&output, 2, 21, 19);
+        assert!(corner != WHITE && corner != BLACK, "{:?}", corner);
+        assert_eq!(pixel(&output, 2, 20, 18), WHITE);
+    }
+
+    #[test]
+    fn lcd_grid_darkens_gaps() {
+        let input = frame(&[]);
+        let mut output = Vec::new();
+        Filter::LcdGrid.apply(&input, &mut output);
+        assert_eq!(pixel(&output, 3, 0, 0), WHITE);
+        assert_eq!(pixel(&output, 3, 2, 0), [0xC0, 0xC0, 0xC0]);
+        assert_eq!(pixel(&output, 3, 0, 2), [0xC0, 0xC0, 0xC0]);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for &filter in Filter::ALL.iter() {
+            assert_eq!(filter.to_string().parse::<Filter>(), Ok(filter));
+        }
+        assert_eq!("HQ2X".parse::<Filter>(), Ok(Filter::Hq2x));
+        assert!("hq4x".parse::<Filter>().is_err());
+        assert_eq!(Filter::LcdGrid.next(), Filter::None);
+    }
+
+    #[test]
+    fn ghosting_averages_frames() {
+        let mut ghosting = Ghosting::new();
+        assert_eq!(ghosting.blend(&[0, 200]), &[0, 200]);
+        assert_eq!(ghosting.blend(&[200, 0]), &[100, 100]);
+        assert_eq!(ghosting.blend(&[200, 0]), &[150, 50]);
+    }
+}
@@ -0,0 +1,157 @@
+use std::io;
+use std::path::Path;
+
+use crate::cheats::Cheats;
+use crate::cpu::Cpu;
+use crate::debugger::Debugger;
+use crate::emulator::Emulator;
+use crate::memsearch::MemorySearch;
+use crate::model::Model;
+use crate::ppu::FRAME_CYCLES;
+use crate::savestate;
+use crate::screenshot;
+use crate::symbols::Symbols;
+use crate::trace::Trace;
+
+/// CPU clock in cycles per second (of the 4 MHz clock, 4 per cycle counted here).
+pub const CLOCK_SPEED: u32 = 4_194_304 / 4;
+
+/// Frames per second of the DMG's LCD, about 59.7275.
+pub const FRAME_RATE: f64 = CLOCK_SPEED as f64 / FRAME_CYCLES as f64;
+
+/// A complete machine: the CPU and everything it is wired to.
+///
+/// This is the entry point for frontends; it owns the run loop so that
+/// it can be driven with or without a window.
+pub struct GameBoy {
+    pub cpu: Cpu,
+    pub emulator: Emulator,
+    /// Print the CPU state before every instruction
+    pub debug: bool,
+    pub debugger: Debugger,
+    /// Instruction trace, written before every instruction while set
+    pub trace: Option<Trace>,
+    /// Labels used by the debugger and traces
+    pub symbols: Symbols,
+    /// Value search in progress, kept between debugger console sessions
+    pub search: Option<MemorySearch>,
+    /// Applied at the start of every frame
+    pub cheats: Cheats,
+}
+
+impl GameBoy {
+    pub fn new(model: Model) -> Self {
+        Self {
+            cpu: Cpu::new(model),
+            emulator: Emulator::new(model),
+            debug: false,
+            debugger: Debugger::new(),
+            trace: None,
+            symbols: Symbols::new(),
+            search: None,
+            cheats: Cheats::new(),
+        }
+    }
+
+    pub fn model(&self) -> Model {
+        self.emulator.model()
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> io::Result<()> {
+        self.emulator.load_rom(rom)
+    }
+
+    pub fn load_bootrom<T: io::Read>(&mut self, src: &mut T) -> io::Result<usize> {
+        self.emulator.load_bootrom(src)
+    }
+
+    /// Start directly at the cartridge entry point, as if the boot ROM had just finished.
+    pub fn skip_boot(&mut self) {
+        self.emulator.skip_boot();
+        self.cpu.skip_boot();
+    }
+
+    /// Execute a single instruction (or interrupt dispatch), returning the cycles it took.
+    ///
+    /// Does nothing and returns 0 while the debugger is paused.
+    pub fn step(&mut self) -> u32 {
+        if self.debugger.before_step(&self.cpu, &mut self.emulator) {
+            return 0;
+        }
+        if self.debug { println!("{:?}", self.cpu) };
+        if let (Some(trace), false) = (&mut self.trace, self.cpu.halted()) {
+            if let Err(e) = trace.log(&self.cpu, &self.emulator, &self.symbols) {
+                eprintln!("Stopped tracing: {}", e);
+                self.trace = None;
+            }
+        }
+
+        self.cpu.step(&mut self.emulator);
+        let cycles = self.emulator.catch_up_cycles();
+        if let Some(trace) = &mut self.trace {
+            trace.add_cycles(cycles);
+        }
+        self.debugger.after_step(&self.cpu, &mut self.emulator);
+        cycles
+    }
+
+    /// Run for at least `cycles` cycles, returning the number actually run.
+    ///
+    /// Stops early if the debugger pauses execution.
+    pub fn run_cycles(&mut self, cycles: u32) -> u32 {
+        let mut elapsed = 0;
+        while elapsed < cycles && !self.debugger.is_paused() {
+            elapsed += self.step();
+        }
+        elapsed
+    }
+
+    /// Run until the PPU enters VBlank, when the framebuffer holds a complete frame,
+    /// returning the number of cycles run.
+    ///
+    /// With the LCD off there's no VBlank, so this stops after a frame's worth of cycles
+    /// instead. Like `run_cycles`, it stops early if the debugger pauses execution.
+    pub fn run_frame(&mut self) -> u32 {
+        self.cheats.apply(&mut self.emulator);
+        let mut elapsed = 0;
+        while !self.debugger.is_paused() {
+            elapsed += self.step();
+            if self.emulator.ppu.take_frame_done() || elapsed >= FRAME_CYCLES {
+                break;
+            }
+        }
+        elapsed
+    }
+
+    /// The screen as 160x144 RGB24 pixels.
+    pub fn framebuffer(&self) -> &[u8] {
+        self.emulator.ppu.framebuffer()
+    }
+
+    /// The shade, 0-3, of each of the 160x144 pixels on screen, independent of the palette.
+    pub fn shades(&self) -> &[u8] {
+        self.emulator.ppu.shades()
+    }
+
+    /// Save the screen to `path` as a 160x144 PNG image.
+    pub fn save_screenshot(&self, path: &Path) -> io::Result<()> {
+        screenshot::save_png(path, 160, 144, self.framebuffer())
+    }
+
+    /// Hold exactly the buttons in `buttons`, a bitmask of `Button::mask`s.
+    pub fn set_buttons(&mut self, buttons: u8) {
+        self.emulator.joypad_set(buttons);
+    }
+
+    pub fn buttons(&self) -> u8 {
+        self.emulator.joypad_held()
+    }
+
+    pub fn save_state(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        savestate::save(&self.cpu, &self.emulator, w)
+    }
+
+    pub fn load_state(&mut self, r: &mut dyn io::Read) -> io::Result<()> {
+        savestate::load(&mut self.cpu, &mut self.emulator, r)
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use sdl2::controller::{Axis, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+use crate::bindings::Bindings;
+use crate::joypad::Button;
+
+/// Game controllers, opened as they're plugged in, and the joypad buttons they hold.
+///
+/// Buttons are mapped through the `[controller]` bindings, and the left stick
+/// works as a D-pad once pushed past the deadzone.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    /// Open controllers by joystick instance id; closed when dropped
+    controllers: HashMap<u32, GameController>,
+    /// Buttons held on each controller, as `Button::mask` bits
+    buttons: HashMap<u32, u8>,
+    /// D-pad directions from each controller's left stick
+    stick: HashMap<u32, u8>,
+}
+
+impl Gamepads {
+    pub fn new(context: &sdl2::Sdl) -> Result<Self, String> {
+        Ok(Self {
+            subsystem: context.game_controller()?,
+            controllers: HashMap::new(),
+            buttons: HashMap::new(),
+            stick: HashMap::new(),
+        })
+    }
+
+    /// Track controllers being added and removed and their buttons and sticks.
+    /// Returns false for events that have nothing to do with controllers.
+    ///
+    /// SDL reports controllers connected at startup as added too.
+    pub fn handle_event(&mut self, event: &Event, bindings: &Bindings) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.open(which) {
+                    Ok(controller) => {
+                        println!("Controller connected: {}", controller.name());
+                        self.controllers.insert(controller.instance_id() as u32, controller);
+                    }
+                    Err(e) => eprintln!("Failed to open controller {}: {}", which, e),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = self.controllers.remove(&which) {
+                    println!("Controller disconnected: {}", controller.name());
+                }
+                self.buttons.remove(&which);
+                self.stick.remove(&which);
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                let held = self.buttons.entry(which).or_insert(0);
+                for button in bindings.controller_button(button) {
+                    *held |= button.mask();
+                }
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                let held = self.buttons.entry(which).or_insert(0);
+                for button in bindings.controller_button(button) {
+                    *held &= !button.mask();
+                }
+            }
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                let (negative, positive) = match axis {
+                    Axis::LeftX => (Button::Left, Button::Right),
+                    Axis::LeftY => (Button::Up, Button::Down),
+                    _ => return true,
+                };
+                let deadzone = bindings.deadzone() as i32;
+                let stick = self.stick.entry(which).or_insert(0);
+                *stick &= !(negative.mask() | positive.mask());
+                if (value as i32) < -deadzone {
+                    *stick |= negative.mask();
+                } else if value as i32 > deadzone {
+                    *stick |= positive.mask();
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// The joypad buttons held on all controllers, as a bitmask of `Button::mask`s.
+    pub fn held(&self) -> u8 {
+        self.buttons.values().chain(self.stick.values())
+            .fold(0, |held, buttons| held | buttons)
+    }
+}
@@ -1,58 +1,270 @@
-use sdl2::pixels::PixelFormatEnum;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use sdl2::video::Window;
-use sdl2::render::Canvas;
-use sdl2::gfx::framerate::FPSManager;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+
+use sdl2::video::{FullscreenType, Window};
+use sdl2::render::{BlendMode, Canvas, Texture};
+
+use crate::filter::{Filter, Ghosting};
+use crate::gameboy::FRAME_RATE;
+use crate::osd::Osd;
+
+/// How much of the wait for the next frame is spent spinning rather than sleeping,
+/// since sleeping can overshoot by about a millisecond.
+const SPIN_TIME: Duration = Duration::from_millis(2);
+/// How many frames late presentation can get before pacing starts over from the current
+/// frame rather than running frames back to back to catch up.
+const MAX_LAG_FRAMES: u32 = 3;
+
+const SCREEN_WIDTH: u32 = 160;
+const SCREEN_HEIGHT: u32 = 144;
+
+/// How the game screen is scaled to fill the window.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scaling {
+    /// The largest whole multiple of the screen size that fits, so every pixel is the same size
+    Integer,
+    /// As large as fits while keeping the aspect ratio, with black bars to fill the rest
+    Fit,
+    /// Fill the whole window
+    Stretch,
+}
+
+impl Scaling {
+    /// The mode after this one, for cycling through them with a hotkey.
+    pub fn next(self) -> Self {
+        match self {
+            Scaling::Integer => Scaling::Fit,
+            Scaling::Fit => Scaling::Stretch,
+            Scaling::Stretch => Scaling::Integer,
+        }
+    }
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "integer" => Ok(Scaling::Integer),
+            "fit" => Ok(Scaling::Fit),
+            "stretch" => Ok(Scaling::Stretch),
+            _ => Err(format!("unknown scaling mode '{}' (expected one of integer, fit, stretch)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Scaling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Scaling::Integer => "integer",
+            Scaling::Fit => "fit",
+            Scaling::Stretch => "stretch",
+        })
+    }
+}
 
 pub struct Gui {
     pub context: sdl2::Sdl,
     canvas: Canvas<Window>,
-    fps_man: FPSManager,
+    /// Streaming texture the filtered framebuffer is copied to, replaced when the filter's scale changes
+    texture: Texture,
+    pub scaling: Scaling,
+    pub filter: Filter,
+    /// Set while ghosting is on
+    ghosting: Option<Ghosting>,
+    /// The framebuffer after filtering
+    filtered: Vec<u8>,
+    /// Time per frame at the current speed, or `None` to run as fast as possible
+    frame_duration: Option<Duration>,
+    /// When the next frame should be presented
+    next_frame: Instant,
+    last_present: Instant,
+    /// Drawn over the game screen
+    pub osd: Osd,
 }
 
 impl Gui {
-    pub fn new(fps: u32) -> Result<Self, String> {
+    /// Open a window `scale` times the size of the screen, paced at the DMG's refresh rate.
+    /// With `vsync`, presenting a frame also waits for the display's refresh, which avoids tearing.
+    pub fn new(scale: u32, vsync: bool) -> Result<Self, String> {
         let context = sdl2::init()
             .map_err(|e| e.to_string())?;
         let video_subsystem = context.video().unwrap();
-        let window = video_subsystem.window("feox", 320, 288)
+        let mut window = video_subsystem.window("feox", SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale)
             .position_centered()
             .resizable()
             .build()
             .map_err(|e| e.to_string())?;
+        window.set_minimum_size(SCREEN_WIDTH, SCREEN_HEIGHT)
+            .map_err(|e| e.to_string())?;
 
-        let canvas = window.into_canvas().build()
+        let mut canvas = window.into_canvas();
+        if vsync {
+            canvas = canvas.present_vsync();
+        }
+        let mut canvas = canvas.build()
+            .map_err(|e| e.to_string())?;
+        canvas.set_blend_mode(BlendMode::Blend);
+        let texture = canvas.texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH, SCREEN_HEIGHT)
             .map_err(|e| e.to_string())?;
-        
-        let mut fps_man = FPSManager::new();
-        fps_man.set_framerate(fps).map_err(|e| e.to_string())?;
 
         Ok(Self {
             context,
             canvas,
-            fps_man,
+            texture,
+            scaling: Scaling::Integer,
+            filter: Filter::None,
+            ghosting: None,
+            filtered: Vec::new(),
+            frame_duration: Some(normal_frame_duration()),
+            next_frame: Instant::now(),
+            last_present: Instant::now(),
+            osd: Osd::new(),
         })
     }
 
+    /// Show a frame. Faster than normal speed, frames arriving sooner than the DMG
+    /// would show them are dropped, since no display could keep up with them anyway.
     pub fn update_screen(&mut self, bytes: &[u8]) -> Result<(), String> {
-        let texture_creator = self.canvas.texture_creator();
-        let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, 160, 144)
-            .map_err(|e| e.to_string())?;
-        texture.update(None, &bytes, 3 * 160)
+        // Ghosting blends every frame, even ones that are dropped
+        let frame = match &mut self.ghosting {
+            Some(ghosting) => ghosting.blend(bytes),
+            None => bytes,
+        };
+        let fast = match self.frame_duration {
+            Some(duration) => duration < normal_frame_duration(),
+            None => true,
+        };
+        if fast && self.last_present.elapsed() < normal_frame_duration() {
+            return Ok(());
+        }
+        self.last_present = Instant::now();
+
+        self.filter.apply(frame, &mut self.filtered);
+        let width = SCREEN_WIDTH * self.filter.scale() as u32;
+        let height = SCREEN_HEIGHT * self.filter.scale() as u32;
+        if self.texture.query().width != width {
+            let texture = self.canvas.texture_creator()
+                .create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+                .map_err(|e| e.to_string())?;
+            let old = std::mem::replace(&mut self.texture, texture);
+            // Safe since the old texture is no longer used, and the canvas that created it still exists
+            unsafe { old.destroy() };
+        }
+        self.texture.update(None, &self.filtered, 3 * width as usize)
             .map_err(|e| e.to_string())?;
-        self.canvas.copy(&texture, None, None)?;
+        let screen = self.screen_rect()?;
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas.copy(&self.texture, None, screen)?;
+        self.osd.draw(&mut self.canvas, screen)?;
         self.canvas.present();
 
         Ok(())
     }
 
+    /// Where the game screen goes in the window, according to the scaling mode.
+    fn screen_rect(&self) -> Result<Rect, String> {
+        let (width, height) = self.canvas.output_size()?;
+        let (screen_width, screen_height) = match self.scaling {
+            Scaling::Integer => {
+                let scale = (width / SCREEN_WIDTH).min(height / SCREEN_HEIGHT).max(1);
+                (SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale)
+            }
+            Scaling::Fit => {
+                let scale = (width as f64 / SCREEN_WIDTH as f64).min(height as f64 / SCREEN_HEIGHT as f64);
+                ((SCREEN_WIDTH as f64 * scale) as u32, (SCREEN_HEIGHT as f64 * scale) as u32)
+            }
+            Scaling::Stretch => (width, height),
+        };
+        // Centered, and cropped evenly if the window is smaller than the screen
+        let x = (width as i32 - screen_width as i32) / 2;
+        let y = (height as i32 - screen_height as i32) / 2;
+        Ok(Rect::new(x, y, screen_width, screen_height))
+    }
+
+    /// The last frame shown, after filtering, scaled up by the largest whole number that
+    /// fits the screen's size in the window, as `(width, height, RGB24 pixels)`.
+    pub fn scaled_screen(&self) -> Result<(u32, u32, Vec<u8>), String> {
+        let filter_scale = self.filter.scale() as u32;
+        let (width, height) = (SCREEN_WIDTH * filter_scale, SCREEN_HEIGHT * filter_scale);
+        if self.filtered.len() != (width * height * 3) as usize {
+            return Err("no frame has been shown yet".to_string());
+        }
+        let screen = self.screen_rect()?;
+        let scale = (screen.width() / width).min(screen.height() / height).max(1);
+        let row_length = (width * scale * 3) as usize;
+        let mut pixels = Vec::with_capacity(row_length * (height * scale) as usize);
+        for row in self.filtered.chunks(width as usize * 3) {
+            let start = pixels.len();
+            for pixel in row.chunks(3) {
+                for _ in 0..scale {
+                    pixels.extend_from_slice(pixel);
+                }
+            }
+            for _ in 1..scale {
+                pixels.extend_from_within(start..start + row_length);
+            }
+        }
+        Ok((width * scale, height * scale, pixels))
+    }
+
+    pub fn ghosting(&self) -> bool {
+        self.ghosting.is_some()
+    }
+
+    /// Turn blending each frame with the previous ones on or off.
+    pub fn set_ghosting(&mut self, enabled: bool) {
+        self.ghosting = if enabled { Some(Ghosting::new()) } else { None };
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.canvas.window().fullscreen_state() != FullscreenType::Off
+    }
+
+    /// Fill the desktop with the window, or go back to a normal window.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), String> {
+        let state = if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+        self.canvas.window_mut().set_fullscreen(state)
+    }
+
+    /// Wait until the next frame is due.
     pub fn delay(&mut self) {
-        self.fps_man.delay();
+        let frame_duration = match self.frame_duration {
+            Some(duration) => duration,
+            None => {
+                self.next_frame = Instant::now();
+                return;
+            }
+        };
+        self.next_frame += frame_duration;
+        let now = Instant::now();
+        if self.next_frame > now {
+            let remaining = self.next_frame - now;
+            if remaining > SPIN_TIME {
+                thread::sleep(remaining - SPIN_TIME);
+            }
+            while Instant::now() < self.next_frame {
+                thread::yield_now();
+            }
+        } else if now - self.next_frame > frame_duration * MAX_LAG_FRAMES {
+            // Far behind, e.g. after sitting in the debugger console
+            self.next_frame = now;
+        }
     }
 
-    pub fn set_framerate(&mut self, fps: u32) -> Result<(), String> {
-        self.fps_man.set_framerate(fps)
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    /// Run at `speed` times the normal frame rate, or as fast as possible with `None`.
+    pub fn set_speed(&mut self, speed: Option<f64>) {
+        self.frame_duration = speed.map(|speed| normal_frame_duration().div_f64(speed));
     }
 }
+
+fn normal_frame_duration() -> Duration {
+    Duration::from_secs_f64(1.0 / FRAME_RATE)
+}
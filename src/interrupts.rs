@@ -1,3 +1,5 @@
+use crate::savestate::{self, SaveState};
+
 #[derive(Debug)]
 pub enum Interrupt {
     VBlank,
@@ -100,3 +102,16 @@ impl InterruptQueue {
         byte
     }
 }
+
+impl SaveState for InterruptQueue {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        savestate::write_u8(w, self.as_byte())?;
+        savestate::write_u8(w, self.flags_as_byte())
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        self.from_byte(savestate::read_u8(r)?);
+        self.flags_from_byte(savestate::read_u8(r)?);
+        Ok(())
+    }
+}
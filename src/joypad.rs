@@ -1,5 +1,7 @@
 use crate::interrupts::{Interrupt, InterruptQueue};
+use crate::savestate::{self, SaveState};
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Button {
     Right,
     Left,
@@ -11,6 +13,27 @@ pub enum Button {
     Start,
 }
 
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Right, Button::Left, Button::Up, Button::Down,
+        Button::A, Button::B, Button::Select, Button::Start,
+    ];
+
+    /// Bit representing this button in a bitmask of held buttons.
+    pub fn mask(self) -> u8 {
+        match self {
+            Button::Right => 1 << 0,
+            Button::Left => 1 << 1,
+            Button::Up => 1 << 2,
+            Button::Down => 1 << 3,
+            Button::A => 1 << 4,
+            Button::B => 1 << 5,
+            Button::Select => 1 << 6,
+            Button::Start => 1 << 7,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Joypad {
     dpad: bool,
@@ -69,6 +92,26 @@ impl Joypad {
         };
     }
 
+    /// Bitmask (see `Button::mask`) of the buttons currently held.
+    pub fn held_buttons(&self) -> u8 {
+        let held = [self.right, self.left, self.up, self.down,
+                    self.a, self.b, self.select, self.start];
+        Button::ALL.iter().zip(held.iter())
+            .filter(|(_, &held)| held)
+            .fold(0, |mask, (button, _)| mask | button.mask())
+    }
+
+    /// Press and release buttons so exactly the ones in `mask` are held.
+    pub fn set_held_buttons(&mut self, interrupts: &mut InterruptQueue, mask: u8) {
+        for &button in Button::ALL.iter() {
+            if mask & button.mask() != 0 {
+                self.press_button(interrupts, button);
+            } else {
+                self.clear_button(button);
+            }
+        }
+    }
+
     pub fn from_byte(&mut self, byte: u8) {
         // Note: `== 0` is intentional
         self.dpad = byte & 1 << 4 == 0;
@@ -89,3 +132,23 @@ impl Joypad {
         byte
     }
 }
+
+impl SaveState for Joypad {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        let flags = [self.dpad, self.right, self.left, self.up, self.down,
+                     self.buttons, self.a, self.b, self.select, self.start];
+        for &flag in flags.iter() {
+            savestate::write_bool(w, flag)?;
+        }
+        Ok(())
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        let flags = [&mut self.dpad, &mut self.right, &mut self.left, &mut self.up, &mut self.down,
+                     &mut self.buttons, &mut self.a, &mut self.b, &mut self.select, &mut self.start];
+        for flag in flags {
+            *flag = savestate::read_bool(r)?;
+        }
+        Ok(())
+    }
+}
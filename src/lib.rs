@@ -4,4 +4,33 @@ pub mod ppu;
 pub mod interrupts;
 pub mod timer;
 pub mod joypad;
+#[cfg(feature = "sdl")]
 pub mod gui;
+#[cfg(feature = "sdl")]
+pub mod osd;
+pub mod model;
+pub mod gameboy;
+pub mod savestate;
+pub mod rewind;
+pub mod movie;
+pub mod debugger;
+pub mod disasm;
+pub mod trace;
+pub mod symbols;
+pub mod memsearch;
+pub mod cheats;
+pub mod patch;
+pub mod archive;
+pub mod config;
+pub mod filter;
+pub mod palette;
+pub mod screenshot;
+pub mod video;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "sdl")]
+pub mod bindings;
+#[cfg(feature = "sdl")]
+pub mod gamepad;
+#[cfg(feature = "sdl")]
+pub mod vram_viewer;
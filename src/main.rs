@@ -1,78 +1,575 @@
-use feox::emulator::Emulator;
-use feox::cpu::Cpu;
-use feox::gui::Gui;
-use feox::joypad::Button;
+use feox::archive;
+use feox::bindings::{Action, Bindings, Hotkey};
+use feox::cheats::{self, Cheats};
+use feox::config::{self, Config};
+use feox::debugger::{self, ConsoleResult, GdbStub};
+use feox::filter::Filter;
+use feox::disasm::disassemble_rom;
+use feox::gameboy::GameBoy;
+use feox::gamepad::Gamepads;
+use feox::gui::{Gui, Scaling};
+use feox::model::Model;
+use feox::movie::Movie;
+use feox::palette::Palette;
+use feox::patch;
+use feox::rewind::Rewind;
+use feox::screenshot;
+use feox::symbols::{self, Symbols};
+use feox::trace::{self, Trace};
+use feox::video::VideoRecorder;
+use feox::vram_viewer::VramViewer;
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use sdl2::keyboard::Keycode;
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
+
+const USAGE: &str = "usage: feox [options] [bootrom] [rom]
+       feox disasm rom [start [end]]
+
+options:
+    --model dmg0|dmg|mgb|sgb|cgb|agb  hardware model to emulate (default: dmg)
+    --rewind-interval N               frames between rewind snapshots (default: 2)
+    --rewind-buffer MIB               memory to spend on rewind history (default: 32)
+    --rewind-speed N                  snapshots stepped back per frame while rewinding (default: 1)
+    --record FILE                     record joypad input to a movie file
+    --play FILE                       replay a movie file, checking every frame against the recording
+    --record-video FILE               record video to FILE (Y4M) and audio next to it (WAV)
+    --debug                           start paused in the debugger console
+    --gdb PORT                        accept gdb remote protocol connections on localhost:PORT
+    --trace FILE                      log the CPU state before every instruction, in Gameboy Doctor format
+    --trace-pc START-END              only trace instructions in this hexadecimal address range
+    --trace-after N                   only trace once N machine cycles have run
+    --trace-symbols                   append the label of PC to trace lines
+    --sym FILE                        load labels from an RGBDS or no$gmb symbol file
+                                      (default: the ROM's path with a .sym extension, if it exists)
+    --entry NAME                      file to load from a zip archive (default: the first .gb or .gbc file)
+    --patch FILE                      apply an IPS, UPS or BPS patch to the ROM (default: the ROM's
+                                      path with a .ips, .ups or .bps extension, if one exists)
+    --cheats FILE                     load and save cheat codes in FILE (default: the ROM's path
+                                      with a .cht extension)
+    --config FILE                     read settings and key bindings from FILE
+                                      (default: $XDG_CONFIG_HOME/feox/config.ini, if it exists)
+    --vsync                           wait for the display's refresh when presenting frames
+    --scale N                         open the window N times the size of the screen (default: 2)
+    --fullscreen                      start in fullscreen
+
+disasm prints a linear disassembly of the ROM, optionally limited to the
+hexadecimal file offsets start..end, using labels from the ROM's .sym file";
 
 fn main() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
-        eprintln!("usage: feox [bootrom] [rom]");
+    if env::args().nth(1).as_deref() == Some("disasm") {
+        return disasm(env::args().skip(2).collect());
+    }
+
+    let mut model = Model::default();
+    let mut rewind_interval = 2;
+    let mut rewind_buffer_mib = 32;
+    let mut rewind_speed = 1;
+    let mut record_path: Option<String> = None;
+    let mut video_path: Option<PathBuf> = None;
+    let mut play_path: Option<String> = None;
+    let mut start_paused = false;
+    let mut gdb_port = None;
+    let mut trace_path: Option<String> = None;
+    let mut trace_pc = None;
+    let mut trace_after = 0;
+    let mut trace_symbols = false;
+    let mut sym_path: Option<PathBuf> = None;
+    let mut cheats_path: Option<PathBuf> = None;
+    let mut patch_path: Option<PathBuf> = None;
+    let mut entry: Option<String> = None;
+    let mut config_path: Option<PathBuf> = None;
+    let mut vsync = false;
+    let mut scale: Option<u32> = None;
+    let mut fullscreen = false;
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--model" => model = parse_option(&mut args, &arg)?,
+            "--rewind-interval" => rewind_interval = parse_option(&mut args, &arg)?,
+            "--rewind-buffer" => rewind_buffer_mib = parse_option(&mut args, &arg)?,
+            "--rewind-speed" => rewind_speed = parse_option(&mut args, &arg)?,
+            "--record" => record_path = Some(parse_option(&mut args, &arg)?),
+            "--record-video" => video_path = Some(parse_option::<String>(&mut args, &arg)?.into()),
+            "--play" => play_path = Some(parse_option(&mut args, &arg)?),
+            "--debug" => start_paused = true,
+            "--gdb" => gdb_port = Some(parse_option(&mut args, &arg)?),
+            "--trace" => trace_path = Some(parse_option(&mut args, &arg)?),
+            "--trace-pc" => trace_pc = Some(trace::parse_pc_range(&parse_option::<String>(&mut args, &arg)?)?),
+            "--trace-after" => trace_after = parse_option(&mut args, &arg)?,
+            "--trace-symbols" => trace_symbols = true,
+            "--sym" => sym_path = Some(parse_option::<String>(&mut args, &arg)?.into()),
+            "--config" => config_path = Some(parse_option::<String>(&mut args, &arg)?.into()),
+            "--entry" => entry = Some(parse_option(&mut args, &arg)?),
+            "--patch" => patch_path = Some(parse_option::<String>(&mut args, &arg)?.into()),
+            "--cheats" => cheats_path = Some(parse_option::<String>(&mut args, &arg)?.into()),
+            "--vsync" => vsync = true,
+            "--scale" => scale = Some(parse_option(&mut args, &arg)?),
+            "--fullscreen" => fullscreen = true,
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() || paths.len() > 2 {
+        eprintln!("{}", USAGE);
         std::process::exit(-1);
     };
-    let mut bootrom = File::open(&args[1])
-        .expect(&format!("expected to find '{}'", args[1]));
-    let mut rom = File::open(&args[2])
-        .expect(&format!("expected to find '{}'", args[2]));
 
-    let mut emulator = Emulator::new();
-    emulator.load_bootrom(&mut bootrom).expect("failed to read bootrom");
-    emulator.load_rom(&mut rom).expect("failed to read rom");
+    let rom_path = paths.last().unwrap();
+    let rom_name = Path::new(rom_path).file_stem().map(|name| name.to_string_lossy().into_owned());
+    let config = match config_path.or_else(|| config::default_path().filter(|path| path.exists())) {
+        Some(path) => Config::load_file(&path, rom_name.as_deref())
+            .map_err(|e| format!("failed to load config '{}': {}", path.display(), e))?,
+        None => Config::new(),
+    };
+    let bindings = Bindings::new(&config)?;
+    // A fast-forward speed of 0 means as fast as possible
+    let fast_forward_speed = Some(speed_setting(&config, "fast_forward", 0.0)?).filter(|&speed| speed > 0.0);
+    let slow_motion_speed = speed_setting(&config, "slow_motion", 0.5)?;
+    if slow_motion_speed == 0.0 {
+        return Err("slow motion speed in [speed] can't be 0".to_string());
+    }
+
+    let palettes = Palette::all(&config)?;
+    let rom_dir = Path::new(rom_path).parent().map(Path::to_path_buf).unwrap_or_default();
+    let screenshot_dir = config.get("screenshots", "directory").map_or_else(|| rom_dir.clone(), PathBuf::from);
+    let video_dir = config.get("recordings", "directory").map_or_else(|| rom_dir.clone(), PathBuf::from);
+
+    let mut gb = GameBoy::new(model);
+    // Without a palette set, the model's own colours are used until the hotkey picks one
+    let mut palette_index = match config.get("video", "palette") {
+        Some(name) => {
+            let index = palettes.iter().position(|(existing, _)| existing == name)
+                .ok_or_else(|| format!("unknown palette '{}' (expected one of {})", name,
+                                       palettes.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", ")))?;
+            gb.emulator.ppu.set_colors(palettes[index].1);
+            Some(index)
+        }
+        None => None,
+    };
+
+    let mut rom = archive::read_rom(Path::new(rom_path), entry.as_deref())
+        .map_err(|e| format!("failed to read rom '{}': {}", rom_path, e))?;
+    if let Some(path) = patch_path.or_else(|| patch::path_for_rom(Path::new(rom_path))) {
+        rom = fs::read(&path).and_then(|data| patch::apply(&rom, &data))
+            .map_err(|e| format!("failed to apply patch '{}': {}", path.display(), e))?;
+        println!("Applied patch '{}'", path.display());
+    }
+    gb.load_rom(&rom)
+        .map_err(|e| format!("failed to load rom '{}': {}", rom_path, e))?;
+
+    if paths.len() == 2 {
+        let mut bootrom = File::open(&paths[0])
+            .unwrap_or_else(|_| panic!("expected to find '{}'", paths[0]));
+        gb.load_bootrom(&mut bootrom).expect("failed to read bootrom");
+    } else {
+        gb.skip_boot();
+    }
 
-    let mut cpu = Cpu::new();
+    if let Some(path) = sym_path.or_else(|| symbols::path_for_rom(Path::new(rom_path))) {
+        gb.symbols = Symbols::load_file(&path)
+            .map_err(|e| format!("failed to load symbols '{}': {}", path.display(), e))?;
+    }
+
+    let cheats_path = cheats_path.unwrap_or_else(|| cheats::path_for_rom(Path::new(rom_path)));
+    if cheats_path.exists() {
+        gb.cheats = Cheats::load_file(&cheats_path)
+            .map_err(|e| format!("failed to load cheats '{}': {}", cheats_path.display(), e))?;
+    }
 
-    let mut gui = Gui::new(160)?;
+    if let Some(path) = trace_path {
+        let file = File::create(&path)
+            .map_err(|e| format!("failed to create trace '{}': {}", path, e))?;
+        let mut trace = Trace::new(Box::new(BufWriter::new(file)));
+        trace.pc_range = trace_pc;
+        trace.start_cycle = trace_after;
+        trace.show_symbols = trace_symbols;
+        gb.trace = Some(trace);
+    }
+
+    if start_paused {
+        gb.debugger.pause();
+    }
+    let mut gdb = match gdb_port {
+        Some(port) => Some(GdbStub::bind(port)
+            .map_err(|e| format!("failed to listen for gdb on port {}: {}", port, e))?),
+        None => None,
+    };
+
+    let mut rewind = Rewind::new(rewind_interval, rewind_buffer_mib * 1024 * 1024);
+
+    let mut recording = record_path.as_ref().map(|_| Movie::begin(&gb.cpu, &gb.emulator));
+    let mut video_recorder = match video_path {
+        Some(path) => Some(VideoRecorder::create(&path)
+            .map_err(|e| format!("failed to record video '{}': {}", path.display(), e))?),
+        None => None,
+    };
+    let mut playback = match play_path {
+        Some(path) => {
+            let movie = File::open(&path).and_then(|mut f| Movie::load(&mut f))
+                .map_err(|e| format!("failed to load movie '{}': {}", path, e))?;
+            movie.rewind_to_start(&mut gb.cpu, &mut gb.emulator)
+                .map_err(|e| format!("failed to start movie '{}': {}", path, e))?;
+            Some(movie)
+        }
+        None => None,
+    };
+    let mut frame = 0;
+
+    // Movies don't store cheats, so one made with them wouldn't replay anywhere else
+    let cheats_enabled = gb.cheats.all_enabled();
+    if (recording.is_some() || playback.is_some()) && cheats_enabled && !gb.cheats.is_empty() {
+        gb.cheats.set_all_enabled(false);
+        println!("Cheats are disabled while a movie is recording or playing");
+    }
+
+    let scale = match scale {
+        Some(scale) => scale,
+        None => setting(&config, "video", "scale", 2)?,
+    };
+    if scale == 0 {
+        return Err("the scale must be at least 1".to_string());
+    }
+    let mut gui = Gui::new(scale, vsync)?;
+    gui.scaling = setting(&config, "video", "scaling", Scaling::Integer)?;
+    gui.filter = setting(&config, "video", "filter", Filter::None)?;
+    gui.set_ghosting(setting(&config, "video", "ghosting", false)?);
+    gui.set_fullscreen(fullscreen || setting(&config, "video", "fullscreen", false)?)?;
     let mut event_pump = gui.context.event_pump()
         .map_err(|e| e.to_string())?;
+    let mut gamepads = Gamepads::new(&gui.context)?;
+    gui.osd.show_fps = setting(&config, "osd", "fps", false)?;
+    gui.osd.show_input = setting(&config, "osd", "input", false)?;
+
+    let mut vram_viewer: Option<VramViewer> = None;
 
-    let mut debug = false;
+    let stdin = io::stdin();
+    let mut rewinding = false;
+    let mut fast_forward_held = false;
+    let mut fast_forward = false;
+    let mut slow_motion = false;
+    let mut game_paused = false;
+    let mut advance_frame = false;
+    let mut held = 0;
     'running: loop {
-        let mut cycles = 0;
-        while cycles < 17476 {
-            for event in event_pump.poll_iter() { match event { Event::Quit {..} |
-                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'running,
-                    Event::KeyDown { keycode: Some(Keycode::Q), .. } => debug = !debug,
-                    Event::KeyDown { keycode: Some(key), .. } => {
-                        if let Some(button) = map_keycode_to_joypad(key) {
-                            emulator.joypad_press(button);
-                        }
+        // Rewinding or loading a state would break the timeline of a movie
+        let movie_active = recording.is_some() || playback.is_some();
+        for event in event_pump.poll_iter() {
+            if gamepads.handle_event(&event, &bindings) {
+                continue;
+            }
+            match event {
+                Event::Quit {..} => break 'running,
+                Event::Window { window_id, win_event: WindowEvent::Close, .. } => {
+                    // Closing the VRAM viewer only closes the viewer, closing the game window quits
+                    if matches!(&vram_viewer, Some(viewer) if viewer.window_id() == window_id) {
+                        vram_viewer = None;
+                    } else {
+                        break 'running;
+                    }
+                }
+                Event::KeyDown { keycode: Some(key), keymod, repeat, .. } => {
+                    match bindings.key_down(key, keymod) {
+                        Some(Action::Joypad(button)) => held |= button.mask(),
+                        // Holding a key down shouldn't toggle things over and over
+                        Some(Action::Hotkey(_)) if repeat => (),
+                        Some(Action::Hotkey(hotkey)) => match hotkey {
+                            Hotkey::Quit => break 'running,
+                            Hotkey::Pause => gb.debugger.pause(),
+                            Hotkey::DebugOutput => gb.debug = !gb.debug,
+                            Hotkey::Rewind => rewinding = !movie_active,
+                            Hotkey::FastForward => fast_forward_held = true,
+                            Hotkey::FastForwardToggle => fast_forward = !fast_forward,
+                            Hotkey::SlowMotion => slow_motion = !slow_motion,
+                            Hotkey::PauseGame => game_paused = !game_paused,
+                            Hotkey::FrameAdvance => {
+                                // Advancing a running game pauses it on the next frame
+                                advance_frame = true;
+                                game_paused = true;
+                            }
+                            Hotkey::ShowFps => gui.osd.show_fps = !gui.osd.show_fps,
+                            Hotkey::ShowInput => gui.osd.show_input = !gui.osd.show_input,
+                            Hotkey::Fullscreen => gui.set_fullscreen(!gui.is_fullscreen())?,
+                            Hotkey::Scaling => {
+                                gui.scaling = gui.scaling.next();
+                                gui.osd.message(format!("Scaling: {}", gui.scaling));
+                            }
+                            Hotkey::Filter => {
+                                gui.filter = gui.filter.next();
+                                gui.osd.message(format!("Filter: {}", gui.filter));
+                            }
+                            Hotkey::Palette => {
+                                let index = palette_index.map_or(0, |index| (index + 1) % palettes.len());
+                                gb.emulator.ppu.set_colors(palettes[index].1);
+                                gui.osd.message(format!("Palette: {}", palettes[index].0));
+                                palette_index = Some(index);
+                            }
+                            Hotkey::Screenshot | Hotkey::ScreenshotScaled => {
+                                let path = screenshot::new_path(&screenshot_dir, rom_name.as_deref().unwrap_or("feox"), "png");
+                                let result = if hotkey == Hotkey::Screenshot {
+                                    gb.save_screenshot(&path).map_err(|e| e.to_string())
+                                } else {
+                                    gui.scaled_screen().and_then(|(width, height, pixels)| {
+                                        screenshot::save_png(&path, width, height, &pixels).map_err(|e| e.to_string())
+                                    })
+                                };
+                                match result {
+                                    Ok(()) => {
+                                        println!("Saved screenshot to '{}'", path.display());
+                                        gui.osd.message("Screenshot saved");
+                                    }
+                                    Err(e) => {
+                                        eprintln!("Failed to save screenshot '{}': {}", path.display(), e);
+                                        gui.osd.message("Failed to save screenshot");
+                                    }
+                                }
+                            }
+                            Hotkey::RecordVideo => match video_recorder.take() {
+                                Some(recorder) => {
+                                    finish_video(recorder);
+                                    gui.osd.message("Video recording stopped");
+                                }
+                                None => {
+                                    let path = screenshot::new_path(&video_dir, rom_name.as_deref().unwrap_or("feox"), "y4m");
+                                    match VideoRecorder::create(&path) {
+                                        Ok(recorder) => {
+                                            println!("Recording video to '{}'", path.display());
+                                            gui.osd.message("Recording video");
+                                            video_recorder = Some(recorder);
+                                        }
+                                        Err(e) => {
+                                            eprintln!("Failed to record video '{}': {}", path.display(), e);
+                                            gui.osd.message("Failed to record video");
+                                        }
+                                    }
+                                }
+                            },
+                            Hotkey::Ghosting => {
+                                let enabled = !gui.ghosting();
+                                gui.set_ghosting(enabled);
+                                gui.osd.message(format!("Ghosting {}", if enabled { "on" } else { "off" }));
+                            }
+                            Hotkey::Cheats => {
+                                if movie_active {
+                                    eprintln!("Can't toggle cheats while a movie is recording or playing");
+                                    gui.osd.message("Can't toggle cheats in a movie");
+                                } else {
+                                    let enabled = !gb.cheats.all_enabled();
+                                    gb.cheats.set_all_enabled(enabled);
+                                    let message = format!("Cheats {}", if enabled { "enabled" } else { "disabled" });
+                                    println!("{}", message);
+                                    gui.osd.message(message);
+                                }
+                            }
+                            Hotkey::VramViewer => {
+                                vram_viewer = match vram_viewer {
+                                    Some(_) => None,
+                                    None => Some(VramViewer::new(&gui.context)?),
+                                };
+                            }
+                            Hotkey::SaveState(slot) => {
+                                let path = state_path(rom_path, slot);
+                                match File::create(&path).and_then(|mut f| gb.save_state(&mut f)) {
+                                    Ok(()) => {
+                                        println!("Saved state {} to '{}'", slot, path.display());
+                                        gui.osd.message(format!("State {} saved", slot));
+                                    }
+                                    Err(e) => {
+                                        eprintln!("Failed to save state {}: {}", slot, e);
+                                        gui.osd.message(format!("Failed to save state {}", slot));
+                                    }
+                                }
+                            }
+                            Hotkey::LoadState(slot) => {
+                                let path = state_path(rom_path, slot);
+                                if movie_active {
+                                    eprintln!("Can't load a save state while a movie is recording or playing");
+                                    gui.osd.message("Can't load states in a movie");
+                                } else {
+                                    match File::open(&path).and_then(|mut f| gb.load_state(&mut f)) {
+                                        Ok(()) => {
+                                            println!("Loaded state {} from '{}'", slot, path.display());
+                                            gui.osd.message(format!("State {} loaded", slot));
+                                        }
+                                        Err(e) => {
+                                            eprintln!("Failed to load state {}: {}", slot, e);
+                                            gui.osd.message(format!("Failed to load state {}", slot));
+                                        }
+                                    }
+                                }
+                            }
+                        },
+                        None => (),
                     }
-                    Event::KeyUp { keycode: Some(key), .. } => {
-                        if let Some(button) = map_keycode_to_joypad(key) {
-                            emulator.joypad_clear(button);
+                }
+                Event::KeyUp { keycode: Some(key), .. } => {
+                    for action in bindings.key_up(key) {
+                        match action {
+                            Action::Joypad(button) => held &= !button.mask(),
+                            Action::Hotkey(Hotkey::Rewind) => rewinding = false,
+                            Action::Hotkey(Hotkey::FastForward) => fast_forward_held = false,
+                            Action::Hotkey(_) => (),
                         }
                     }
-                    _ => (),
+                }
+                _ => (),
+            }
+        }
+
+        let (speed, status) = if fast_forward_held || fast_forward {
+            (fast_forward_speed, match fast_forward_speed {
+                Some(speed) => format!("Fast forward {}x", speed),
+                None => "Fast forward".to_string(),
+            })
+        } else if slow_motion {
+            (Some(slow_motion_speed), format!("Slow motion {}x", slow_motion_speed))
+        } else {
+            (Some(1.0), String::new())
+        };
+        gui.set_speed(speed);
+        let status = if game_paused { "Paused".to_string() } else { status };
+        gui.osd.set_status(&status);
+
+        if game_paused && !advance_frame {
+            // Keep showing the last frame
+        } else if rewinding {
+            for _ in 0..rewind_speed {
+                if !rewind.step_back(&mut gb.cpu, &mut gb.emulator) {
+                    break;
                 }
             }
+        } else {
+            // Input only changes between frames so that movies replay deterministically
+            let buttons = match &playback {
+                Some(movie) => movie.buttons(frame).unwrap_or(0),
+                None => held | gamepads.held(),
+            };
+            gb.set_buttons(buttons);
+            gui.osd.set_input(buttons);
+            let cycles = gb.run_frame();
+            gui.osd.count_frame();
+            rewind.end_frame(&gb.cpu, &gb.emulator);
 
-            if debug { println!("{:?}", cpu) };
+            if let Some(recorder) = &mut video_recorder {
+                if let Err(e) = recorder.record_frame(gb.framebuffer(), cycles) {
+                    eprintln!("Stopped recording video: {}", e);
+                    gui.osd.message("Video recording failed");
+                    video_recorder = None;
+                }
+            }
 
-            cpu.step(&mut emulator);
-            cycles += emulator.catch_up_cycles();
+            if let Some(movie) = &mut recording {
+                movie.record_frame(buttons, gb.shades());
+            }
+            if let Some(movie) = &playback {
+                if let Err(desync) = movie.verify_frame(frame, gb.shades()) {
+                    eprintln!("{}", desync);
+                }
+                if frame + 1 >= movie.len() {
+                    println!("Movie finished after {} frames", movie.len());
+                    gui.osd.message("Movie finished");
+                    playback = None;
+                    if recording.is_none() {
+                        gb.cheats.set_all_enabled(cheats_enabled);
+                    }
+                }
+            }
+            frame += 1;
         }
+        advance_frame = false;
 
-        gui.update_screen(&emulator.ppu.framebuffer())?;
+        gui.update_screen(gb.framebuffer())?;
+        if let Some(viewer) = &mut vram_viewer {
+            viewer.update(&gb.emulator.ppu)?;
+        }
+        if let Some(gdb) = &mut gdb {
+            gdb.poll(&mut gb);
+        }
+        let gdb_attached = matches!(&gdb, Some(gdb) if gdb.is_attached());
+        if gb.debugger.is_paused() && !gdb_attached
+            && debugger::console(&mut gb, &mut stdin.lock()) == ConsoleResult::Quit {
+            break 'running;
+        }
         gui.delay();
     }
+
+    if gb.cheats.is_modified() {
+        gb.cheats.save_file(&cheats_path)
+            .map_err(|e| format!("failed to save cheats '{}': {}", cheats_path.display(), e))?;
+    }
+    if let Some(recorder) = video_recorder {
+        finish_video(recorder);
+    }
+    if let (Some(movie), Some(path)) = (recording, record_path) {
+        File::create(&path).and_then(|mut f| movie.save(&mut f))
+            .map_err(|e| format!("failed to save movie '{}': {}", path, e))?;
+        println!("Recorded {} frames to '{}'", movie.len(), path);
+    }
     Ok(())
 }
- 
-fn map_keycode_to_joypad(keycode: Keycode) -> Option<Button> {
-    match keycode {
-        Keycode::Up => Some(Button::Up),
-        Keycode::Down => Some(Button::Down),
-        Keycode::Left => Some(Button::Left),
-        Keycode::Right => Some(Button::Right),
-        Keycode::A => Some(Button::A),
-        Keycode::B => Some(Button::B),
-        Keycode::Space => Some(Button::Select),
-        Keycode::Return => Some(Button::Start),
-        _ => None,
+
+fn finish_video(recorder: VideoRecorder) {
+    let (path, frames) = (recorder.path().to_path_buf(), recorder.frames());
+    match recorder.finish() {
+        Ok(()) => println!("Recorded {} frames of video to '{}'", frames, path.display()),
+        Err(e) => eprintln!("Failed to finish video '{}': {}", path.display(), e),
+    }
+}
+
+fn disasm(args: Vec<String>) -> Result<(), String> {
+    if args.is_empty() || args.len() > 3 {
+        eprintln!("{}", USAGE);
+        std::process::exit(-1);
     }
+    let rom = archive::read_rom(Path::new(&args[0]), None)
+        .map_err(|e| format!("failed to read '{}': {}", args[0], e))?;
+    let offset = |i: usize, default: usize| match args.get(i) {
+        Some(arg) => usize::from_str_radix(arg.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("invalid offset '{}'", arg)),
+        None => Ok(default),
+    };
+    let start = offset(1, 0)?;
+    let end = offset(2, rom.len())?;
+    let symbols = match symbols::path_for_rom(Path::new(&args[0])) {
+        Some(path) => Symbols::load_file(&path)
+            .map_err(|e| format!("failed to load symbols '{}': {}", path.display(), e))?,
+        None => Symbols::new(),
+    };
+
+    let stdout = io::stdout();
+    disassemble_rom(&rom, start, end, &symbols, &mut stdout.lock())
+        .map_err(|e| e.to_string())
+}
+
+fn parse_option<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<T, String>
+    where T::Err: std::fmt::Display
+{
+    let value = args.next().ok_or(format!("{} expects an argument", flag))?;
+    value.parse().map_err(|e| format!("invalid value '{}' for {}: {}", value, flag, e))
+}
+
+/// A setting from the config, or `default` if it isn't set.
+fn setting<T: FromStr>(config: &Config, section: &str, key: &str, default: T) -> Result<T, String> {
+    match config.get(section, key) {
+        Some(value) => value.parse()
+            .map_err(|_| format!("invalid value '{}' for {} in [{}]", value, key, section)),
+        None => Ok(default),
+    }
+}
+
+/// A speed multiplier from the `[speed]` section of the config.
+fn speed_setting(config: &Config, key: &str, default: f64) -> Result<f64, String> {
+    let speed = setting(config, "speed", key, default)?;
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(format!("invalid speed '{}' for {} in [speed]", speed, key));
+    }
+    Ok(speed)
+}
+
+fn state_path(rom_path: &str, slot: u8) -> PathBuf {
+    Path::new(rom_path).with_extension(format!("ss{}", slot))
 }
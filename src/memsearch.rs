@@ -0,0 +1,171 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::emulator::Emulator;
+
+/// RAM areas searched for values: cartridge RAM, work RAM and high RAM.
+const SEARCH_RANGES: [RangeInclusive<u16>; 3] = [0xA000..=0xBFFF, 0xC000..=0xDFFF, 0xFF80..=0xFFFE];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValueSize {
+    Byte,
+    /// Two bytes, little endian like the CPU stores them
+    Word,
+}
+
+impl FromStr for ValueSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(ValueSize::Byte),
+            "16" => Ok(ValueSize::Word),
+            _ => Err(format!("invalid value size '{}', expected 8 or 16", s)),
+        }
+    }
+}
+
+/// How to narrow down a search, comparing each candidate's current value
+/// with the value it had when the search last looked at it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Equal(u16),
+    NotEqual(u16),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+/// An address still matching every filter applied so far.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub address: u16,
+    /// Value when the last filter was applied
+    pub value: u16,
+}
+
+/// Iterative search for the RAM address holding a value, e.g. a game's life counter.
+///
+/// Starting a search snapshots every address in RAM. Each filter then keeps only
+/// the addresses whose value passes it and updates the snapshot, so alternating
+/// between playing and filtering quickly narrows down to a handful of addresses.
+#[derive(Debug, Clone)]
+pub struct MemorySearch {
+    size: ValueSize,
+    candidates: Vec<Candidate>,
+}
+
+impl MemorySearch {
+    pub fn new(emulator: &Emulator, size: ValueSize) -> Self {
+        let last = match size {
+            ValueSize::Byte => 0,
+            ValueSize::Word => 1,
+        };
+        let candidates = SEARCH_RANGES.iter()
+            .flat_map(|range| *range.start()..=*range.end() - last)
+            .map(|address| Candidate { address, value: read(emulator, size, address) })
+            .collect();
+        Self { size, candidates }
+    }
+
+    pub fn size(&self) -> ValueSize {
+        self.size
+    }
+
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    /// Keep only the candidates passing `filter`, returning how many are left.
+    pub fn filter(&mut self, emulator: &Emulator, filter: Filter) -> usize {
+        let size = self.size;
+        self.candidates = self.candidates.iter()
+            .filter_map(|candidate| {
+                let current = read(emulator, size, candidate.address);
+                let keep = match filter {
+                    Filter::Equal(value) => current == value,
+                    Filter::NotEqual(value) => current != value,
+                    Filter::Changed => current != candidate.value,
+                    Filter::Unchanged => current == candidate.value,
+                    Filter::Increased => current > candidate.value,
+                    Filter::Decreased => current < candidate.value,
+                };
+                if keep {
+                    Some(Candidate { address: candidate.address, value: current })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        self.candidates.len()
+    }
+}
+
+fn read(emulator: &Emulator, size: ValueSize, address: u16) -> u16 {
+    let low = emulator.read_mapped(address) as u16;
+    match size {
+        ValueSize::Byte => low,
+        ValueSize::Word => (emulator.read_mapped(address + 1) as u16) << 8 | low,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(search: &MemorySearch) -> Vec<u16> {
+        search.candidates().iter().map(|candidate| candidate.address).collect()
+    }
+
+    #[test]
+    fn narrows_down_bytes() {
+        let mut emulator = Emulator::default();
+        emulator.write_mapped(0xC010, 5);
+        emulator.write_mapped(0xD020, 5);
+        emulator.write_mapped(0xFF90, 5);
+        let mut search = MemorySearch::new(&emulator, ValueSize::Byte);
+        assert_eq!(search.candidates().last().map(|c| c.address), Some(0xFFFE));
+
+        assert_eq!(search.filter(&emulator, Filter::Equal(5)), 3);
+        assert_eq!(addresses(&search), [0xC010, 0xD020, 0xFF90]);
+
+        emulator.write_mapped(0xC010, 6);
+        emulator.write_mapped(0xD020, 4);
+        assert_eq!(search.filter(&emulator, Filter::Changed), 2);
+        assert_eq!(search.candidates(), [Candidate { address: 0xC010, value: 6 },
+                                         Candidate { address: 0xD020, value: 4 }]);
+
+        // Compared with the value at the last filter, not at the start
+        emulator.write_mapped(0xC010, 7);
+        emulator.write_mapped(0xD020, 3);
+        let mut increased = search.clone();
+        assert_eq!(increased.filter(&emulator, Filter::Increased), 1);
+        assert_eq!(addresses(&increased), [0xC010]);
+        assert_eq!(search.filter(&emulator, Filter::Decreased), 1);
+        assert_eq!(addresses(&search), [0xD020]);
+
+        assert_eq!(search.filter(&emulator, Filter::Decreased), 0);
+    }
+
+    #[test]
+    fn words_are_little_endian_and_stay_in_range() {
+        let mut emulator = Emulator::default();
+        emulator.write_mapped(0xC100, 0x34);
+        emulator.write_mapped(0xC101, 0x12);
+        let mut search = MemorySearch::new(&emulator, ValueSize::Word);
+
+        // A word never reaches past the end of an area, and never reads IE at FFFF
+        let all = addresses(&search);
+        assert_eq!(all.last(), Some(&0xFFFD));
+        assert!(all.contains(&0xDFFE) && !all.contains(&0xDFFF));
+        assert!(all.contains(&0xBFFE) && !all.contains(&0xBFFF));
+
+        assert_eq!(search.filter(&emulator, Filter::Equal(0x1234)), 1);
+        assert_eq!(addresses(&search), [0xC100]);
+
+        emulator.write_mapped(0xC101, 0x11);
+        assert_eq!(search.filter(&emulator, Filter::Decreased), 1);
+        assert_eq!(search.candidates()[0].value, 0x1134);
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Game Boy hardware revisions.
+///
+/// feox only emulates the monochrome (DMG-compatible) mode of each model,
+/// but the revisions still differ in their post-boot state and in a few quirks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Model {
+    /// Early original Game Boy revision with a different boot ROM
+    Dmg0,
+    /// Original Game Boy
+    #[default]
+    Dmg,
+    /// Game Boy Pocket
+    Mgb,
+    /// Super Game Boy
+    Sgb,
+    /// Game Boy Color
+    Cgb,
+    /// Game Boy Advance
+    Agb,
+}
+
+impl Model {
+    /// Whether this model uses the Game Boy Color CPU (which fixes several DMG hardware bugs).
+    pub fn is_cgb(self) -> bool {
+        matches!(self, Model::Cgb | Model::Agb)
+    }
+
+    /// 16-bit increments and decrements pointing into OAM during mode 2 corrupt OAM
+    /// on every model except the CGB family.
+    pub fn has_oam_corruption(self) -> bool {
+        !self.is_cgb()
+    }
+
+    /// Writing to STAT during HBlank or VBlank raises a spurious STAT interrupt
+    /// on every model except the CGB family.
+    pub fn has_stat_write_bug(self) -> bool {
+        !self.is_cgb()
+    }
+
+    /// The four shades a monochrome game is displayed with on this model.
+    pub fn default_palette(self) -> [(u8, u8, u8); 4] {
+        match self {
+            Model::Dmg0 | Model::Dmg => [
+                (0xE0, 0xF8, 0xD0),
+                (0x88, 0xC0, 0x70),
+                (0x34, 0x68, 0x56),
+                (0x08, 0x18, 0x20),
+            ],
+            Model::Mgb => [
+                (0xC4, 0xCF, 0xA1),
+                (0x8B, 0x95, 0x6D),
+                (0x4D, 0x53, 0x3C),
+                (0x1F, 0x1F, 0x1F),
+            ],
+            // SGB palette 1-A
+            Model::Sgb => [
+                (0xF8, 0xE8, 0xC8),
+                (0xD8, 0x90, 0x48),
+                (0xA8, 0x28, 0x20),
+                (0x30, 0x18, 0x50),
+            ],
+            // Palette picked by the CGB boot ROM for unrecognized monochrome games
+            Model::Cgb | Model::Agb => [
+                (0xFF, 0xFF, 0xFF),
+                (0x7B, 0xFF, 0x31),
+                (0x00, 0x63, 0xC5),
+                (0x00, 0x00, 0x00),
+            ],
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dmg0" => Ok(Model::Dmg0),
+            "dmg" => Ok(Model::Dmg),
+            "mgb" => Ok(Model::Mgb),
+            "sgb" => Ok(Model::Sgb),
+            "cgb" => Ok(Model::Cgb),
+            "agb" => Ok(Model::Agb),
+            _ => Err(format!("unknown model '{}' (expected one of dmg0, dmg, mgb, sgb, cgb, agb)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Model::Dmg0 => "dmg0",
+            Model::Dmg => "dmg",
+            Model::Mgb => "mgb",
+            Model::Sgb => "sgb",
+            Model::Cgb => "cgb",
+            Model::Agb => "agb",
+        };
+        write!(w, "{}", name)
+    }
+}
@@ -0,0 +1,259 @@
+use std::io::{self, Read, Write};
+
+use crate::cpu::Cpu;
+use crate::emulator::Emulator;
+use crate::savestate::{self, invalid_data};
+
+const MAGIC: &[u8; 8] = b"FEOXMOVI";
+pub const MOVIE_VERSION: u16 = 3;
+/// Far larger than any save state, to reject corrupt lengths before allocating
+const MAX_START_STATE_SIZE: usize = 1024 * 1024;
+/// Bytes stored per frame: the buttons and the screen CRC
+const FRAME_SIZE: usize = 5;
+
+/// A recording of the joypad state on every frame, starting from a save state.
+///
+/// Along with the input, the CRC32 of the shades on screen at the end of each frame is
+/// recorded, so playback can check that it reproduces the original run exactly.
+/// Shades rather than colours are checked so that the palette doesn't matter.
+pub struct Movie {
+    rom_checksum: u32,
+    start_state: Vec<u8>,
+    frames: Vec<Frame>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Frame {
+    buttons: u8,
+    screen_crc: u32,
+}
+
+/// Reported when a replayed frame doesn't look like the recorded one.
+#[derive(Debug)]
+pub struct Desync {
+    pub frame: usize,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl Movie {
+    /// Start recording from the current state of the machine.
+    pub fn begin(cpu: &Cpu, emulator: &Emulator) -> Self {
+        let mut start_state = Vec::new();
+        savestate::save(cpu, emulator, &mut start_state)
+            .expect("writing to a Vec can't fail");
+        Self {
+            rom_checksum: emulator.rom_checksum(),
+            start_state,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Record a frame that was run with `buttons` held and ended with `shades` on screen.
+    pub fn record_frame(&mut self, buttons: u8, shades: &[u8]) {
+        self.frames.push(Frame {
+            buttons,
+            screen_crc: crc32fast::hash(shades),
+        });
+    }
+
+    /// Put the machine back in the state the recording started from.
+    pub fn rewind_to_start(&self, cpu: &mut Cpu, emulator: &mut Emulator) -> io::Result<()> {
+        if self.rom_checksum != emulator.rom_checksum() {
+            return Err(invalid_data(&format!("movie was recorded with a different ROM (checksum {:08X}, loaded ROM is {:08X})",
+                                             self.rom_checksum, emulator.rom_checksum())));
+        }
+        savestate::load(cpu, emulator, &mut &self.start_state[..])
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Buttons held during the given frame, as a `Button::mask` bitmask.
+    pub fn buttons(&self, frame: usize) -> Option<u8> {
+        self.frames.get(frame).map(|f| f.buttons)
+    }
+
+    /// Check the shades on screen at the end of a replayed frame against the recording.
+    pub fn verify_frame(&self, frame: usize, shades: &[u8]) -> Result<(), Desync> {
+        let expected = match self.frames.get(frame) {
+            Some(f) => f.screen_crc,
+            None => return Ok(()),
+        };
+        let actual = crc32fast::hash(shades);
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Desync { frame, expected, actual })
+        }
+    }
+
+    pub fn save(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        savestate::write_u16(w, MOVIE_VERSION)?;
+        savestate::write_u32(w, self.rom_checksum)?;
+        savestate::write_u32(w, self.start_state.len() as u32)?;
+        w.write_all(&self.start_state)?;
+        savestate::write_u32(w, self.frames.len() as u32)?;
+        for frame in &self.frames {
+            savestate::write_u8(w, frame.buttons)?;
+            savestate::write_u32(w, frame.screen_crc)?;
+        }
+        Ok(())
+    }
+
+    pub fn load(r: &mut dyn Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a feox movie"));
+        }
+        let version = savestate::read_u16(r)?;
+        if version != MOVIE_VERSION {
+            return Err(invalid_data(&format!("unsupported movie version {} (expected {})",
+                                             version, MOVIE_VERSION)));
+        }
+        let rom_checksum = savestate::read_u32(r)?;
+        let state_size = savestate::read_u32(r)? as usize;
+        if state_size > MAX_START_STATE_SIZE {
+            return Err(invalid_data(&format!("movie's save state is {} bytes, more than the maximum of {}",
+                                             state_size, MAX_START_STATE_SIZE)));
+        }
+        let start_state = read_bytes(r, state_size)?;
+
+        // Read the frames before allocating for them, so a bogus count can't
+        // allocate more than the file actually holds
+        let frame_count = savestate::read_u32(r)? as usize;
+        let data = read_bytes(r, frame_count * FRAME_SIZE)?;
+        let frames = data.chunks_exact(FRAME_SIZE)
+            .map(|frame| Frame {
+                buttons: frame[0],
+                screen_crc: u32::from_le_bytes([frame[1], frame[2], frame[3], frame[4]]),
+            })
+            .collect();
+        Ok(Self { rom_checksum, start_state, frames })
+    }
+}
+
+/// Read exactly `len` bytes, growing the buffer only as data arrives.
+fn read_bytes(r: &mut dyn Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    r.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(invalid_data("movie is truncated"));
+    }
+    Ok(data)
+}
+
+impl std::fmt::Display for Desync {
+    fn fmt(&self, w: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(w, "replay desynced at frame {}: screen CRC is {:08X}, recorded {:08X}",
+               self.frame, self.actual, self.expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::GameBoy;
+    use crate::palette::Palette;
+    use crate::testing::input_gameboy;
+
+    /// Record `frames` frames with the d-pad cycling through every combination.
+    fn record(gb: &mut GameBoy, frames: usize) -> Movie {
+        let mut movie = Movie::begin(&gb.cpu, &gb.emulator);
+        for frame in 0..frames {
+            let buttons = (frame / 4 % 16) as u8;
+            gb.set_buttons(buttons);
+            gb.run_frame();
+            movie.record_frame(buttons, gb.shades());
+        }
+        movie
+    }
+
+    #[test]
+    fn replay_matches_every_frame() {
+        let mut gb = input_gameboy();
+        for _ in 0..10 {
+            gb.run_frame();
+        }
+        let movie = record(&mut gb, 120);
+        let crcs: Vec<_> = movie.frames.iter().map(|frame| frame.screen_crc).collect();
+        assert!(crcs.windows(2).any(|pair| pair[0] != pair[1]), "input should change the screen");
+
+        let mut file = Vec::new();
+        movie.save(&mut file).unwrap();
+        let movie = Movie::load(&mut &file[..]).unwrap();
+        assert_eq!(movie.len(), 120);
+
+        // Replay on a fresh machine that has run a different number of frames
+        let mut gb = input_gameboy();
+        gb.run_frame();
+        movie.rewind_to_start(&mut gb.cpu, &mut gb.emulator).unwrap();
+        for frame in 0..movie.len() {
+            gb.set_buttons(movie.buttons(frame).unwrap());
+            gb.run_frame();
+            if let Err(desync) = movie.verify_frame(frame, gb.shades()) {
+                panic!("{}", desync);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_ignores_the_palette() {
+        let mut gb = input_gameboy();
+        gb.emulator.ppu.set_colors(Palette::presets()[3].1);
+        let movie = record(&mut gb, 60);
+
+        let mut gb = input_gameboy();
+        movie.rewind_to_start(&mut gb.cpu, &mut gb.emulator).unwrap();
+        gb.emulator.ppu.set_colors(Palette::presets()[2].1);
+        for frame in 0..movie.len() {
+            gb.set_buttons(movie.buttons(frame).unwrap());
+            gb.run_frame();
+            if let Err(desync) = movie.verify_frame(frame, gb.shades()) {
+                panic!("{}", desync);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_with_other_input_desyncs() {
+        let mut gb = input_gameboy();
+        let movie = record(&mut gb, 60);
+        movie.rewind_to_start(&mut gb.cpu, &mut gb.emulator).unwrap();
+        let desynced = (0..movie.len()).any(|frame| {
+            gb.set_buttons(!movie.buttons(frame).unwrap() & 0x0F);
+            gb.run_frame();
+            movie.verify_frame(frame, gb.shades()).is_err()
+        });
+        assert!(desynced);
+    }
+
+    #[test]
+    fn rejects_bogus_lengths() {
+        let mut gb = input_gameboy();
+        let mut file = Vec::new();
+        record(&mut gb, 10).save(&mut file).unwrap();
+        let load = |data: &[u8]| Movie::load(&mut &data[..]).err().map(|e| e.kind());
+
+        // Start state length, right after the magic, version and ROM checksum
+        let mut huge_state = file.clone();
+        huge_state[14..18].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(load(&huge_state), Some(io::ErrorKind::InvalidData));
+
+        // Frame count
+        let mut huge_count = file.clone();
+        let count_at = file.len() - 10 * FRAME_SIZE - 4;
+        huge_count[count_at..count_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(load(&huge_count), Some(io::ErrorKind::InvalidData));
+
+        assert_eq!(load(&file[..file.len() - 1]), Some(io::ErrorKind::InvalidData));
+        assert!(Movie::load(&mut &file[..]).is_ok());
+    }
+}
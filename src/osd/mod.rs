@@ -0,0 +1,194 @@
+//! On-screen display: notifications, a status line, an FPS counter and a joypad
+//! input display, drawn over the game screen.
+
+mod font;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::joypad::Button;
+use font::{GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// How long a message stays on screen.
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+/// Older messages are dropped once there are this many.
+const MAX_MESSAGES: usize = 4;
+/// Distance from the edges of the screen, in Game Boy pixels
+const MARGIN: i32 = 2;
+const LINE_HEIGHT: i32 = GLYPH_HEIGHT + 3;
+
+const TEXT_COLOR: Color = Color::RGB(0xFF, 0xFF, 0xFF);
+const BACKGROUND_COLOR: Color = Color::RGBA(0x00, 0x00, 0x00, 0xA0);
+const HELD_COLOR: Color = Color::RGB(0xFF, 0xFF, 0xFF);
+const RELEASED_COLOR: Color = Color::RGB(0x60, 0x60, 0x60);
+
+/// Where each button is in the input display, as (button, x, y, width, height).
+const INPUT_LAYOUT: [(Button, i32, i32, i32, i32); 8] = [
+    (Button::Up, 3, 0, 3, 3),
+    (Button::Left, 0, 3, 3, 3),
+    (Button::Right, 6, 3, 3, 3),
+    (Button::Down, 3, 6, 3, 3),
+    (Button::Select, 12, 6, 4, 2),
+    (Button::Start, 18, 6, 4, 2),
+    (Button::B, 24, 4, 3, 3),
+    (Button::A, 28, 2, 3, 3),
+];
+const INPUT_WIDTH: i32 = 31;
+const INPUT_HEIGHT: i32 = 9;
+
+pub struct Osd {
+    messages: VecDeque<(String, Instant)>,
+    /// Shown in the top right corner until changed, e.g. "Paused"
+    status: String,
+    pub show_fps: bool,
+    pub show_input: bool,
+    /// Held buttons, as a bitmask of `Button::mask`s
+    input: u8,
+    fps: f64,
+    frames: u32,
+    frames_since: Instant,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            status: String::new(),
+            show_fps: false,
+            show_input: false,
+            input: 0,
+            fps: 0.0,
+            frames: 0,
+            frames_since: Instant::now(),
+        }
+    }
+
+    /// Show a notification for a few seconds.
+    pub fn message(&mut self, text: impl Into<String>) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((text.into(), Instant::now()));
+    }
+
+    /// Show `status` until it's changed; an empty status shows nothing.
+    pub fn set_status(&mut self, status: &str) {
+        if status != self.status {
+            self.status = status.to_string();
+        }
+    }
+
+    pub fn set_input(&mut self, buttons: u8) {
+        self.input = buttons;
+    }
+
+    /// Count an emulated frame for the FPS counter.
+    pub fn count_frame(&mut self) {
+        self.frames += 1;
+        let elapsed = self.frames_since.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.frames = 0;
+            self.frames_since = Instant::now();
+        }
+    }
+
+    /// Draw over the game screen, which covers `screen` in the window.
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, screen: Rect) -> Result<(), String> {
+        while matches!(self.messages.front(), Some((_, shown)) if shown.elapsed() >= MESSAGE_DURATION) {
+            self.messages.pop_front();
+        }
+
+        let mut painter = Painter::new(canvas, screen);
+        if self.show_fps {
+            painter.text(MARGIN, MARGIN, &format!("{:.1} FPS", self.fps))?;
+        }
+        if !self.status.is_empty() {
+            let x = painter.width - MARGIN - text_width(&self.status);
+            painter.text(x, MARGIN, &self.status)?;
+        }
+        let mut y = painter.height - MARGIN - GLYPH_HEIGHT;
+        for (text, _) in self.messages.iter().rev() {
+            painter.text(MARGIN, y, text)?;
+            y -= LINE_HEIGHT;
+        }
+        if self.show_input {
+            let x = painter.width - MARGIN - INPUT_WIDTH;
+            let y = painter.height - MARGIN - INPUT_HEIGHT;
+            painter.fill(x - 1, y - 1, INPUT_WIDTH + 2, INPUT_HEIGHT + 2, BACKGROUND_COLOR)?;
+            for &(button, bx, by, width, height) in INPUT_LAYOUT.iter() {
+                let color = if self.input & button.mask() != 0 { HELD_COLOR } else { RELEASED_COLOR };
+                painter.fill(x + bx, y + by, width, height, color)?;
+            }
+            painter.fill(x + 3, y + 3, 3, 3, RELEASED_COLOR)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Osd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn text_width(text: &str) -> i32 {
+    text.chars().count() as i32 * (GLYPH_WIDTH + 1) - 1
+}
+
+/// Draws in Game Boy pixels, scaled up to the size of the screen in the window.
+struct Painter<'a> {
+    canvas: &'a mut Canvas<Window>,
+    screen: Rect,
+    scale: i32,
+    /// Size of the screen in scaled pixels; more than 160x144 if the screen is stretched
+    width: i32,
+    height: i32,
+}
+
+impl<'a> Painter<'a> {
+    fn new(canvas: &'a mut Canvas<Window>, screen: Rect) -> Self {
+        let scale = (screen.width() / 160).min(screen.height() / 144).max(1) as i32;
+        Self {
+            canvas,
+            screen,
+            scale,
+            width: screen.width() as i32 / scale,
+            height: screen.height() as i32 / scale,
+        }
+    }
+
+    fn rect(&self, x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect::new(self.screen.x() + x * self.scale, self.screen.y() + y * self.scale,
+                  (width * self.scale) as u32, (height * self.scale) as u32)
+    }
+
+    fn fill(&mut self, x: i32, y: i32, width: i32, height: i32, color: Color) -> Result<(), String> {
+        let rect = self.rect(x, y, width, height);
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rect(rect)
+    }
+
+    /// Draw a line of text on a dark background, with its top left corner at `(x, y)`.
+    fn text(&mut self, x: i32, y: i32, text: &str) -> Result<(), String> {
+        self.fill(x - 1, y - 1, text_width(text) + 2, GLYPH_HEIGHT + 2, BACKGROUND_COLOR)?;
+        let mut pixels = Vec::new();
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as i32 * (GLYPH_WIDTH + 1);
+            for (row, bits) in font::glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (0x10 >> column) != 0 {
+                        pixels.push(self.rect(left + column, y + row as i32, 1, 1));
+                    }
+                }
+            }
+        }
+        self.canvas.set_draw_color(TEXT_COLOR);
+        self.canvas.fill_rects(&pixels)
+    }
+}
@@ -0,0 +1,155 @@
+//! The colours monochrome games are shown in.
+
+use crate::config::Config;
+use crate::model::Model;
+
+/// The four shades, from lightest to darkest, that the DMG's palette registers pick from.
+pub type Shades = [(u8, u8, u8); 4];
+
+/// Shades for the background and window, and for each of the two sprite palettes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub bg: Shades,
+    pub obj0: Shades,
+    pub obj1: Shades,
+}
+
+impl Palette {
+    /// The same shades for the background and sprites, as on real hardware.
+    pub fn uniform(shades: Shades) -> Self {
+        Self { bg: shades, obj0: shades, obj1: shades }
+    }
+
+    /// The built-in palettes, by name.
+    pub fn presets() -> Vec<(String, Palette)> {
+        vec![
+            ("dmg".to_string(), Palette::uniform(Model::Dmg.default_palette())),
+            ("pocket".to_string(), Palette::uniform(Model::Mgb.default_palette())),
+            ("light".to_string(), Palette::uniform([
+                (0x00, 0xB5, 0x81),
+                (0x00, 0x9A, 0x71),
+                (0x00, 0x69, 0x4A),
+                (0x00, 0x4F, 0x3B),
+            ])),
+            ("grey".to_string(), Palette::uniform([
+                (0xFF, 0xFF, 0xFF),
+                (0xAA, 0xAA, 0xAA),
+                (0x55, 0x55, 0x55),
+                (0x00, 0x00, 0x00),
+            ])),
+        ]
+    }
+
+    /// The presets followed by the palettes defined in the `[palettes]` section of the config:
+    ///
+    /// ```ini
+    /// [palettes]
+    /// sepia = F8E8C8 D0B088 806040 302010
+    /// red = FFFFFF FF8080 C00000 400000
+    /// red.obj1 = FFFFFF 80C0FF 0040C0 000040
+    /// ```
+    ///
+    /// `name = ...` sets the shades of every layer, and `name.bg`, `name.obj0`
+    /// and `name.obj1` set the shades of a single one.
+    pub fn all(config: &Config) -> Result<Vec<(String, Palette)>, String> {
+        let mut palettes = Self::presets();
+        let preset_count = palettes.len();
+
+        // Whole palettes first, so layers can be set in any order
+        let entries: Vec<_> = config.section("palettes").collect();
+        for &(name, value) in entries.iter().filter(|(name, _)| !name.contains('.')) {
+            let palette = Palette::uniform(parse_shades(value)
+                .map_err(|e| format!("invalid palette '{}' in [palettes]: {}", name, e))?);
+            match palettes.iter_mut().position(|(existing, _)| existing == name) {
+                Some(i) if i < preset_count => return Err(format!("palette '{}' in [palettes] is a preset", name)),
+                Some(i) => palettes[i].1 = palette,
+                None => palettes.push((name.to_string(), palette)),
+            }
+        }
+        for &(key, value) in entries.iter().filter(|(name, _)| name.contains('.')) {
+            let mut parts = key.splitn(2, '.');
+            let (name, layer) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+            let palette = palettes.iter_mut().skip(preset_count)
+                .find(|(existing, _)| existing == name)
+                .map(|(_, palette)| palette)
+                .ok_or_else(|| format!("'{}' in [palettes] is for a palette that isn't defined", key))?;
+            let shades = match layer {
+                "bg" => &mut palette.bg,
+                "obj0" => &mut palette.obj0,
+                "obj1" => &mut palette.obj1,
+                _ => return Err(format!("unknown palette layer '{}' in [palettes] (expected bg, obj0 or obj1)", layer)),
+            };
+            *shades = parse_shades(value).map_err(|e| format!("invalid palette '{}' in [palettes]: {}", key, e))?;
+        }
+        Ok(palettes)
+    }
+}
+
+/// Four colours as hexadecimal RGB, lightest first, separated by spaces or commas.
+pub fn parse_shades(s: &str) -> Result<Shades, String> {
+    let colors: Vec<_> = s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|color| !color.is_empty())
+        .collect();
+    if colors.len() != 4 {
+        return Err(format!("expected 4 colours, got {}", colors.len()));
+    }
+    let mut shades = [(0, 0, 0); 4];
+    for (shade, color) in shades.iter_mut().zip(colors) {
+        let hex = color.trim_start_matches('#');
+        let rgb = Some(hex).filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| format!("invalid colour '{}' (expected RRGGBB)", color))?;
+        *shade = ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+    }
+    Ok(shades)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEPIA: Shades = [(0xF8, 0xE8, 0xC8), (0xD0, 0xB0, 0x88), (0x80, 0x60, 0x40), (0x30, 0x20, 0x10)];
+
+    fn palettes(text: &str) -> Result<Vec<(String, Palette)>, String> {
+        Palette::all(&Config::load(&mut text.as_bytes(), None).unwrap())
+    }
+
+    #[test]
+    fn parses_shades() {
+        assert_eq!(parse_shades("F8E8C8 D0B088 806040 302010"), Ok(SEPIA));
+        assert_eq!(parse_shades("#f8e8c8, #d0b088,#806040 ,302010"), Ok(SEPIA));
+        assert!(parse_shades("F8E8C8 D0B088 806040").is_err());
+        assert!(parse_shades("F8E8C8 D0B088 806040 302010 000000").is_err());
+        assert!(parse_shades("F8E8C8 D0B088 806040 30201").is_err());
+        assert!(parse_shades("F8E8C8 D0B088 806040 30201G").is_err());
+        assert!(parse_shades("F8E8C8 D0B088 806040 +02010").is_err());
+    }
+
+    #[test]
+    fn reads_palettes_after_presets() {
+        let all = palettes("[palettes]\n\
+                            red.obj1 = FFFFFF 80C0FF 0040C0 000040\n\
+                            sepia = F8E8C8 D0B088 806040 302010\n\
+                            red = FFFFFF FF8080 C00000 400000\n").unwrap();
+        let presets = Palette::presets();
+        assert_eq!(&all[..presets.len()], &presets[..]);
+
+        let custom = &all[presets.len()..];
+        assert_eq!(custom[0], ("sepia".to_string(), Palette::uniform(SEPIA)));
+        let red = custom[1].1;
+        assert_eq!(custom[1].0, "red");
+        assert_eq!(red.bg, red.obj0);
+        assert_eq!(red.bg[2], (0xC0, 0x00, 0x00));
+        assert_eq!(red.obj1[1], (0x80, 0xC0, 0xFF));
+    }
+
+    #[test]
+    fn rejects_invalid_palettes() {
+        // Redefining a preset, a layer of an undefined palette, an unknown layer, bad colours
+        assert!(palettes("[palettes]\ndmg = FFFFFF AAAAAA 555555 000000\n").is_err());
+        assert!(palettes("[palettes]\nred.bg = FFFFFF AAAAAA 555555 000000\n").is_err());
+        assert!(palettes("[palettes]\nred = FFFFFF AAAAAA 555555 000000\nred.win = FFFFFF AAAAAA 555555 000000\n").is_err());
+        assert!(palettes("[palettes]\nred = FFFFFF AAAAAA 555555\n").is_err());
+        assert!(palettes("").is_ok());
+    }
+}
@@ -0,0 +1,372 @@
+//! IPS, UPS and BPS patches, applied to a ROM in memory.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::emulator::MAX_ROM_SIZE;
+use crate::savestate::invalid_data;
+
+/// The patch for a ROM: `game.ips`, `game.ups` or `game.bps` next to `game.gb`, if one exists.
+pub fn path_for_rom(rom_path: &Path) -> Option<PathBuf> {
+    ["ips", "ups", "bps"].iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .find(|path| path.exists())
+}
+
+/// Apply a patch to `rom`, returning the patched ROM. The format is detected from the patch's header.
+///
+/// UPS and BPS patches carry CRC32s of the ROM they apply to, of the patched ROM and of
+/// the patch itself; any mismatch is an error, since it means the patch is for another ROM
+/// (or revision of it) or one of the files is corrupt.
+pub fn apply(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(rom, &patch[5..])
+    } else if patch.starts_with(b"UPS1") {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(rom, patch)
+    } else {
+        Err(invalid_data("not an IPS, UPS or BPS patch"))
+    }
+}
+
+/// Reads the fields of a patch, failing on truncated input.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self {
+        Self { data, offset }
+    }
+
+    fn bytes(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.saturating_add(length))
+            .ok_or_else(|| invalid_data("patch is truncated"))?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16_be(&mut self) -> io::Result<usize> {
+        Ok(self.bytes(2)?.iter().fold(0, |value, &b| value << 8 | b as usize))
+    }
+
+    fn u24_be(&mut self) -> io::Result<usize> {
+        Ok(self.bytes(3)?.iter().fold(0, |value, &b| value << 8 | b as usize))
+    }
+
+    /// The variable length numbers used by UPS and BPS: 7 bits per byte, least significant
+    /// first, with the top bit marking the last byte and an offset making encodings unique.
+    fn number(&mut self) -> io::Result<usize> {
+        let too_large = || invalid_data("number in patch is too large");
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.u8()?;
+            value = (byte as usize & 0x7F).checked_mul(shift)
+                .and_then(|n| value.checked_add(n))
+                .ok_or_else(too_large)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(too_large)?;
+            value = value.checked_add(shift).ok_or_else(too_large)?;
+        }
+    }
+}
+
+/// IPS: records of a 24-bit offset and either a run of bytes or a repeated byte,
+/// up to an `EOF` marker optionally followed by the size to truncate the ROM to.
+fn apply_ips(rom: &[u8], records: &[u8]) -> io::Result<Vec<u8>> {
+    let mut target = rom.to_vec();
+    let mut reader = Reader::new(records, 0);
+    loop {
+        let offset = reader.u24_be()?;
+        if offset == 0x454F46 {
+            // "EOF"
+            break;
+        }
+        let length = reader.u16_be()?;
+        let (length, bytes) = match length {
+            0 => {
+                let length = reader.u16_be()?;
+                (length, vec![reader.u8()?; length])
+            }
+            _ => (length, reader.bytes(length)?.to_vec()),
+        };
+        if offset + length > MAX_ROM_SIZE {
+            return Err(invalid_data("patch makes the ROM too large"));
+        }
+        if target.len() < offset + length {
+            target.resize(offset + length, 0);
+        }
+        target[offset..offset + length].copy_from_slice(&bytes);
+    }
+    if let Ok(size) = reader.u24_be() {
+        target.truncate(size);
+    }
+    Ok(target)
+}
+
+/// Check the CRC32s at the end of a UPS or BPS patch, returning the size of the patch without them.
+fn check_footer(rom: &[u8], patch: &[u8]) -> io::Result<(usize, u32)> {
+    if patch.len() < 4 + 12 {
+        return Err(invalid_data("patch is truncated"));
+    }
+    let footer = patch.len() - 12;
+    let crc = |offset: usize| u32::from_le_bytes([patch[offset], patch[offset + 1], patch[offset + 2], patch[offset + 3]]);
+    if crc32fast::hash(&patch[..patch.len() - 4]) != crc(footer + 8) {
+        return Err(invalid_data("patch is corrupt (checksum mismatch)"));
+    }
+    if crc32fast::hash(rom) != crc(footer) {
+        return Err(invalid_data(&format!("patch is for a different ROM (checksum {:08X}, ROM is {:08X})",
+                                         crc(footer), crc32fast::hash(rom))));
+    }
+    Ok((footer, crc(footer + 4)))
+}
+
+fn check_target(target: &[u8], expected: u32) -> io::Result<()> {
+    let crc = crc32fast::hash(target);
+    if crc != expected {
+        return Err(invalid_data(&format!("patched ROM has checksum {:08X}, expected {:08X}", crc, expected)));
+    }
+    Ok(())
+}
+
+/// UPS: sizes of the source and target, then runs of bytes XORed with the source,
+/// each preceded by the distance from the end of the previous one.
+fn apply_ups(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    let (end, target_crc) = check_footer(rom, patch)?;
+    let mut reader = Reader::new(&patch[..end], 4);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    if source_size != rom.len() {
+        return Err(invalid_data(&format!("patch is for a {} byte ROM, ROM is {} bytes", source_size, rom.len())));
+    }
+    if target_size > MAX_ROM_SIZE {
+        return Err(invalid_data("patch makes the ROM too large"));
+    }
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+    let mut offset: usize = 0;
+    while reader.offset < end {
+        offset = offset.saturating_add(reader.number()?);
+        // Each run ends with a 0 byte, which also counts as a byte of the run
+        loop {
+            let byte = reader.u8()?;
+            if let Some(target_byte) = target.get_mut(offset) {
+                *target_byte ^= byte;
+            }
+            offset += 1;
+            if byte == 0 {
+                break;
+            }
+        }
+    }
+    check_target(&target, target_crc)?;
+    Ok(target)
+}
+
+/// BPS: sizes of the source and target and some metadata, then commands building the
+/// target from bytes of the source, of the patch, or of the target written so far.
+fn apply_bps(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    let (end, target_crc) = check_footer(rom, patch)?;
+    let mut reader = Reader::new(&patch[..end], 4);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(invalid_data(&format!("patch is for a {} byte ROM, ROM is {} bytes", source_size, rom.len())));
+    }
+    if target_size > MAX_ROM_SIZE {
+        return Err(invalid_data("patch makes the ROM too large"));
+    }
+
+    let out_of_range = || invalid_data("patch copies from outside the ROM");
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+    while reader.offset < end {
+        let command = reader.number()?;
+        let length = (command >> 2) + 1;
+        if length > target_size - target.len() {
+            return Err(invalid_data("patch writes past the end of the ROM"));
+        }
+        match command & 3 {
+            // SourceRead: copy from the same position in the source
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + length).ok_or_else(out_of_range)?);
+            }
+            // TargetRead: bytes stored in the patch
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            // SourceCopy and TargetCopy: copy from a position relative to the last copy
+            kind => {
+                let relative = reader.number()?;
+                let offset = if kind == 2 { &mut source_offset } else { &mut target_offset };
+                *offset = if relative & 1 != 0 {
+                    offset.checked_sub(relative >> 1)
+                } else {
+                    offset.checked_add(relative >> 1)
+                }.ok_or_else(out_of_range)?;
+                if kind == 2 {
+                    target.extend_from_slice(rom.get(*offset..offset.saturating_add(length)).ok_or_else(out_of_range)?);
+                    *offset += length;
+                } else {
+                    // The copy may overlap the bytes it produces, so go one byte at a time
+                    for _ in 0..length {
+                        let byte = *target.get(*offset).ok_or_else(out_of_range)?;
+                        target.push(byte);
+                        *offset += 1;
+                    }
+                }
+            }
+        }
+    }
+    if target.len() != target_size {
+        return Err(invalid_data(&format!("patched ROM is {} bytes, expected {}", target.len(), target_size)));
+    }
+    check_target(&target, target_crc)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a number the way UPS and BPS store them.
+    fn number(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    /// A UPS or BPS patch: the header and commands, then the CRC32s of the ROM,
+    /// the patched ROM and the patch.
+    fn with_footer(body: Vec<u8>, rom: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut patch = body;
+        patch.extend_from_slice(&crc32fast::hash(rom).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        let crc = crc32fast::hash(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        patch
+    }
+
+    fn is_invalid(result: io::Result<Vec<u8>>) -> bool {
+        matches!(result, Err(e) if e.kind() == io::ErrorKind::InvalidData)
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        for &value in &[0, 1, 0x7F, 0x80, 0x407F, 0x4080, 0x12_3456, MAX_ROM_SIZE] {
+            let encoded = number(value);
+            assert_eq!(Reader::new(&encoded, 0).number().unwrap(), value, "{:?}", encoded);
+        }
+        assert!(Reader::new(&[0x7F; 16], 0).number().is_err());
+    }
+
+    #[test]
+    fn ips_writes_records() {
+        let rom = [0u8; 8];
+        let mut patch = b"PATCH".to_vec();
+        // 2 bytes at 1, then 3 copies of $AA at 5, then a byte past the end
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0x11, 0x22]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0xAA]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x09, 0x00, 0x01, 0x33]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(apply(&rom, &patch).unwrap(), [0, 0x11, 0x22, 0, 0, 0xAA, 0xAA, 0xAA, 0, 0x33]);
+
+        // A size after the EOF marker truncates the ROM
+        patch.extend_from_slice(&[0x00, 0x00, 0x04]);
+        assert_eq!(apply(&rom, &patch).unwrap(), [0, 0x11, 0x22, 0]);
+    }
+
+    #[test]
+    fn ips_rejects_bad_patches() {
+        assert!(is_invalid(apply(&[0; 4], b"PATCH\x00\x00\x01\x00\x04\x11")));
+        assert!(is_invalid(apply(&[0; 4], b"PATCH\x00\x00\x01")));
+        assert!(is_invalid(apply(&[0; 4], b"PATCH\x80\x00\x00\x00\x01\x11EOF")));
+        assert!(is_invalid(apply(&[0; 4], b"NOT A PATCH")));
+    }
+
+    #[test]
+    fn ups_xors_runs() {
+        let rom = [0x10u8, 0x20, 0x30, 0x40];
+        let target = [0x10u8, 0x21, 0x32, 0x40, 0x00, 0x55];
+        let mut body = b"UPS1".to_vec();
+        body.extend(number(rom.len()));
+        body.extend(number(target.len()));
+        // Skip 1 byte, XOR 2, then skip 1 and XOR the byte the ROM is extended by
+        body.extend(number(1));
+        body.extend_from_slice(&[0x01, 0x02, 0x00]);
+        body.extend(number(1));
+        body.extend_from_slice(&[0x55, 0x00]);
+        let patch = with_footer(body, &rom, &target);
+        assert_eq!(apply(&rom, &patch).unwrap(), target);
+
+        // Another ROM, and a corrupted patch
+        assert!(is_invalid(apply(&[0x11, 0x20, 0x30, 0x40], &patch)));
+        let mut corrupt = patch.clone();
+        corrupt[8] ^= 1;
+        assert!(is_invalid(apply(&rom, &corrupt)));
+    }
+
+    #[test]
+    fn bps_runs_every_command() {
+        let rom = b"ABCDEFGH";
+        let target = b"ABxyxyxyCDEFGH";
+        let mut body = b"BPS1".to_vec();
+        body.extend(number(rom.len()));
+        body.extend(number(target.len()));
+        body.extend(number(2));
+        body.extend_from_slice(b"md");
+        let command = |kind: usize, length: usize| number((length - 1) << 2 | kind);
+        // SourceRead "AB"
+        body.extend(command(0, 2));
+        // TargetRead "xy"
+        body.extend(command(1, 2));
+        body.extend_from_slice(b"xy");
+        // TargetCopy "xyxy" from offset 2, overlapping what it writes
+        body.extend(command(3, 4));
+        body.extend(number(2 << 1));
+        // SourceCopy "CDEF" from offset 2
+        body.extend(command(2, 4));
+        body.extend(number(2 << 1));
+        // SourceCopy "GH", right after the last source copy
+        body.extend(command(2, 2));
+        body.extend(number(0 << 1));
+        let patch = with_footer(body, rom, target);
+        assert_eq!(apply(rom, &patch).unwrap(), &target[..]);
+        assert!(is_invalid(apply(b"ABCDEFGX", &patch)));
+    }
+
+    #[test]
+    fn bps_rejects_copies_outside_the_rom() {
+        let rom = b"ABCD";
+        let target = b"ABCDAB";
+        let mut body = b"BPS1".to_vec();
+        body.extend(number(rom.len()));
+        body.extend(number(target.len()));
+        body.extend(number(0));
+        // SourceCopy from 2 bytes before the start
+        body.extend(number((6 - 1) << 2 | 2));
+        body.extend(number(2 << 1 | 1));
+        let patch = with_footer(body, rom, target);
+        assert!(is_invalid(apply(rom, &patch)));
+    }
+}
@@ -0,0 +1,92 @@
+//! Read-only views of VRAM and OAM for debugging tools.
+
+use crate::ppu::Ppu;
+
+/// Number of 8x8 tiles in VRAM's tile data area ($8000-$97FF).
+pub const TILE_COUNT: usize = 384;
+
+/// A sprite's entry in OAM, with its attribute byte decoded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Sprite {
+    /// Position in OAM, 0-39; lower indices have priority
+    pub index: u8,
+    /// Screen position plus 16, as stored in OAM
+    pub y: u8,
+    /// Screen position plus 8, as stored in OAM
+    pub x: u8,
+    pub tile: u8,
+    pub behind_bg: bool,
+    pub flip_y: bool,
+    pub flip_x: bool,
+    /// Uses OBP1 rather than OBP0
+    pub palette1: bool,
+}
+
+impl Ppu {
+    /// The color numbers (0-3, before applying a palette) of tile `index`'s pixels,
+    /// indexed by row then column. Tile `index` is stored at $8000 + index * 16.
+    pub fn tile(&self, index: usize) -> [[u8; 8]; 8] {
+        let address = index * 16;
+        let mut pixels = [[0; 8]; 8];
+        for (y, row) in pixels.iter_mut().enumerate() {
+            let byte1 = self.vram[address + y * 2];
+            let byte2 = self.vram[address + y * 2 + 1];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let bit = 7 - x;
+                *pixel = (byte2 >> bit) & 1 | ((byte1 >> bit) & 1) << 1;
+            }
+        }
+        pixels
+    }
+
+    /// One of the two 32x32 tile maps (0 at $9800, 1 at $9C00), as raw tile numbers.
+    pub fn tile_map(&self, map: usize) -> [[u8; 32]; 32] {
+        let base = if map == 0 { 0x1800 } else { 0x1C00 };
+        let mut tiles = [[0; 32]; 32];
+        for (y, row) in tiles.iter_mut().enumerate() {
+            row.copy_from_slice(&self.vram[base + y * 32..base + y * 32 + 32]);
+        }
+        tiles
+    }
+
+    /// The index (for `tile`) of the tile a tile map entry refers to, following
+    /// the background addressing mode selected in LCDC.
+    pub fn bg_tile_index(&self, tile_number: u8) -> usize {
+        if self.control.tile_ram_unsigned_mode || tile_number >= 128 {
+            tile_number as usize
+        } else {
+            256 + tile_number as usize
+        }
+    }
+
+    /// Which tile map the background uses.
+    pub fn bg_map(&self) -> usize {
+        self.control.bg_use_upper_map as usize
+    }
+
+    /// Which tile map the window uses.
+    pub fn window_map(&self) -> usize {
+        self.control.window_use_upper_map as usize
+    }
+
+    /// Sprite height in pixels, 8 or 16.
+    pub fn sprite_height(&self) -> u8 {
+        if self.control.obj_long_blocks { 16 } else { 8 }
+    }
+
+    /// All 40 entries in OAM.
+    pub fn sprites(&self) -> Vec<Sprite> {
+        self.oam.chunks_exact(4).enumerate()
+            .map(|(index, data)| Sprite {
+                index: index as u8,
+                y: data[0],
+                x: data[1],
+                tile: data[2],
+                behind_bg: data[3] & 1 << 7 != 0,
+                flip_y: data[3] & 1 << 6 != 0,
+                flip_x: data[3] & 1 << 5 != 0,
+                palette1: data[3] & 1 << 4 != 0,
+            })
+            .collect()
+    }
+}
@@ -1,24 +1,30 @@
 mod control;
+mod inspect;
 
 use control::PpuControl;
+pub use inspect::{Sprite, TILE_COUNT};
 use crate::interrupts::{Interrupt, InterruptQueue};
+use crate::model::Model;
+use crate::palette::{Palette, Shades};
+use crate::savestate::{self, SaveState};
 
 const VRAM_SIZE: usize = 0x2000;
 const OAM_SIZE: usize = 0xA0;
 const OAM_ACCESS_CYCLES: u32 = 20;
 const VRAM_ACCESS_CYCLES: u32 = 43;
 const HBLANK_CYCLES: u32 = 51;
-const SCANLINE_CYCLES: u32 = OAM_ACCESS_CYCLES + VRAM_ACCESS_CYCLES;
+/// Every line takes 456 dots, VBlank lines included, even though they have no
+/// OAM scan, drawing or HBlank of their own.
+const SCANLINE_CYCLES: u32 = OAM_ACCESS_CYCLES + VRAM_ACCESS_CYCLES + HBLANK_CYCLES;
+/// Cycles from the start of one frame to the next: 144 visible lines and 10 lines of VBlank.
+pub const FRAME_CYCLES: u32 = SCANLINE_CYCLES * 154;
 
 const SCREEN_WIDTH: usize = 160;
 const SCREEN_HEIGHT: usize = 144;
 const TILE_WIDTH: usize = 8;
 
 const LCD_OFF_FRAMEBUFFER: [u8; SCREEN_WIDTH * SCREEN_HEIGHT * 3] = [0x05; SCREEN_WIDTH * SCREEN_HEIGHT * 3];
-const COLOR0: (u8, u8, u8) = (0xE0, 0xF8, 0xD0);
-const COLOR1: (u8, u8, u8) = (0x88, 0xC0, 0x70);
-const COLOR2: (u8, u8, u8) = (0x34, 0x68, 0x56);
-const COLOR3: (u8, u8, u8) = (0x08, 0x18, 0x20);
+static LCD_OFF_SHADES: [u8; SCREEN_WIDTH * SCREEN_HEIGHT] = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
 
 #[derive(Debug, Clone, Copy)]
 enum PpuMode {
@@ -29,7 +35,12 @@ enum PpuMode {
 }
 
 pub struct Ppu {
+    model: Model,
+    /// The shades the palette registers pick from
+    colors: Palette,
     framebuffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT * 3],
+    /// The shade, 0-3, each pixel of the framebuffer was drawn in
+    shades: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
     cycles: u32,
     control: PpuControl,
     mode: PpuMode,
@@ -39,29 +50,42 @@ pub struct Ppu {
     pub palette: [(u8, u8, u8); 4],
     pub obj_palette0: [(u8, u8, u8); 4],
     pub obj_palette1: [(u8, u8, u8); 4],
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
     current_line: u8,
     lyc: u8,
     pub scroll_y: u8,
     pub scroll_x: u8,
+    /// Set on entering VBlank, when the framebuffer holds a complete frame
+    frame_done: bool,
 }
 
 impl Ppu {
-    pub fn new() -> Self {
+    pub fn new(model: Model) -> Self {
+        let colors = Palette::uniform(model.default_palette());
         Self {
+            model,
+            colors,
             framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT * 3],
+            shades: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
             cycles: 0,
             control: PpuControl::default(),
             mode: PpuMode::InOam,
             interrupt_enabled: [false; 4],
             vram: [0; VRAM_SIZE],
             oam: [0; OAM_SIZE],
-            palette: [COLOR0; 4],
-            obj_palette0: [COLOR0; 4],
-            obj_palette1: [COLOR0; 4],
+            palette: [colors.bg[0]; 4],
+            obj_palette0: [colors.obj0[0]; 4],
+            obj_palette1: [colors.obj1[0]; 4],
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
             current_line: 0,
             lyc: 0,
             scroll_x: 0,
             scroll_y: 0,
+            frame_done: false,
         }
     }
 
@@ -102,6 +126,7 @@ impl Ppu {
                         self.current_line += 1;
                         if self.current_line as usize == SCREEN_HEIGHT {
                             self.mode = PpuMode::VBlank;
+                            self.frame_done = true;
                             interrupts.request_interrupt(Interrupt::VBlank);
                             if self.interrupt_enabled[1] {
                                 interrupts.request_interrupt(Interrupt::Lcd);
@@ -172,7 +197,8 @@ impl Ppu {
                 // Get actual index of pixel on screen
                 let screen_idx = self.current_line as usize * SCREEN_WIDTH + tile_x * TILE_WIDTH + i;
 
-                self.framebuffer[screen_idx * 3 + 0] = r;
+                self.shades[screen_idx] = shade(self.bgp, color_id);
+                self.framebuffer[screen_idx * 3] = r;
                 self.framebuffer[screen_idx * 3 + 1] = g;
                 self.framebuffer[screen_idx * 3 + 2] = b;
             }
@@ -199,7 +225,11 @@ impl Ppu {
             let behind_bg = attributes & 1 << 7 != 0;
             let flip_vertically = attributes & 1 << 6 != 0;
             let flip_horizontally = attributes & 1 << 5 != 0;
-            let obj_palette = if attributes & 1 << 4 != 0 { self.obj_palette1 } else { self.obj_palette0 };
+            let (obj_palette, obp) = if attributes & 1 << 4 != 0 {
+                (self.obj_palette1, self.obp1)
+            } else {
+                (self.obj_palette0, self.obp0)
+            };
 
             // This sprite is on top of the background and will be drawn again anyway; so skip it
             if !behind_bg && !drawing_over_bg { continue; }
@@ -231,12 +261,9 @@ impl Ppu {
 
                 // If drawing over background and sprite is behind,
                 // only update the pixel if it is Color 0.
-                if behind_bg && !drawing_over_bg {
-                    if self.framebuffer[screen_idx * 3 + 0] != COLOR0.0 { continue; }
-                    if self.framebuffer[screen_idx * 3 + 1] != COLOR0.1 { continue; }
-                    if self.framebuffer[screen_idx * 3 + 2] != COLOR0.2 { continue; }
-                }
-                self.framebuffer[screen_idx * 3 + 0] = r;
+                if behind_bg && !drawing_over_bg && self.shades[screen_idx] != 0 { continue; }
+                self.shades[screen_idx] = shade(obp, color_id);
+                self.framebuffer[screen_idx * 3] = r;
                 self.framebuffer[screen_idx * 3 + 1] = g;
                 self.framebuffer[screen_idx * 3 + 2] = b;
             }
@@ -288,6 +315,34 @@ impl Ppu {
         }
     }
 
+    /// Emulate the OAM corruption bug triggered by a 16-bit increment or decrement
+    /// of an address in OAM while the PPU is scanning it.
+    ///
+    /// `pending_cycles` have run since the PPU was last stepped, so the access
+    /// happened that far into the current mode.
+    pub fn corrupt_oam(&mut self, pending_cycles: u32) {
+        match self.mode {
+            PpuMode::InOam if self.control.lcd_on => (),
+            _ => return,
+        }
+        let row = match oam_scan_row(self.cycles + pending_cycles) {
+            Some(row) => row,
+            None => return,
+        };
+        let word = |oam: &[u8], offset: usize| (oam[offset + 1] as u16) << 8 | oam[offset] as u16;
+        let current = row * 8;
+        let previous = current - 8;
+        let a = word(&self.oam, current);
+        let b = word(&self.oam, previous);
+        let c = word(&self.oam, previous + 4);
+        let corrupted = ((a ^ c) & (b ^ c)) ^ c;
+        self.oam[current] = (corrupted & 0xFF) as u8;
+        self.oam[current + 1] = (corrupted >> 8) as u8;
+        for i in 2..8 {
+            self.oam[current + i] = self.oam[previous + i];
+        }
+    }
+
     pub fn control_from_byte(&mut self, byte: u8) {
         self.control = PpuControl::from_byte(byte);
         if !self.control.lcd_on {
@@ -309,18 +364,27 @@ impl Ppu {
         self.current_line = 0
     }
 
+    /// Whether a frame was completed since the last call.
+    pub fn take_frame_done(&mut self) -> bool {
+        std::mem::replace(&mut self.frame_done, false)
+    }
+
     pub fn framebuffer(&self) -> &[u8] {
         if self.control.lcd_on { &self.framebuffer }
         else { &LCD_OFF_FRAMEBUFFER }
     }
 
-    pub fn palette_from_byte(byte: u8) -> [(u8, u8, u8); 4] {
-        let decode_color = |b| match b {
-            0 => COLOR0,
-            1 => COLOR1,
-            2 => COLOR2,
-            3 => COLOR3,
-            _ => unreachable!("Invalid color code"),
+    /// The shade, 0-3, of each pixel on screen, whatever colours the palette shows them in.
+    pub fn shades(&self) -> &[u8] {
+        if self.control.lcd_on { &self.shades }
+        else { &LCD_OFF_SHADES }
+    }
+
+    /// The colours a palette register value maps the color numbers 0-3 to.
+    pub fn palette_from_byte(shades: &Shades, byte: u8) -> [(u8, u8, u8); 4] {
+        let decode_color = |b: u8| {
+            debug_assert!((0..4).contains(&b), "Invalid color code");
+            shades[b as usize]
         };
         [decode_color(byte >> 0 & 0b11),
          decode_color(byte >> 2 & 0b11),
@@ -328,6 +392,29 @@ impl Ppu {
          decode_color(byte >> 6 & 0b11)]
     }
 
+    pub fn bg_palette_from_byte(&mut self, byte: u8) {
+        self.bgp = byte;
+        self.palette = Self::palette_from_byte(&self.colors.bg, byte);
+    }
+
+    pub fn obj_palette0_from_byte(&mut self, byte: u8) {
+        self.obp0 = byte;
+        self.obj_palette0 = Self::palette_from_byte(&self.colors.obj0, byte);
+    }
+
+    pub fn obj_palette1_from_byte(&mut self, byte: u8) {
+        self.obp1 = byte;
+        self.obj_palette1 = Self::palette_from_byte(&self.colors.obj1, byte);
+    }
+
+    /// Change the shades games are shown in. The frame being drawn changes colour from the current line on.
+    pub fn set_colors(&mut self, colors: Palette) {
+        self.colors = colors;
+        self.bg_palette_from_byte(self.bgp);
+        self.obj_palette0_from_byte(self.obp0);
+        self.obj_palette1_from_byte(self.obp1);
+    }
+
     pub fn stat_as_byte(&self) -> u8 {
         let mut byte: u8 = match self.mode {
             PpuMode::HBlank => 0,
@@ -343,10 +430,147 @@ impl Ppu {
         byte
     }
 
-    pub fn stat_from_byte(&mut self, byte: u8) {
+    pub fn stat_from_byte(&mut self, byte: u8, interrupts: &mut InterruptQueue) {
+        // On DMG-family models, any write to STAT during HBlank or VBlank
+        // briefly enables every STAT source, which raises an interrupt.
+        if self.model.has_stat_write_bug() && self.control.lcd_on {
+            match self.mode {
+                PpuMode::HBlank | PpuMode::VBlank => interrupts.request_interrupt(Interrupt::Lcd),
+                _ => (),
+            }
+        }
         self.interrupt_enabled[0] = byte & 1 << 3 != 0;
         self.interrupt_enabled[1] = byte & 1 << 4 != 0;
         self.interrupt_enabled[2] = byte & 1 << 5 != 0;
         self.interrupt_enabled[3] = byte & 1 << 6 != 0;
     }
 }
+
+/// The shade a palette register value maps color number `color_id` to.
+fn shade(register: u8, color_id: u8) -> u8 {
+    register >> (color_id * 2) & 0b11
+}
+
+/// The row of OAM affected by the corruption bug `cycles` cycles into mode 2.
+///
+/// Mode 2 lasts 80 dots and reads one 8-byte row of OAM every 4 dots, which is one of
+/// the cycles counted here, so the row is the number of cycles into the mode.
+/// The first row is never corrupted, and once the scan is over, nothing is.
+fn oam_scan_row(cycles: u32) -> Option<usize> {
+    Some(cycles as usize).filter(|&row| row != 0 && row < OAM_SIZE / 8)
+}
+
+impl SaveState for Ppu {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        w.write_all(&self.framebuffer)?;
+        w.write_all(&self.shades)?;
+        savestate::write_u32(w, self.cycles)?;
+        savestate::write_u8(w, self.control.as_byte())?;
+        savestate::write_u8(w, match self.mode {
+            PpuMode::HBlank => 0,
+            PpuMode::VBlank => 1,
+            PpuMode::InOam => 2,
+            PpuMode::InVram => 3,
+        })?;
+        for &enabled in self.interrupt_enabled.iter() {
+            savestate::write_bool(w, enabled)?;
+        }
+        w.write_all(&self.vram)?;
+        w.write_all(&self.oam)?;
+        w.write_all(&[self.bgp, self.obp0, self.obp1])?;
+        w.write_all(&[self.current_line, self.lyc, self.scroll_y, self.scroll_x])
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        r.read_exact(&mut self.framebuffer)?;
+        r.read_exact(&mut self.shades)?;
+        self.cycles = savestate::read_u32(r)?;
+        self.control = PpuControl::from_byte(savestate::read_u8(r)?);
+        self.mode = match savestate::read_u8(r)? {
+            0 => PpuMode::HBlank,
+            1 => PpuMode::VBlank,
+            2 => PpuMode::InOam,
+            3 => PpuMode::InVram,
+            m => return Err(savestate::invalid_data(&format!("invalid PPU mode {}", m))),
+        };
+        for enabled in self.interrupt_enabled.iter_mut() {
+            *enabled = savestate::read_bool(r)?;
+        }
+        r.read_exact(&mut self.vram)?;
+        r.read_exact(&mut self.oam)?;
+        self.bg_palette_from_byte(savestate::read_u8(r)?);
+        self.obj_palette0_from_byte(savestate::read_u8(r)?);
+        self.obj_palette1_from_byte(savestate::read_u8(r)?);
+        self.current_line = savestate::read_u8(r)?;
+        self.lyc = savestate::read_u8(r)?;
+        self.scroll_y = savestate::read_u8(r)?;
+        self.scroll_x = savestate::read_u8(r)?;
+        self.frame_done = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oam_scan_row_follows_mode_2() {
+        assert_eq!(oam_scan_row(0), None);
+        assert_eq!(oam_scan_row(1), Some(1));
+        assert_eq!(oam_scan_row(7), Some(7));
+        assert_eq!(oam_scan_row(OAM_ACCESS_CYCLES - 1), Some(19));
+        assert_eq!(oam_scan_row(OAM_ACCESS_CYCLES), None);
+    }
+
+    #[test]
+    fn corrupts_the_row_being_scanned() {
+        let mut ppu = Ppu::new(Model::Dmg);
+        ppu.control_from_byte(0x91);
+        for (i, byte) in ppu.oam.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        // The PPU is 3 cycles into mode 2 and 2 more have passed since it was stepped
+        ppu.cycles = 3;
+        ppu.corrupt_oam(2);
+
+        let (a, b, c) = (0x2928u16, 0x2120u16, 0x2524u16);
+        let corrupted = ((a ^ c) & (b ^ c)) ^ c;
+        assert_eq!(&ppu.oam[40..42], &corrupted.to_le_bytes());
+        assert_eq!(&ppu.oam[42..48], &[34, 35, 36, 37, 38, 39]);
+        assert!(ppu.oam[..40].iter().enumerate().all(|(i, &byte)| byte == i as u8));
+        assert!(ppu.oam[48..].iter().enumerate().all(|(i, &byte)| byte == (i + 48) as u8));
+    }
+
+    #[test]
+    fn leaves_oam_alone_outside_the_scan() {
+        let mut ppu = Ppu::new(Model::Dmg);
+        ppu.control_from_byte(0x91);
+        for (i, byte) in ppu.oam.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        ppu.corrupt_oam(0);
+        ppu.corrupt_oam(OAM_ACCESS_CYCLES);
+        ppu.mode = PpuMode::InVram;
+        ppu.corrupt_oam(5);
+        assert!(ppu.oam.iter().enumerate().all(|(i, &byte)| byte == i as u8));
+    }
+
+    #[test]
+    fn frames_take_70224_dots() {
+        let mut ppu = Ppu::new(Model::Dmg);
+        let mut interrupts = InterruptQueue::new();
+        ppu.control_from_byte(0x91);
+        let mut cycles_to_vblank = || {
+            let mut cycles = 0;
+            while !ppu.take_frame_done() {
+                ppu.step(1, &mut interrupts);
+                cycles += 1;
+            }
+            cycles
+        };
+        cycles_to_vblank();
+        assert_eq!(cycles_to_vblank(), FRAME_CYCLES);
+        assert_eq!(FRAME_CYCLES * 4, 70224);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+use crate::emulator::Emulator;
+use crate::savestate::SaveState;
+
+/// Ring buffer of past machine states for stepping backwards in time.
+///
+/// Only the most recent snapshot is kept in full. Every older snapshot is stored
+/// as the difference against the one that followed it, XORed and run-length encoded,
+/// which is tiny since most of memory doesn't change between two frames.
+/// Rewinding walks this chain backwards from the newest snapshot, so when the buffer
+/// grows past its size limit the oldest deltas can simply be dropped.
+pub struct Rewind {
+    interval: u32,
+    frames_since_capture: u32,
+    max_bytes: usize,
+    used_bytes: usize,
+    current: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    /// Capture a snapshot every `interval` frames, keeping at most `max_bytes` of history.
+    pub fn new(interval: u32, max_bytes: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            frames_since_capture: 0,
+            max_bytes,
+            used_bytes: 0,
+            current: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Call once at the end of every emulated frame.
+    pub fn end_frame(&mut self, cpu: &Cpu, emulator: &Emulator) {
+        self.frames_since_capture += 1;
+        if self.frames_since_capture >= self.interval {
+            self.frames_since_capture = 0;
+            self.capture(cpu, emulator);
+        }
+    }
+
+    pub fn capture(&mut self, cpu: &Cpu, emulator: &Emulator) {
+        let state = snapshot(cpu, emulator);
+        if let Some(previous) = self.current.take() {
+            let delta = encode_delta(&previous, &state);
+            self.used_bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.current = Some(state);
+
+        while self.used_bytes > self.max_bytes {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used_bytes -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Restore the snapshot preceding the current one.
+    /// Returns false once the oldest remembered state has been reached.
+    pub fn step_back(&mut self, cpu: &mut Cpu, emulator: &mut Emulator) -> bool {
+        let (current, delta) = match (self.current.as_mut(), self.deltas.pop_back()) {
+            (Some(current), Some(delta)) => (current, delta),
+            _ => return false,
+        };
+        self.used_bytes -= delta.len();
+        apply_delta(current, &delta);
+        self.frames_since_capture = 0;
+
+        let mut src = &current[..];
+        cpu.load_state(&mut src)
+            .and_then(|_| emulator.load_state(&mut src))
+            .expect("rewind snapshot should always be loadable");
+        true
+    }
+
+    /// Number of snapshots that can still be stepped back through.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Memory currently used by the history, not counting the newest full snapshot.
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+        self.used_bytes = 0;
+        self.frames_since_capture = 0;
+    }
+}
+
+fn snapshot(cpu: &Cpu, emulator: &Emulator) -> Vec<u8> {
+    let mut state = Vec::new();
+    cpu.save_state(&mut state)
+        .and_then(|_| emulator.save_state(&mut state))
+        .expect("writing to a Vec can't fail");
+    state
+}
+
+/// Encode `old XOR new` as a sequence of `(zero run, literal length, literal bytes)` records,
+/// with both lengths stored as LEB128 varints.
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    debug_assert_eq!(old.len(), new.len());
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < old.len() {
+        let zeros_start = i;
+        while i < old.len() && old[i] == new[i] {
+            i += 1;
+        }
+        let literal_start = i;
+        while i < old.len() && old[i] != new[i] {
+            i += 1;
+        }
+        write_varint(&mut out, literal_start - zeros_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend(old[literal_start..i].iter().zip(&new[literal_start..i]).map(|(a, b)| a ^ b));
+    }
+    out
+}
+
+/// Apply a delta produced by `encode_delta` in place. Since XOR is its own inverse,
+/// this turns either side of the delta into the other.
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut src = delta;
+    while !src.is_empty() {
+        pos += read_varint(&mut src);
+        let len = read_varint(&mut src);
+        for (byte, diff) in state[pos..pos + len].iter_mut().zip(&src[..len]) {
+            *byte ^= diff;
+        }
+        src = &src[len..];
+        pos += len;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(src: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = src[0];
+        *src = &src[1..];
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::GameBoy;
+    use crate::testing::input_gameboy;
+
+    fn state(gb: &GameBoy) -> Vec<u8> {
+        snapshot(&gb.cpu, &gb.emulator)
+    }
+
+    #[test]
+    fn deltas_round_trip() {
+        let old: Vec<u8> = (0..1000).map(|i| (i * 7 % 256) as u8).collect();
+        let mut new = old.clone();
+        new[0] ^= 1;
+        // A change longer than a one-byte varint, and one at the very end
+        for byte in &mut new[300..600] {
+            *byte = byte.wrapping_add(3);
+        }
+        new[999] = !new[999];
+
+        let delta = encode_delta(&old, &new);
+        assert!(delta.len() < 320);
+        let mut state = old.clone();
+        apply_delta(&mut state, &delta);
+        assert_eq!(state, new);
+        apply_delta(&mut state, &delta);
+        assert_eq!(state, old);
+
+        // A single run of 1000 unchanged bytes
+        assert_eq!(encode_delta(&old, &old), [0xE8, 0x07, 0x00]);
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for &value in &[0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, 123_456_789] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            let mut src = &out[..];
+            assert_eq!(read_varint(&mut src), value);
+            assert!(src.is_empty());
+        }
+    }
+
+    #[test]
+    fn steps_back_through_captured_states() {
+        let mut gb = input_gameboy();
+        let mut rewind = Rewind::new(2, usize::MAX);
+        let mut captured = Vec::new();
+        for frame in 0..10u8 {
+            gb.set_buttons(frame % 16);
+            gb.run_frame();
+            rewind.end_frame(&gb.cpu, &gb.emulator);
+            if frame % 2 == 1 {
+                captured.push(state(&gb));
+            }
+        }
+        assert_eq!(rewind.len(), captured.len() - 1);
+
+        captured.pop();
+        while let Some(expected) = captured.pop() {
+            assert!(rewind.step_back(&mut gb.cpu, &mut gb.emulator));
+            assert!(state(&gb) == expected, "{} snapshots left", captured.len());
+        }
+        assert!(!rewind.step_back(&mut gb.cpu, &mut gb.emulator));
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn drops_oldest_history_over_limit() {
+        let mut gb = input_gameboy();
+        let mut rewind = Rewind::new(1, 0);
+        for _ in 0..5 {
+            gb.run_frame();
+            rewind.end_frame(&gb.cpu, &gb.emulator);
+        }
+        assert!(rewind.is_empty());
+        assert_eq!(rewind.used_bytes(), 0);
+
+        let mut rewind = Rewind::new(1, usize::MAX);
+        for _ in 0..5 {
+            gb.run_frame();
+            rewind.end_frame(&gb.cpu, &gb.emulator);
+        }
+        let limit = rewind.used_bytes() / 2;
+        let mut limited = Rewind::new(1, limit);
+        for _ in 0..5 {
+            gb.run_frame();
+            limited.end_frame(&gb.cpu, &gb.emulator);
+        }
+        assert!(limited.used_bytes() <= limit);
+        assert!(!limited.is_empty() && limited.len() < 4);
+    }
+}
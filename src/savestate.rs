@@ -0,0 +1,209 @@
+use std::io::{self, Read, Write};
+
+use crate::cpu::Cpu;
+use crate::emulator::Emulator;
+
+const MAGIC: &[u8; 8] = b"FEOXSAVE";
+/// Bump this whenever the layout of any saved component changes.
+pub const STATE_VERSION: u16 = 3;
+
+/// A component of the machine whose internal state can be saved and restored.
+///
+/// `load_state` must read back exactly what `save_state` wrote, in the same order.
+pub trait SaveState {
+    fn save_state(&self, w: &mut dyn Write) -> io::Result<()>;
+    fn load_state(&mut self, r: &mut dyn Read) -> io::Result<()>;
+}
+
+/// Write a full save state: a header identifying the format and the ROM,
+/// followed by the state of the CPU and the rest of the machine.
+pub fn save(cpu: &Cpu, emulator: &Emulator, w: &mut dyn Write) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    write_u16(w, STATE_VERSION)?;
+    write_u32(w, emulator.rom_checksum())?;
+    write_str(w, &emulator.model().to_string())?;
+    cpu.save_state(w)?;
+    emulator.save_state(w)
+}
+
+/// Restore a save state written by `save`.
+///
+/// The header is checked before anything is modified, so a state belonging to
+/// another ROM or model is rejected without touching the running machine.
+pub fn load(cpu: &mut Cpu, emulator: &mut Emulator, r: &mut dyn Read) -> io::Result<()> {
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a feox save state"));
+    }
+    let version = read_u16(r)?;
+    if version != STATE_VERSION {
+        return Err(invalid_data(&format!("unsupported save state version {} (expected {})",
+                                         version, STATE_VERSION)));
+    }
+    let checksum = read_u32(r)?;
+    if checksum != emulator.rom_checksum() {
+        return Err(invalid_data(&format!("save state is for a different ROM (checksum {:08X}, loaded ROM is {:08X})",
+                                         checksum, emulator.rom_checksum())));
+    }
+    let model = read_str(r)?;
+    if model != emulator.model().to_string() {
+        return Err(invalid_data(&format!("save state was made on model '{}', but running '{}'",
+                                         model, emulator.model())));
+    }
+
+    // Keep a copy of the current state so a truncated or corrupt file
+    // can't leave the machine half-restored
+    let mut backup = Vec::new();
+    cpu.save_state(&mut backup)?;
+    emulator.save_state(&mut backup)?;
+
+    let result = cpu.load_state(r).and_then(|_| emulator.load_state(r));
+    if result.is_err() {
+        let mut src = &backup[..];
+        cpu.load_state(&mut src)?;
+        emulator.load_state(&mut src)?;
+    }
+    result
+}
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+pub fn write_u8(w: &mut dyn Write, value: u8) -> io::Result<()> {
+    w.write_all(&[value])
+}
+
+pub fn write_bool(w: &mut dyn Write, value: bool) -> io::Result<()> {
+    write_u8(w, value as u8)
+}
+
+pub fn write_u16(w: &mut dyn Write, value: u16) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+pub fn write_u32(w: &mut dyn Write, value: u32) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+pub fn write_str(w: &mut dyn Write, value: &str) -> io::Result<()> {
+    write_u8(w, value.len() as u8)?;
+    w.write_all(value.as_bytes())
+}
+
+pub fn read_u8(r: &mut dyn Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+pub fn read_bool(r: &mut dyn Read) -> io::Result<bool> {
+    match read_u8(r)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        b => Err(invalid_data(&format!("invalid boolean {:#04X} in save state", b))),
+    }
+}
+
+pub fn read_u16(r: &mut dyn Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+pub fn read_u32(r: &mut dyn Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub fn read_str(r: &mut dyn Read) -> io::Result<String> {
+    let len = read_u8(r)? as usize;
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|_| invalid_data("invalid string in save state"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::GameBoy;
+    use crate::model::Model;
+    use crate::testing::{input_gameboy, input_rom};
+
+    fn run(gb: &mut GameBoy, frames: u8) -> Vec<u32> {
+        (0..frames).map(|frame| {
+            gb.set_buttons(frame % 16);
+            gb.run_frame();
+            crc32fast::hash(gb.framebuffer())
+        }).collect()
+    }
+
+    fn state(gb: &GameBoy) -> Vec<u8> {
+        let mut state = Vec::new();
+        gb.save_state(&mut state).unwrap();
+        state
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut gb = input_gameboy();
+        run(&mut gb, 5);
+        let saved = state(&gb);
+        let expected = run(&mut gb, 20);
+
+        // On the same machine after it has moved on, and on a fresh one
+        gb.load_state(&mut &saved[..]).unwrap();
+        assert!(state(&gb) == saved);
+        assert_eq!(run(&mut gb, 20), expected);
+
+        let mut gb = input_gameboy();
+        gb.load_state(&mut &saved[..]).unwrap();
+        assert_eq!(run(&mut gb, 20), expected);
+    }
+
+    #[test]
+    fn rejects_other_roms_and_models() {
+        let saved = state(&input_gameboy());
+
+        let mut rom = input_rom();
+        rom[0x7FFF] = 1;
+        let mut other_rom = GameBoy::new(Model::Dmg);
+        other_rom.load_rom(&rom).unwrap();
+        assert!(other_rom.load_state(&mut &saved[..]).is_err());
+
+        let mut other_model = GameBoy::new(Model::Mgb);
+        other_model.load_rom(&input_rom()).unwrap();
+        assert!(other_model.load_state(&mut &saved[..]).is_err());
+
+        let mut gb = input_gameboy();
+        let mut bad_magic = saved.clone();
+        bad_magic[0] = b'X';
+        assert!(gb.load_state(&mut &bad_magic[..]).is_err());
+        let mut bad_version = saved.clone();
+        bad_version[8..10].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert!(gb.load_state(&mut &bad_version[..]).is_err());
+    }
+
+    #[test]
+    fn truncated_state_leaves_machine_untouched() {
+        let mut gb = input_gameboy();
+        let saved = state(&gb);
+        run(&mut gb, 3);
+        let before = state(&gb);
+        let error = gb.load_state(&mut &saved[..saved.len() - 100]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(state(&gb) == before);
+    }
+
+    #[test]
+    fn rejects_invalid_booleans() {
+        assert!(read_bool(&mut &[1u8][..]).unwrap());
+        assert!(!read_bool(&mut &[0u8][..]).unwrap());
+        assert_eq!(read_bool(&mut &[2u8][..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let mut buffer = Vec::new();
+        write_str(&mut buffer, "dmg").unwrap();
+        assert_eq!(read_str(&mut &buffer[..]).unwrap(), "dmg");
+    }
+}
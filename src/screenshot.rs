@@ -0,0 +1,163 @@
+//! Saving the screen as a PNG image.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1A\n";
+
+/// Write `width` by `height` RGB24 pixels as a PNG image, losslessly.
+pub fn write_png(w: &mut dyn Write, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    assert_eq!(rgb.len(), width as usize * height as usize * 3, "image is not {}x{} RGB24", width, height);
+    w.write_all(PNG_SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, truecolour, deflate, no filtering, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(w, b"IHDR", &header)?;
+
+    // Each row starts with its filter type, 0 for none
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    for row in rgb.chunks(width as usize * 3) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    write_chunk(w, b"IDAT", &encoder.finish()?)?;
+    write_chunk(w, b"IEND", &[])
+}
+
+fn write_chunk(w: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    w.write_all(&crc.finalize().to_be_bytes())
+}
+
+/// Save an image to `path` as a PNG.
+pub fn save_png(path: &Path, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_png(&mut file, width, height, rgb)?;
+    file.flush()
+}
+
+/// A path for a new screenshot or recording of `rom_name` in `dir`, named after the
+/// current date and time in UTC, like `game-20240131-235959.png`, and numbered if
+/// one was already saved that second.
+pub fn new_path(dir: &Path, rom_name: &str, extension: &str) -> PathBuf {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time = seconds % 86400;
+    let stem = format!("{}-{:04}{:02}{:02}-{:02}{:02}{:02}", rom_name, year, month, day,
+                       time / 3600, time / 60 % 60, time % 60);
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut number = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", stem, number, extension));
+        number += 1;
+    }
+    path
+}
+
+/// The date `days` days after 1970-01-01 in the proleptic Gregorian calendar,
+/// using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(59), (1970, 3, 1));
+        // 2000 was a leap year, 1900 and 2100 aren't
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_753), (2024, 1, 31));
+        assert_eq!(civil_from_days(-25_508), (1900, 3, 1));
+        assert_eq!(civil_from_days(47_540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+    }
+
+    /// The chunks of a PNG file, as `(kind, data)`, checking their CRCs.
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert!(png.starts_with(PNG_SIGNATURE));
+        let mut chunks = Vec::new();
+        let mut rest = &png[PNG_SIGNATURE.len()..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = &rest[8 + length..12 + length];
+            let mut expected = crc32fast::Hasher::new();
+            expected.update(kind);
+            expected.update(data);
+            assert_eq!(crc, expected.finalize().to_be_bytes());
+            chunks.push(([kind[0], kind[1], kind[2], kind[3]], data.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn writes_png() {
+        let rgb: Vec<u8> = (0..3 * 2 * 3).map(|i| i as u8 * 10).collect();
+        let mut png = Vec::new();
+        write_png(&mut png, 3, 2, &rgb).unwrap();
+
+        let chunks = chunks(&png);
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+
+        let mut pixels = Vec::new();
+        ZlibDecoder::new(&chunks[1].1[..]).read_to_end(&mut pixels).unwrap();
+        let mut expected = vec![0];
+        expected.extend_from_slice(&rgb[..9]);
+        expected.push(0);
+        expected.extend_from_slice(&rgb[9..]);
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn numbers_paths_taken_that_second() {
+        let dir = std::env::temp_dir().join(format!("feox-screenshots-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = new_path(&dir, "game", "png");
+        fs::write(&first, b"").unwrap();
+        let second = new_path(&dir, "game", "png");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let name = first.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("game-") && name.ends_with(".png"), "{}", name);
+        assert_eq!(name.len(), "game-20240131-235959.png".len());
+        assert_ne!(first, second);
+        // Unless the clock ticked over between the two calls
+        if second.file_name().unwrap().to_str().unwrap().len() != name.len() {
+            assert_eq!(second, dir.join(name.replace(".png", "-2.png")));
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::savestate::invalid_data;
+
+/// A label from a symbol file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub bank: u16,
+    pub address: u16,
+    pub name: String,
+}
+
+/// Labels loaded from RGBDS or no$gmb `.sym` files.
+///
+/// Both formats have one `bank:address name` line per label, in hexadecimal,
+/// with `;` starting a comment. The same address can carry different labels in
+/// different banks, so lookups take the bank currently mapped at that address.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    /// Keyed by region, then bank, then address, so that a range lookup
+    /// never crosses into another region or bank
+    by_location: BTreeMap<(u8, u16, u16), Symbol>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(r: &mut dyn BufRead) -> io::Result<Self> {
+        let mut symbols = Self::new();
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let symbol = parse_line(line)
+                .ok_or_else(|| invalid_data(&format!("invalid symbol on line {}: '{}'", number + 1, line)))?;
+            symbols.insert(symbol);
+        }
+        Ok(symbols)
+    }
+
+    pub fn load_file(path: &Path) -> io::Result<Self> {
+        Self::load(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn insert(&mut self, symbol: Symbol) {
+        self.by_location.insert((region(symbol.address), symbol.bank, symbol.address), symbol);
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_location.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_location.is_empty()
+    }
+
+    /// The label at exactly `bank:address`.
+    pub fn get(&self, bank: u16, address: u16) -> Option<&Symbol> {
+        self.by_location.get(&(region(address), bank, address))
+    }
+
+    /// The closest label at or before `bank:address` in the same memory region,
+    /// with the offset of `address` from it.
+    pub fn nearest(&self, bank: u16, address: u16) -> Option<(&Symbol, u16)> {
+        let start = (region(address), bank, 0);
+        let end = (region(address), bank, address);
+        self.by_location.range(start..=end).next_back()
+            .map(|(_, symbol)| (symbol, address - symbol.address))
+    }
+
+    /// `address` as `Label` or `Label+$N` if a label precedes it, e.g. for disassembly.
+    pub fn describe(&self, bank: u16, address: u16) -> Option<String> {
+        self.nearest(bank, address).map(|(symbol, offset)| match offset {
+            0 => symbol.name.clone(),
+            _ => format!("{}+${:X}", symbol.name, offset),
+        })
+    }
+
+    /// Find a label by name.
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.by_location.values().find(|s| s.name == name)
+    }
+}
+
+/// The symbol file for a ROM: `game.sym` next to `game.gb`, if it exists.
+pub fn path_for_rom(rom_path: &Path) -> Option<PathBuf> {
+    Some(rom_path.with_extension("sym")).filter(|path| path.exists())
+}
+
+/// Memory regions that labels can't extend across.
+fn region(address: u16) -> u8 {
+    match address {
+        0x0000..=0x3FFF => 0,
+        0x4000..=0x7FFF => 1,
+        0x8000..=0x9FFF => 2,
+        0xA000..=0xBFFF => 3,
+        0xC000..=0xCFFF => 4,
+        0xD000..=0xDFFF => 5,
+        0xE000..=0xFDFF => 6,
+        0xFE00..=0xFEFF => 7,
+        0xFF00..=0xFF7F => 8,
+        0xFF80..=0xFFFF => 9,
+    }
+}
+
+fn parse_line(line: &str) -> Option<Symbol> {
+    let mut parts = line.split_whitespace();
+    let location = parts.next()?;
+    let name = parts.next()?.to_string();
+    let mut location = location.splitn(2, ':');
+    let bank = u16::from_str_radix(location.next()?, 16).ok()?;
+    let address = u16::from_str_radix(location.next()?, 16).ok()?;
+    Some(Symbol { bank, address, name })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Emulator;
+
+    fn load(text: &str) -> Symbols {
+        Symbols::load(&mut text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn parses_symbol_file() {
+        let symbols = load("; File generated by rgblink\n\
+                            00:0150 Main\n\
+                            01:4000 Bank1Code ; comment\n\
+                            02:4000 Bank2Code\n\
+                            \n\
+                            00:C000 wCounter\n");
+        assert_eq!(symbols.len(), 4);
+        assert_eq!(symbols.find("Main"), Some(&Symbol { bank: 0, address: 0x0150, name: "Main".to_string() }));
+        assert_eq!(symbols.get(2, 0x4000).map(|s| s.name.as_str()), Some("Bank2Code"));
+        assert_eq!(symbols.describe(1, 0x4010), Some("Bank1Code+$10".to_string()));
+        assert!(Symbols::load(&mut "00:01XY Bad".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn labels_stay_in_their_region() {
+        let symbols = load("00:3FF0 EndOfBank0\n00:C000 wStart\n");
+        assert_eq!(symbols.describe(1, 0x4000), None);
+        assert_eq!(symbols.describe(0, 0xC100), Some("wStart+$100".to_string()));
+        assert_eq!(symbols.describe(0, 0xD000), None);
+    }
+
+    #[test]
+    fn resolves_wramx_labels() {
+        // RGBDS puts WRAMX in bank 1 on the DMG
+        let symbols = load("01:D000 wPlayerX\n01:D001 wPlayerY\n");
+        let emulator = Emulator::default();
+        let bank = emulator.mapped_bank(0xD001);
+        assert_eq!(symbols.describe(bank, 0xD001), Some("wPlayerY".to_string()));
+        assert_eq!(symbols.describe(bank, 0xD002), Some("wPlayerY+$1".to_string()));
+        assert_eq!(symbols.get(emulator.mapped_bank(0xD000), 0xD000).map(|s| s.name.as_str()), Some("wPlayerX"));
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by the unit tests.
+
+use crate::gameboy::GameBoy;
+use crate::model::Model;
+
+/// A 32 KiB ROM that endlessly copies the d-pad state into the tiles the
+/// background is drawn with, so what's on screen depends on the input.
+pub fn input_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x114].copy_from_slice(&[
+        0x21, 0x00, 0x80, // LD HL,$8000
+        0x3E, 0x20,       // LD A,$20
+        0xE0, 0x00,       // LDH ($00),A
+        0xF0, 0x00,       // LDH A,($00)
+        0x22,             // LD (HL+),A
+        0x7C,             // LD A,H
+        0xFE, 0x88,       // CP $88
+        0x20, 0xF4,       // JR NZ,$0103
+        0x21, 0x00, 0x80, // LD HL,$8000
+        0x18, 0xEF,       // JR $0103
+    ]);
+    rom
+}
+
+/// A machine running `input_rom`, started past the boot ROM.
+pub fn input_gameboy() -> GameBoy {
+    let mut gb = GameBoy::new(Model::Dmg);
+    gb.load_rom(&input_rom()).unwrap();
+    gb.skip_boot();
+    gb
+}
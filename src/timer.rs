@@ -1,4 +1,5 @@
 use crate::interrupts::{Interrupt, InterruptQueue};
+use crate::savestate::{self, SaveState};
 
 const MODE0: u32 = 256;
 const MODE1: u32 = 4;
@@ -65,3 +66,29 @@ impl Timer {
         };
     }
 }
+
+impl SaveState for Timer {
+    fn save_state(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        savestate::write_u8(w, self.counter)?;
+        savestate::write_u8(w, self.modulo)?;
+        savestate::write_u8(w, self.divider)?;
+        savestate::write_bool(w, self.enabled)?;
+        savestate::write_u32(w, self.frequency)?;
+        savestate::write_u32(w, self.cycles)?;
+        savestate::write_u32(w, self.div_cycles)
+    }
+
+    fn load_state(&mut self, r: &mut dyn std::io::Read) -> std::io::Result<()> {
+        self.counter = savestate::read_u8(r)?;
+        self.modulo = savestate::read_u8(r)?;
+        self.divider = savestate::read_u8(r)?;
+        self.enabled = savestate::read_bool(r)?;
+        self.frequency = match savestate::read_u32(r)? {
+            f @ MODE0 | f @ MODE1 | f @ MODE2 | f @ MODE3 => f,
+            f => return Err(savestate::invalid_data(&format!("invalid timer frequency {}", f))),
+        };
+        self.cycles = savestate::read_u32(r)?;
+        self.div_cycles = savestate::read_u32(r)?;
+        Ok(())
+    }
+}
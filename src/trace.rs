@@ -0,0 +1,156 @@
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use crate::cpu::Cpu;
+use crate::emulator::Emulator;
+use crate::symbols::Symbols;
+
+/// Logs the CPU state before every instruction, one line each, in the format used
+/// by Gameboy Doctor so that traces can be diffed against other emulators:
+///
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+///
+/// With `show_symbols`, lines get the label of PC appended, e.g. ` ; Main+$3`.
+pub struct Trace {
+    out: Box<dyn Write>,
+    /// Only log instructions at these addresses
+    pub pc_range: Option<RangeInclusive<u16>>,
+    /// Only log once this many cycles have run since the trace started
+    pub start_cycle: u64,
+    pub show_symbols: bool,
+    cycles: u64,
+}
+
+impl Trace {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Self {
+            out,
+            pc_range: None,
+            start_cycle: 0,
+            show_symbols: false,
+            cycles: 0,
+        }
+    }
+
+    /// Log the instruction about to execute, if it passes the filters.
+    pub fn log(&mut self, cpu: &Cpu, emulator: &Emulator, symbols: &Symbols) -> io::Result<()> {
+        let regs = cpu.registers();
+        if self.cycles < self.start_cycle {
+            return Ok(());
+        }
+        if let Some(range) = &self.pc_range {
+            if !range.contains(&regs.pc) {
+                return Ok(());
+            }
+        }
+
+        let pcmem = |offset| emulator.read_mapped(regs.pc.wrapping_add(offset));
+        write!(self.out, "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} \
+                          SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+               regs.a, regs.f, regs.b, regs.c, regs.d, regs.e, regs.h, regs.l,
+               regs.sp, regs.pc, pcmem(0), pcmem(1), pcmem(2), pcmem(3))?;
+        if self.show_symbols {
+            if let Some(label) = symbols.describe(emulator.mapped_bank(regs.pc), regs.pc) {
+                write!(self.out, " ; {}", label)?;
+            }
+        }
+        writeln!(self.out)
+    }
+
+    /// Account for cycles run since the last instruction was logged.
+    pub fn add_cycles(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+    }
+}
+
+/// Parse an address range written as `START-END` in hexadecimal, e.g. `0100-01FF`.
+pub fn parse_pc_range(s: &str) -> Result<RangeInclusive<u16>, String> {
+    let parse = |s: &str| u16::from_str_radix(s.trim_start_matches('$').trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid address '{}'", s));
+    match s.find('-') {
+        Some(i) => Ok(parse(&s[..i])?..=parse(&s[i + 1..])?),
+        None => Err(format!("expected a range like 0100-01FF, got '{}'", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::debugger::Register;
+    use crate::gameboy::GameBoy;
+    use crate::symbols::Symbol;
+    use crate::testing::input_gameboy;
+
+    /// A writer whose output can still be read after the trace has taken it.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(str::to_string).collect()
+        }
+    }
+
+    fn traced_gameboy(configure: impl FnOnce(&mut Trace)) -> (GameBoy, Output) {
+        let mut gb = input_gameboy();
+        let output = Output::default();
+        let mut trace = Trace::new(Box::new(output.clone()));
+        configure(&mut trace);
+        gb.trace = Some(trace);
+        (gb, output)
+    }
+
+    #[test]
+    fn logs_in_gameboy_doctor_format() {
+        let (mut gb, output) = traced_gameboy(|trace| trace.show_symbols = true);
+        for &(register, value) in &[(Register::AF, 0x01B0), (Register::BC, 0x0013), (Register::DE, 0x00D8),
+                                    (Register::HL, 0x014D), (Register::SP, 0xFFFE), (Register::PC, 0x0100)] {
+            register.write(&mut gb.cpu, value);
+        }
+        gb.symbols.insert(Symbol { bank: 0, address: 0x0100, name: "Start".to_string() });
+        gb.step();
+        gb.step();
+
+        assert_eq!(output.lines(), [
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:21,00,80,3E ; Start",
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:80 L:00 SP:FFFE PC:0103 PCMEM:3E,20,E0,00 ; Start+$3",
+        ]);
+    }
+
+    #[test]
+    fn filters_by_pc_and_start_cycle() {
+        let (mut gb, output) = traced_gameboy(|trace| trace.pc_range = Some(0x0103..=0x0104));
+        gb.run_cycles(200);
+        let lines = output.lines();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.contains(" PC:0103 ")));
+
+        // LD HL,$8000 takes 3 cycles, so it's the only instruction skipped
+        let (mut gb, output) = traced_gameboy(|trace| trace.start_cycle = 3);
+        gb.step();
+        gb.step();
+        assert_eq!(output.lines().len(), 1);
+        assert!(output.lines()[0].contains(" PC:0103 "));
+    }
+
+    #[test]
+    fn parses_pc_ranges() {
+        assert_eq!(parse_pc_range("0100-01FF"), Ok(0x0100..=0x01FF));
+        assert_eq!(parse_pc_range("$4000-0x7fff"), Ok(0x4000..=0x7FFF));
+        assert!(parse_pc_range("0100").is_err());
+        assert!(parse_pc_range("0100-10000").is_err());
+    }
+}
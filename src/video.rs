@@ -0,0 +1,222 @@
+//! Recording gameplay as an uncompressed Y4M video with a WAV audio track next to it.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::gameboy::CLOCK_SPEED;
+use crate::ppu::FRAME_CYCLES;
+
+pub const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+const BYTES_PER_SAMPLE: u16 = 2;
+const WAV_HEADER_SIZE: u32 = 44;
+
+/// Writes frames to `name.y4m` and sound to `name.wav`.
+///
+/// Timing comes from the emulated cycles rather than the wall clock, so recordings play
+/// at normal speed however fast the game ran: a frame is written for every frame's worth
+/// of cycles, at exactly the DMG's frame rate, and the audio is as long as the cycles run.
+/// Nothing produces sound yet, so the audio track is silent.
+pub struct VideoRecorder {
+    video: BufWriter<File>,
+    audio: BufWriter<File>,
+    video_path: PathBuf,
+    cycles: u64,
+    frames: u64,
+    samples: u64,
+    /// The last frame converted to YUV, kept to avoid reallocating it every frame
+    yuv: Vec<u8>,
+    /// Zeros, grown to the most audio a frame has needed
+    silence: Vec<u8>,
+}
+
+impl VideoRecorder {
+    /// Start recording to `path`, with the audio going next to it with a `.wav` extension.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut video = BufWriter::new(File::create(path)?);
+        // 4:4:4 so no colour is lost to chroma subsampling, at 1048576/17556 = 59.7275 frames per second
+        writeln!(video, "YUV4MPEG2 W160 H144 F{}:{} Ip A1:1 C444", CLOCK_SPEED, FRAME_CYCLES)?;
+
+        let mut audio = BufWriter::new(File::create(path.with_extension("wav"))?);
+        write_wav_header(&mut audio, 0)?;
+
+        Ok(Self {
+            video,
+            audio,
+            video_path: path.to_path_buf(),
+            cycles: 0,
+            frames: 0,
+            samples: 0,
+            yuv: Vec::new(),
+            silence: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.video_path
+    }
+
+    /// Frames written so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Record the 160x144 RGB24 `frame` produced by running `cycles` cycles.
+    ///
+    /// The frame is written as many times as needed to keep the video in step with
+    /// the emulated time, which is usually once; it's skipped if the time since the
+    /// last frame was too short, as when the LCD is turned on mid-frame.
+    pub fn record_frame(&mut self, frame: &[u8], cycles: u32) -> io::Result<()> {
+        self.cycles += cycles as u64;
+        if self.frames * (FRAME_CYCLES as u64) < self.cycles {
+            rgb_to_yuv444(frame, &mut self.yuv);
+            while self.frames * (FRAME_CYCLES as u64) < self.cycles {
+                self.video.write_all(b"FRAME\n")?;
+                self.video.write_all(&self.yuv)?;
+                self.frames += 1;
+            }
+        }
+
+        let samples = self.cycles * SAMPLE_RATE as u64 / CLOCK_SPEED as u64;
+        let size = ((samples - self.samples) * (CHANNELS * BYTES_PER_SAMPLE) as u64) as usize;
+        if self.silence.len() < size {
+            self.silence.resize(size, 0);
+        }
+        self.audio.write_all(&self.silence[..size])?;
+        self.samples = samples;
+        Ok(())
+    }
+
+    /// Fill in the sizes the WAV header couldn't know until the end and close both files.
+    ///
+    /// A WAV file can't describe more than 4 GiB of audio, about 6.7 hours, so a longer
+    /// track is marked as the most it can hold and an error returned; the file still plays.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.video.flush()?;
+        let data_size = self.samples * (CHANNELS * BYTES_PER_SAMPLE) as u64;
+        let max_size = max_wav_data_size();
+        self.audio.seek(SeekFrom::Start(0))?;
+        write_wav_header(&mut self.audio, data_size.min(max_size as u64) as u32)?;
+        self.audio.flush()?;
+        if data_size > max_size as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "audio track is too long for a WAV file, only its start will play"));
+        }
+        Ok(())
+    }
+}
+
+/// The most sample data a WAV header can describe: the RIFF size is 32 bits and covers
+/// the rest of the header, and the data has to be a whole number of samples.
+fn max_wav_data_size() -> u32 {
+    let block_align = (CHANNELS * BYTES_PER_SAMPLE) as u32;
+    (u32::MAX - (WAV_HEADER_SIZE - 8)) / block_align * block_align
+}
+
+/// A header for 16-bit stereo PCM at `SAMPLE_RATE`, followed by `data_size` bytes of samples.
+fn write_wav_header(w: &mut dyn Write, data_size: u32) -> io::Result<()> {
+    let block_align = CHANNELS * BYTES_PER_SAMPLE;
+    w.write_all(b"RIFF")?;
+    w.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    w.write_all(b"WAVEfmt ")?;
+    w.write_all(&16u32.to_le_bytes())?;
+    // PCM
+    w.write_all(&1u16.to_le_bytes())?;
+    w.write_all(&CHANNELS.to_le_bytes())?;
+    w.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    w.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    w.write_all(&block_align.to_le_bytes())?;
+    w.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+    w.write_all(b"data")?;
+    w.write_all(&data_size.to_le_bytes())
+}
+
+/// Convert RGB24 pixels to the Y, Cb and Cr planes of a 4:4:4 frame in `yuv`, using
+/// the limited range BT.601 coefficients video players assume for Y4M.
+fn rgb_to_yuv444(rgb: &[u8], yuv: &mut Vec<u8>) {
+    let pixels = rgb.len() / 3;
+    yuv.resize(pixels * 3, 0);
+    for (i, pixel) in rgb.chunks_exact(3).enumerate() {
+        let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+        yuv[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+        yuv[pixels + i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+        yuv[pixels * 2 + i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const FRAME_SIZE: usize = 160 * 144 * 3;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("feox-{}-{}.y4m", name, std::process::id()))
+    }
+
+    fn le_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    #[test]
+    fn converts_to_limited_range_yuv() {
+        let mut yuv = Vec::new();
+        rgb_to_yuv444(&[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00], &mut yuv);
+        // Y plane, then Cb, then Cr
+        assert_eq!(yuv, [235, 16, 82, 128, 128, 90, 128, 128, 240]);
+    }
+
+    #[test]
+    fn records_frames_in_emulated_time() {
+        let path = temp_path("frames");
+        let mut recorder = VideoRecorder::create(&path).unwrap();
+        let frame = vec![0xFF; FRAME_SIZE];
+        // The first frame is short, like the one the boot ROM skip leaves
+        recorder.record_frame(&frame, FRAME_CYCLES - 100).unwrap();
+        // Too short to need another frame, as when the LCD is turned on mid-frame
+        recorder.record_frame(&frame, 100).unwrap();
+        assert_eq!(recorder.frames(), 1);
+        // Twice as long, e.g. while the LCD was off: the frame is repeated
+        recorder.record_frame(&frame, FRAME_CYCLES * 2).unwrap();
+        assert_eq!(recorder.frames(), 3);
+        recorder.finish().unwrap();
+
+        let video = fs::read(&path).unwrap();
+        let audio = fs::read(path.with_extension("wav")).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("wav")).unwrap();
+
+        let header = b"YUV4MPEG2 W160 H144 F1048576:17556 Ip A1:1 C444\n";
+        assert!(video.starts_with(header));
+        assert_eq!(video.len(), header.len() + 3 * (b"FRAME\n".len() + FRAME_SIZE));
+
+        let cycles = FRAME_CYCLES as u64 * 3;
+        let data_size = (cycles * SAMPLE_RATE as u64 / CLOCK_SPEED as u64 * 4) as u32;
+        assert_eq!(&audio[..4], b"RIFF");
+        assert_eq!(le_u32(&audio, 4), 36 + data_size);
+        assert_eq!(&audio[8..16], b"WAVEfmt ");
+        assert_eq!(le_u32(&audio, 24), SAMPLE_RATE);
+        assert_eq!(&audio[36..40], b"data");
+        assert_eq!(le_u32(&audio, 40), data_size);
+        assert_eq!(audio.len(), WAV_HEADER_SIZE as usize + data_size as usize);
+        assert!(audio[44..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn overlong_audio_keeps_a_valid_header() {
+        let path = temp_path("overlong");
+        let mut recorder = VideoRecorder::create(&path).unwrap();
+        recorder.samples = 1 << 31;
+        assert!(recorder.finish().is_err());
+
+        let audio = fs::read(path.with_extension("wav")).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("wav")).unwrap();
+        let data_size = le_u32(&audio, 40);
+        assert_eq!(data_size, max_wav_data_size());
+        assert_eq!(data_size % 4, 0);
+        assert_eq!(le_u32(&audio, 4), u32::MAX - 3);
+    }
+}
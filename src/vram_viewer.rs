@@ -0,0 +1,143 @@
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use crate::ppu::{Ppu, TILE_COUNT};
+
+const GAP: usize = 4;
+const TILES_PER_ROW: usize = 16;
+const TILE_SHEET_WIDTH: usize = TILES_PER_ROW * 8;
+const TILE_SHEET_HEIGHT: usize = TILE_COUNT / TILES_PER_ROW * 8;
+const MAP_SIZE: usize = 256;
+const SPRITES_PER_ROW: usize = 16;
+
+const WIDTH: usize = TILE_SHEET_WIDTH + GAP + MAP_SIZE + GAP + MAP_SIZE;
+const HEIGHT: usize = MAP_SIZE;
+
+const BACKGROUND: (u8, u8, u8) = (0x40, 0x40, 0x40);
+const VIEWPORT: (u8, u8, u8) = (0xFF, 0x00, 0x00);
+
+/// A second window showing the contents of VRAM and OAM.
+///
+/// From left to right: the tile data sheet with the sprites in OAM below it,
+/// then the tile maps at $9800 and $9C00, with the visible part of the
+/// background outlined.
+pub struct VramViewer {
+    canvas: Canvas<Window>,
+    texture: Texture,
+    pixels: Vec<u8>,
+}
+
+impl VramViewer {
+    pub fn new(context: &sdl2::Sdl) -> Result<Self, String> {
+        let video_subsystem = context.video()?;
+        let window = video_subsystem.window("feox - VRAM", WIDTH as u32 * 2, HEIGHT as u32 * 2)
+            .resizable()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window.into_canvas().build()
+            .map_err(|e| e.to_string())?;
+        let texture = canvas.texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, WIDTH as u32, HEIGHT as u32)
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            canvas,
+            texture,
+            pixels: vec![0; WIDTH * HEIGHT * 3],
+        })
+    }
+
+    /// Used to tell which window SDL window events are meant for.
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    pub fn update(&mut self, ppu: &Ppu) -> Result<(), String> {
+        self.draw(ppu);
+
+        self.texture.update(None, &self.pixels, 3 * WIDTH)
+            .map_err(|e| e.to_string())?;
+        self.canvas.copy(&self.texture, None, None)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    fn draw(&mut self, ppu: &Ppu) {
+        for pixel in self.pixels.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&[BACKGROUND.0, BACKGROUND.1, BACKGROUND.2]);
+        }
+
+        for index in 0..TILE_COUNT {
+            let x = index % TILES_PER_ROW * 8;
+            let y = index / TILES_PER_ROW * 8;
+            self.draw_tile(ppu, index, x, y, &ppu.palette, false, false, false);
+        }
+
+        let sprites_y = TILE_SHEET_HEIGHT + GAP;
+        let height = ppu.sprite_height() as usize;
+        for sprite in ppu.sprites() {
+            let x = sprite.index as usize % SPRITES_PER_ROW * 8;
+            let y = sprites_y + sprite.index as usize / SPRITES_PER_ROW * 16;
+            let palette = if sprite.palette1 { &ppu.obj_palette1 } else { &ppu.obj_palette0 };
+            if height == 16 {
+                // The top tile is the even one, unless the sprite is flipped vertically
+                let (top, bottom) = if sprite.flip_y { (1, 0) } else { (0, 1) };
+                let tile = sprite.tile as usize & !1;
+                self.draw_tile(ppu, tile | top, x, y, palette, sprite.flip_x, sprite.flip_y, true);
+                self.draw_tile(ppu, tile | bottom, x, y + 8, palette, sprite.flip_x, sprite.flip_y, true);
+            } else {
+                self.draw_tile(ppu, sprite.tile as usize, x, y, palette, sprite.flip_x, sprite.flip_y, true);
+            }
+        }
+
+        for map in 0..2 {
+            let map_x = TILE_SHEET_WIDTH + GAP + map * (MAP_SIZE + GAP);
+            for (row, tiles) in ppu.tile_map(map).iter().enumerate() {
+                for (column, &tile) in tiles.iter().enumerate() {
+                    let index = ppu.bg_tile_index(tile);
+                    self.draw_tile(ppu, index, map_x + column * 8, row * 8, &ppu.palette, false, false, false);
+                }
+            }
+        }
+
+        let bg_map_x = TILE_SHEET_WIDTH + GAP + ppu.bg_map() * (MAP_SIZE + GAP);
+        self.draw_viewport(bg_map_x, ppu.scroll_x as usize, ppu.scroll_y as usize);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_tile(&mut self, ppu: &Ppu, index: usize, x: usize, y: usize, palette: &[(u8, u8, u8); 4],
+                 flip_x: bool, flip_y: bool, transparent: bool) {
+        let pixels = ppu.tile(index);
+        for (row, colors) in pixels.iter().enumerate() {
+            for (column, &color) in colors.iter().enumerate() {
+                // Color 0 is transparent for sprites
+                if transparent && color == 0 {
+                    continue;
+                }
+                let dx = if flip_x { 7 - column } else { column };
+                let dy = if flip_y { 7 - row } else { row };
+                self.set_pixel(x + dx, y + dy, palette[color as usize]);
+            }
+        }
+    }
+
+    /// Outline the 160x144 area of a 256x256 map starting at the scroll position, wrapping around.
+    fn draw_viewport(&mut self, map_x: usize, scroll_x: usize, scroll_y: usize) {
+        for i in 0..160 {
+            let x = map_x + (scroll_x + i) % MAP_SIZE;
+            self.set_pixel(x, scroll_y, VIEWPORT);
+            self.set_pixel(x, (scroll_y + 143) % MAP_SIZE, VIEWPORT);
+        }
+        for i in 0..144 {
+            let y = (scroll_y + i) % MAP_SIZE;
+            self.set_pixel(map_x + scroll_x, y, VIEWPORT);
+            self.set_pixel(map_x + (scroll_x + 159) % MAP_SIZE, y, VIEWPORT);
+        }
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, (r, g, b): (u8, u8, u8)) {
+        let i = (y * WIDTH + x) * 3;
+        self.pixels[i..i + 3].copy_from_slice(&[r, g, b]);
+    }
+}